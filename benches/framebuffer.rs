@@ -0,0 +1,93 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use chip8_rs::chip8::{Chip8, Framebuffer};
+
+// The previous one byte per pixel representation, kept here as the baseline
+struct BytePerPixel([u8; Chip8::DISPLAY_SIZE]);
+
+impl BytePerPixel {
+    fn draw(&mut self, ox: usize, oy: usize, sprite: &[u8]) -> bool {
+        let mut collision = false;
+        for (y, sprite_line) in sprite.iter().enumerate() {
+            for x in 0..8 {
+                let sx = (ox + x) & 63;
+                let sy = (oy + y) & 31;
+                let px = if (sprite_line >> (7 - x)) & 0x01 == 1 { 255 } else { 0 };
+                let pixel = &mut self.0[sy * Chip8::DISPLAY_WIDTH + sx];
+                collision |= px != 0 && *pixel != 0;
+                *pixel ^= px;
+            }
+        }
+        collision
+    }
+}
+
+const SPRITE: [u8; 15] = [0x3c, 0x42, 0x81, 0xa5, 0x81, 0x99, 0x42, 0x3c, 0xff, 0x00, 0xff, 0x18, 0x24, 0x42, 0x81];
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw 15 row sprite");
+    group.bench_function("byte per pixel", |b| {
+        let mut display = BytePerPixel([0; Chip8::DISPLAY_SIZE]);
+        let mut x = 0;
+        b.iter(|| {
+            x = (x + 7) & 63;
+            black_box(display.draw(x, x / 2, black_box(&SPRITE)))
+        });
+    });
+    group.bench_function("packed", |b| {
+        let mut display = Framebuffer::new(Chip8::DISPLAY_WIDTH, Chip8::DISPLAY_HEIGHT);
+        let mut x = 0;
+        b.iter(|| {
+            x = (x + 7) & 63;
+            black_box(display.xor_sprite(0, x, x / 2, black_box(&SPRITE)))
+        });
+    });
+    group.finish();
+}
+
+fn clear(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clear");
+    group.bench_function("byte per pixel", |b| {
+        let mut display = BytePerPixel([255; Chip8::DISPLAY_SIZE]);
+        b.iter(|| black_box(&mut display).0 = [0; Chip8::DISPLAY_SIZE]);
+    });
+    group.bench_function("packed", |b| {
+        let mut display = Framebuffer::new(Chip8::DISPLAY_WIDTH, Chip8::DISPLAY_HEIGHT);
+        b.iter(|| black_box(&mut display).clear());
+    });
+    group.finish();
+}
+
+fn upload(c: &mut Criterion) {
+    // What a renderer pays to get one byte per pixel for a texture upload
+    let mut group = c.benchmark_group("prepare upload");
+    let mut out = vec![0; Chip8::DISPLAY_SIZE];
+    group.bench_function("byte per pixel", |b| {
+        let display = BytePerPixel([255; Chip8::DISPLAY_SIZE]);
+        b.iter(|| out.copy_from_slice(&black_box(&display).0));
+    });
+    group.bench_function("packed", |b| {
+        let display = Framebuffer::new(Chip8::DISPLAY_WIDTH, Chip8::DISPLAY_HEIGHT);
+        b.iter(|| black_box(&display).unpack(&mut out));
+    });
+    group.finish();
+}
+
+fn draw_loop(c: &mut Criterion) {
+    // I = sprite, draw it, move right and loop forever
+    let mut program = vec![
+        0xa2, 0x0a,
+        0xd0, 0x1f,
+        0x70, 0x03,
+        0x12, 0x02,
+        0x00, 0x00,
+    ];
+    program.extend_from_slice(&SPRITE);
+
+    c.bench_function("interpreter draw loop, 1000 instructions", |b| {
+        let mut c8 = Chip8::new(&program);
+        b.iter(|| c8.step(black_box(1000)));
+    });
+}
+
+criterion_group!(benches, draw, clear, upload, draw_loop);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use chip8_rs::chip8::Chip8;
+
+const INSTRUCTIONS: u64 = 10_000;
+
+// Register arithmetic, skips and jumps without touching the display
+const ALU_LOOP: &[u8] = &[
+    0x60, 0x01,     // gp0 = 1
+    0x81, 0x04,     // gp1 += gp0
+    0x82, 0x15,     // gp2 -= gp1
+    0x83, 0x23,     // gp3 ^= gp2
+    0x31, 0x00,     // skip if gp1 == 0
+    0x12, 0x02,     // loop
+    0x12, 0x00,
+];
+
+// Moves a sprite across the screen
+const DRAW_LOOP: &[u8] = &[
+    0xa2, 0x0a,     // i = sprite
+    0xd0, 0x18,     // draw 8 rows at gp0, gp1
+    0x70, 0x03,     // gp0 += 3
+    0x12, 0x02,     // loop
+    0x00, 0x00,
+    0x3c, 0x42, 0x81, 0xa5, 0x81, 0x99, 0x42, 0x3c,
+];
+
+// Reads and writes memory through i
+const MEMORY_LOOP: &[u8] = &[
+    0xa3, 0x00,     // i = 0x300
+    0xf7, 0x55,     // store gp0..gp7
+    0xa3, 0x00,     // i = 0x300
+    0xf7, 0x65,     // load gp0..gp7
+    0x70, 0x01,     // gp0 += 1
+    0x12, 0x00,     // loop
+];
+
+fn synthetic(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instructions per second");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for (name, program) in [("alu", ALU_LOOP), ("draw", DRAW_LOOP), ("memory", MEMORY_LOOP)] {
+        group.bench_with_input(BenchmarkId::from_parameter(name), program, |b, program| {
+            let mut c8 = Chip8::new(program);
+            b.iter(|| c8.step(black_box(INSTRUCTIONS as usize)));
+        });
+    }
+    group.finish();
+}
+
+fn roms(c: &mut Criterion) {
+    // The bundled ROMs, run the way the frontend runs them: 10 instructions per 60Hz tick
+    let mut group = c.benchmark_group("rom instructions per second");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    let roms: [(&str, &[u8]); 3] = [
+        ("trip8", include_bytes!("../roms/trip8.rom")),
+        ("particle", include_bytes!("../roms/particle.rom")),
+        ("stars", include_bytes!("../roms/stars.rom")),
+    ];
+    for (name, rom) in roms {
+        group.bench_with_input(BenchmarkId::from_parameter(name), rom, |b, rom| {
+            let mut c8 = Chip8::new(rom);
+            b.iter(|| {
+                for _ in 0..INSTRUCTIONS / 10 {
+                    c8.tick_60hz();
+                    c8.step(10).unwrap();
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, synthetic, roms);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+/*
+ * Runs a ROM headlessly for a number of frames and prints the display.
+ *
+ *   cargo build -p chip8-ffi --release
+ *   cc ffi/examples/run_rom.c -Iffi/include -Ltarget/release -lchip8 -o run_rom
+ *   LD_LIBRARY_PATH=target/release ./run_rom roms/trip8.rom 300
+ */
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "chip8.h"
+
+#define INSTRUCTIONS_PER_FRAME 10
+
+static uint8_t *read_file(const char *path, size_t *len) {
+    FILE *f = fopen(path, "rb");
+    if (!f) {
+        return NULL;
+    }
+    uint8_t *data = malloc(CHIP8_MAX_ROM_SIZE + 1);
+    *len = fread(data, 1, CHIP8_MAX_ROM_SIZE + 1, f);
+    fclose(f);
+    return data;
+}
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "usage: %s ROM [FRAMES]\n", argv[0]);
+        return 2;
+    }
+    int frames = argc > 2 ? atoi(argv[2]) : 600;
+
+    size_t len;
+    uint8_t *rom = read_file(argv[1], &len);
+    if (!rom) {
+        perror(argv[1]);
+        return 1;
+    }
+    Chip8Machine *machine = chip8_new(rom, len);
+    free(rom);
+    if (!machine) {
+        fprintf(stderr, "%s: not a CHIP-8 ROM or larger than %d bytes\n", argv[1], CHIP8_MAX_ROM_SIZE);
+        return 1;
+    }
+
+    int result = 0;
+    for (int frame = 0; frame < frames; frame++) {
+        chip8_tick_timers(machine);
+        if (chip8_step(machine, INSTRUCTIONS_PER_FRAME) != CHIP8_STATUS_OK) {
+            fprintf(stderr, "Stopped at frame %d: %s\n", frame, chip8_last_error(machine));
+            result = 1;
+            break;
+        }
+    }
+
+    uint8_t pixels[CHIP8_DISPLAY_SIZE];
+    chip8_framebuffer(machine, pixels, sizeof(pixels));
+    for (int y = 0; y < CHIP8_DISPLAY_HEIGHT; y++) {
+        for (int x = 0; x < CHIP8_DISPLAY_WIDTH; x++) {
+            putchar(pixels[y * CHIP8_DISPLAY_WIDTH + x] ? '#' : '.');
+        }
+        putchar('\n');
+    }
+    printf("%llu instructions\n", (unsigned long long)chip8_cycles(machine));
+
+    chip8_free(machine);
+    return result;
+}
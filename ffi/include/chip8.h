@@ -0,0 +1,153 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from src/lib.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Width of the display in pixels
+ */
+#define CHIP8_DISPLAY_WIDTH 64
+
+/**
+ * Height of the display in pixels
+ */
+#define CHIP8_DISPLAY_HEIGHT 32
+
+/**
+ * Bytes chip8_framebuffer writes, one per pixel
+ */
+#define CHIP8_DISPLAY_SIZE (CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT)
+
+/**
+ * Largest ROM that fits in memory
+ */
+#define CHIP8_MAX_ROM_SIZE 3584
+
+/**
+ * Result of every call that can fail
+ */
+typedef enum Chip8Status {
+  CHIP8_STATUS_OK = 0,
+  /**
+   * A required pointer was NULL
+   */
+  CHIP8_STATUS_NULL_POINTER = 1,
+  /**
+   * The ROM is larger than CHIP8_MAX_ROM_SIZE
+   */
+  CHIP8_STATUS_ROM_TOO_LARGE = 2,
+  /**
+   * Keys are 0 to 15
+   */
+  CHIP8_STATUS_INVALID_KEY = 3,
+  /**
+   * The output buffer is too small, nothing was written
+   */
+  CHIP8_STATUS_BUFFER_TOO_SMALL = 4,
+  /**
+   * The save state is corrupt or from an incompatible version
+   */
+  CHIP8_STATUS_INVALID_STATE = 5,
+  /**
+   * The program made an invalid memory access or ran an unimplemented instruction, pc is
+   * left at the instruction
+   */
+  CHIP8_STATUS_FAULT = 6,
+  /**
+   * The interpreter panicked, e.g. on a stack overflow
+   */
+  CHIP8_STATUS_PANIC = 7,
+} Chip8Status;
+
+/**
+ * Opaque handle to a machine
+ */
+typedef struct Chip8Machine Chip8Machine;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a machine running a copy of `rom`. Returns NULL if `rom` is NULL or
+ * larger than CHIP8_MAX_ROM_SIZE. Free the machine with chip8_free.
+ */
+struct Chip8Machine *chip8_new(const uint8_t *rom, size_t len);
+
+/**
+ * Destroys a machine created by chip8_new. NULL is ignored.
+ */
+void chip8_free(struct Chip8Machine *machine);
+
+/**
+ * Replaces the program with a copy of `rom` and resets the machine
+ */
+enum Chip8Status chip8_load_rom(struct Chip8Machine *machine, const uint8_t *rom, size_t len);
+
+/**
+ * Jumps back to the start of the program and clears the registers
+ */
+enum Chip8Status chip8_reset(struct Chip8Machine *machine);
+
+/**
+ * Executes up to `cycles` instructions. Nothing runs while the program waits for a key.
+ */
+enum Chip8Status chip8_step(struct Chip8Machine *machine, size_t cycles);
+
+/**
+ * Counts the delay and sound timers down, call at 60Hz
+ */
+enum Chip8Status chip8_tick_timers(struct Chip8Machine *machine);
+
+/**
+ * Presses or releases key 0 to 15 of the hex keypad
+ */
+enum Chip8Status chip8_set_key(struct Chip8Machine *machine, uint8_t key, bool pressed);
+
+/**
+ * Copies the display to `out`, CHIP8_DISPLAY_SIZE bytes row by row, 255 for lit pixels
+ */
+enum Chip8Status chip8_framebuffer(struct Chip8Machine *machine, uint8_t *out, size_t len);
+
+/**
+ * Instructions executed since the machine was created
+ */
+uint64_t chip8_cycles(const struct Chip8Machine *machine);
+
+/**
+ * Size of the buffer chip8_save_state needs
+ */
+size_t chip8_state_size(const struct Chip8Machine *machine);
+
+/**
+ * Writes the registers, timers, memory and display to `out` and the number of
+ * bytes written to `written`, which may be NULL. The program and key states
+ * are not part of the state.
+ */
+enum Chip8Status chip8_save_state(struct Chip8Machine *machine,
+                                  uint8_t *out,
+                                  size_t len,
+                                  size_t *written);
+
+/**
+ * Restores a state written by chip8_save_state. The machine is unchanged on error.
+ */
+enum Chip8Status chip8_load_state(struct Chip8Machine *machine, const uint8_t *state, size_t len);
+
+/**
+ * Message for the last failed call on the machine, empty after a successful
+ * one. Valid until the next call with the same machine.
+ */
+const char *chip8_last_error(const struct Chip8Machine *machine);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,233 @@
+// C ABI around Chip8, declared in include/chip8.h. Every function takes the
+// machine returned by chip8_new and reports failures as a Chip8Status with
+// the details in chip8_last_error. Panics in the interpreter, e.g. on a
+// stack overflow, are caught instead of unwinding into C.
+//
+// All functions share one safety contract: machine pointers are NULL or come
+// from chip8_new and are not used after chip8_free, and buffers are NULL or
+// valid for `len` bytes. A machine must not be used from two threads at once.
+#![allow(clippy::missing_safety_doc)]
+
+use std::any::Any;
+use std::ffi::{c_char, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use chip8_rs::chip8::Chip8;
+
+/// Width of the display in pixels
+pub const CHIP8_DISPLAY_WIDTH: usize = 64;
+/// Height of the display in pixels
+pub const CHIP8_DISPLAY_HEIGHT: usize = 32;
+/// Bytes chip8_framebuffer writes, one per pixel
+pub const CHIP8_DISPLAY_SIZE: usize = CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT;
+/// Largest ROM that fits in memory
+pub const CHIP8_MAX_ROM_SIZE: usize = 3584;
+
+const _: () = assert!(CHIP8_DISPLAY_SIZE == Chip8::DISPLAY_SIZE && CHIP8_MAX_ROM_SIZE == Chip8::MAX_PROGRAM_SIZE);
+
+/// Result of every call that can fail
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Status {
+    Ok = 0,
+    /// A required pointer was NULL
+    NullPointer = 1,
+    /// The ROM is larger than CHIP8_MAX_ROM_SIZE
+    RomTooLarge = 2,
+    /// Keys are 0 to 15
+    InvalidKey = 3,
+    /// The output buffer is too small, nothing was written
+    BufferTooSmall = 4,
+    /// The save state is corrupt or from an incompatible version
+    InvalidState = 5,
+    /// The program made an invalid memory access or ran an unimplemented instruction, pc is
+    /// left at the instruction
+    Fault = 6,
+    /// The interpreter panicked, e.g. on a stack overflow
+    Panic = 7,
+}
+
+/// Opaque handle to a machine
+pub struct Chip8Machine {
+    chip8: Chip8<'static>,
+    last_error: CString,
+}
+
+impl Chip8Machine {
+    fn fail(&mut self, status: Chip8Status, message: impl Into<Vec<u8>>) -> Chip8Status {
+        // Interior NULs can't be represented, the message is dropped rather than truncated
+        self.last_error = CString::new(message).unwrap_or_default();
+        status
+    }
+
+    fn succeed(&mut self) -> Chip8Status {
+        self.last_error = CString::default();
+        Chip8Status::Ok
+    }
+}
+
+unsafe fn input_slice<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if data.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(data, len))
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("Interpreter panicked".into(), |s| s.to_string()),
+    }
+}
+
+/// Creates a machine running a copy of `rom`. Returns NULL if `rom` is NULL or
+/// larger than CHIP8_MAX_ROM_SIZE. Free the machine with chip8_free.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_new(rom: *const u8, len: usize) -> *mut Chip8Machine {
+    match input_slice(rom, len) {
+        Some(rom) if rom.len() <= CHIP8_MAX_ROM_SIZE => Box::into_raw(Box::new(Chip8Machine {
+            chip8: Chip8::from_rom(rom.to_vec()),
+            last_error: CString::default(),
+        })),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Destroys a machine created by chip8_new. NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(machine: *mut Chip8Machine) {
+    if !machine.is_null() {
+        drop(Box::from_raw(machine));
+    }
+}
+
+/// Replaces the program with a copy of `rom` and resets the machine
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(machine: *mut Chip8Machine, rom: *const u8, len: usize) -> Chip8Status {
+    let Some(m) = machine.as_mut() else { return Chip8Status::NullPointer };
+    match input_slice(rom, len) {
+        None => Chip8Status::NullPointer,
+        Some(rom) if rom.len() > CHIP8_MAX_ROM_SIZE => {
+            m.fail(Chip8Status::RomTooLarge, format!("ROM is {} bytes, at most {CHIP8_MAX_ROM_SIZE} fit", rom.len()))
+        }
+        Some(rom) => {
+            m.chip8.load_program(rom.to_vec());
+            m.succeed()
+        }
+    }
+}
+
+/// Jumps back to the start of the program and clears the registers
+#[no_mangle]
+pub unsafe extern "C" fn chip8_reset(machine: *mut Chip8Machine) -> Chip8Status {
+    let Some(m) = machine.as_mut() else { return Chip8Status::NullPointer };
+    m.chip8.reset();
+    m.succeed()
+}
+
+/// Executes up to `cycles` instructions. Nothing runs while the program waits for a key.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(machine: *mut Chip8Machine, cycles: usize) -> Chip8Status {
+    let Some(m) = machine.as_mut() else { return Chip8Status::NullPointer };
+    match panic::catch_unwind(AssertUnwindSafe(|| m.chip8.step(cycles))) {
+        Ok(Ok(())) => m.succeed(),
+        Ok(Err(fault)) => m.fail(Chip8Status::Fault, fault.to_string()),
+        Err(payload) => m.fail(Chip8Status::Panic, panic_message(payload)),
+    }
+}
+
+/// Counts the delay and sound timers down, call at 60Hz
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_timers(machine: *mut Chip8Machine) -> Chip8Status {
+    let Some(m) = machine.as_mut() else { return Chip8Status::NullPointer };
+    m.chip8.tick_60hz();
+    m.succeed()
+}
+
+/// Presses or releases key 0 to 15 of the hex keypad
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(machine: *mut Chip8Machine, key: u8, pressed: bool) -> Chip8Status {
+    let Some(m) = machine.as_mut() else { return Chip8Status::NullPointer };
+    if key >= 16 {
+        return m.fail(Chip8Status::InvalidKey, format!("Invalid key {key}"));
+    }
+    m.chip8.set_key_state(key, pressed);
+    m.succeed()
+}
+
+/// Copies the display to `out`, CHIP8_DISPLAY_SIZE bytes row by row, 255 for lit pixels
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(machine: *mut Chip8Machine, out: *mut u8, len: usize) -> Chip8Status {
+    let Some(m) = machine.as_mut() else { return Chip8Status::NullPointer };
+    if out.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    if len < CHIP8_DISPLAY_SIZE {
+        return m.fail(Chip8Status::BufferTooSmall, format!("Framebuffer needs {CHIP8_DISPLAY_SIZE} bytes"));
+    }
+    m.chip8.framebuffer().unpack(slice::from_raw_parts_mut(out, CHIP8_DISPLAY_SIZE));
+    m.succeed()
+}
+
+/// Instructions executed since the machine was created
+#[no_mangle]
+pub unsafe extern "C" fn chip8_cycles(machine: *const Chip8Machine) -> u64 {
+    machine.as_ref().map_or(0, |m| m.chip8.cycles())
+}
+
+/// Size of the buffer chip8_save_state needs
+#[no_mangle]
+pub unsafe extern "C" fn chip8_state_size(machine: *const Chip8Machine) -> usize {
+    machine.as_ref().map_or(0, |m| m.chip8.save_snapshot().len())
+}
+
+/// Writes the registers, timers, memory and display to `out` and the number of
+/// bytes written to `written`, which may be NULL. The program and key states
+/// are not part of the state.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(
+    machine: *mut Chip8Machine,
+    out: *mut u8,
+    len: usize,
+    written: *mut usize,
+) -> Chip8Status {
+    let Some(m) = machine.as_mut() else { return Chip8Status::NullPointer };
+    if out.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    let state = m.chip8.save_snapshot();
+    if len < state.len() {
+        return m.fail(Chip8Status::BufferTooSmall, format!("State needs {} bytes", state.len()));
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), out, state.len());
+    if !written.is_null() {
+        *written = state.len();
+    }
+    m.succeed()
+}
+
+/// Restores a state written by chip8_save_state. The machine is unchanged on error.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(machine: *mut Chip8Machine, state: *const u8, len: usize) -> Chip8Status {
+    let Some(m) = machine.as_mut() else { return Chip8Status::NullPointer };
+    let Some(state) = input_slice(state, len) else { return Chip8Status::NullPointer };
+    match m.chip8.load_snapshot(state) {
+        Ok(()) => m.succeed(),
+        Err(e) => m.fail(Chip8Status::InvalidState, e.to_string()),
+    }
+}
+
+/// Message for the last failed call on the machine, empty after a successful
+/// one. Valid until the next call with the same machine.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_last_error(machine: *const Chip8Machine) -> *const c_char {
+    match machine.as_ref() {
+        Some(m) => m.last_error.as_ptr(),
+        None => c"NULL machine".as_ptr(),
+    }
+}
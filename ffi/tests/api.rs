@@ -0,0 +1,141 @@
+use std::ffi::CStr;
+use std::ptr;
+
+use chip8::*;
+
+const DRAW_ZERO: &[u8] = &[
+    0x60, 0x00,     // gp0 = 0
+    0xf0, 0x29,     // i = glyph for gp0
+    0xd0, 0x05,     // draw it at (gp0, gp0)
+    0x70, 0x01,     // gp0 += 1
+    0x12, 0x02,     // loop
+];
+
+unsafe fn last_error(machine: *const Chip8Machine) -> String {
+    CStr::from_ptr(chip8_last_error(machine)).to_string_lossy().into_owned()
+}
+
+unsafe fn pixels(machine: *mut Chip8Machine) -> Vec<u8> {
+    let mut pixels = vec![0; CHIP8_DISPLAY_SIZE];
+    assert_eq!(chip8_framebuffer(machine, pixels.as_mut_ptr(), pixels.len()), Chip8Status::Ok);
+    pixels
+}
+
+#[test]
+fn test_run_and_framebuffer() {
+    unsafe {
+        let machine = chip8_new(DRAW_ZERO.as_ptr(), DRAW_ZERO.len());
+        assert!(!machine.is_null());
+        assert_eq!(chip8_step(machine, 3), Chip8Status::Ok);
+        assert_eq!(chip8_tick_timers(machine), Chip8Status::Ok);
+        assert_eq!(chip8_cycles(machine), 3);
+
+        let pixels = pixels(machine);
+        assert_eq!(pixels[..5], [255, 255, 255, 255, 0]);
+        assert_eq!(pixels[CHIP8_DISPLAY_WIDTH..CHIP8_DISPLAY_WIDTH + 5], [255, 0, 0, 255, 0]);
+
+        let mut small = [0; 16];
+        assert_eq!(chip8_framebuffer(machine, small.as_mut_ptr(), small.len()), Chip8Status::BufferTooSmall);
+        assert!(last_error(machine).contains("2048"));
+        chip8_free(machine);
+    }
+}
+
+// Delay and sound timer of a machine, read from a save state: magic, version, pc, i, sp, V0 to
+// VF and 16 stack entries come first
+unsafe fn timers(machine: *mut Chip8Machine) -> (u8, u8) {
+    let mut state = vec![0; chip8_state_size(machine)];
+    assert_eq!(chip8_save_state(machine, state.as_mut_ptr(), state.len(), ptr::null_mut()), Chip8Status::Ok);
+    let offset = 4 + 1 + 4 + 4 + 1 + 16 + 16 * 4;
+    (state[offset], state[offset + 1])
+}
+
+#[test]
+fn test_tick_timers_stops_at_zero() {
+    let program = [
+        0x60, 0x02,     // gp0 = 2
+        0xf0, 0x15,     // delay timer = gp0
+        0xf0, 0x18,     // sound timer = gp0
+        0x12, 0x06,     // loop forever
+    ];
+    unsafe {
+        let machine = chip8_new(program.as_ptr(), program.len());
+        assert_eq!(chip8_step(machine, 3), Chip8Status::Ok);
+        assert_eq!(timers(machine), (2, 2));
+        assert_eq!(chip8_tick_timers(machine), Chip8Status::Ok);
+        assert_eq!(timers(machine), (1, 1));
+        for _ in 0..3 {
+            assert_eq!(chip8_tick_timers(machine), Chip8Status::Ok);
+            assert_eq!(timers(machine), (0, 0));
+        }
+        chip8_free(machine);
+    }
+}
+
+#[test]
+fn test_save_and_load_state() {
+    unsafe {
+        let machine = chip8_new(DRAW_ZERO.as_ptr(), DRAW_ZERO.len());
+        chip8_step(machine, 3);
+        let mut state = vec![0; chip8_state_size(machine)];
+        let mut written = 0;
+        assert_eq!(chip8_save_state(machine, state.as_mut_ptr(), state.len(), &mut written), Chip8Status::Ok);
+        assert_eq!(written, state.len());
+        let saved = pixels(machine);
+
+        chip8_step(machine, 50);
+        assert_ne!(pixels(machine), saved);
+        assert_eq!(chip8_load_state(machine, state.as_ptr(), state.len()), Chip8Status::Ok);
+        assert_eq!(pixels(machine), saved);
+        assert_eq!(chip8_cycles(machine), 3);
+
+        assert_eq!(chip8_load_state(machine, state.as_ptr(), 10), Chip8Status::InvalidState);
+        assert_eq!(last_error(machine), "Snapshot is truncated");
+        assert_eq!(chip8_save_state(machine, state.as_mut_ptr(), 10, ptr::null_mut()), Chip8Status::BufferTooSmall);
+        chip8_free(machine);
+    }
+}
+
+#[test]
+fn test_errors() {
+    unsafe {
+        let rom = vec![0; CHIP8_MAX_ROM_SIZE + 1];
+        assert!(chip8_new(rom.as_ptr(), rom.len()).is_null());
+        assert!(chip8_new(ptr::null(), 2).is_null());
+
+        let machine = chip8_new(ptr::null(), 0);
+        assert!(!machine.is_null());
+        assert_eq!(chip8_load_rom(machine, rom.as_ptr(), rom.len()), Chip8Status::RomTooLarge);
+        assert_eq!(chip8_set_key(machine, 16, true), Chip8Status::InvalidKey);
+        assert_eq!(last_error(machine), "Invalid key 16");
+        assert_eq!(chip8_set_key(machine, 15, true), Chip8Status::Ok);
+        assert_eq!(last_error(machine), "");
+
+        // Unimplemented instructions fault
+        let bad = [0xff, 0xff];
+        assert_eq!(chip8_load_rom(machine, bad.as_ptr(), bad.len()), Chip8Status::Ok);
+        assert_eq!(chip8_step(machine, 1), Chip8Status::Fault);
+        assert!(last_error(machine).contains("Unimplemented"));
+
+        assert_eq!(chip8_step(ptr::null_mut(), 1), Chip8Status::NullPointer);
+        chip8_free(machine);
+        chip8_free(ptr::null_mut());
+    }
+}
+
+// include/chip8.h is written by cbindgen, make sure it wasn't forgotten after an API change
+#[test]
+fn test_header_declares_every_function() {
+    let source = include_str!("../src/lib.rs");
+    let header = include_str!("../include/chip8.h");
+
+    let exported: Vec<&str> = source.lines()
+        .filter_map(|line| line.strip_prefix("pub unsafe extern \"C\" fn "))
+        .map(|rest| &rest[..rest.find('(').unwrap()])
+        .collect();
+    assert_eq!(exported.len(), 13);
+    for name in exported {
+        assert!(header.contains(&format!("{name}(")), "{name} missing from chip8.h");
+    }
+    assert!(header.contains(&format!("#define CHIP8_MAX_ROM_SIZE {CHIP8_MAX_ROM_SIZE}")));
+}
@@ -0,0 +1,38 @@
+// Builds examples/run_rom.c against the cdylib and runs it on a bundled ROM
+#![cfg(unix)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_c_example() {
+    // The test binary lives in target/<profile>/deps next to the cdylib
+    let deps = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let exe = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("run_rom");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = Command::new(&compiler)
+        .arg(manifest.join("examples/run_rom.c"))
+        .arg("-I").arg(manifest.join("include"))
+        .arg("-L").arg(&deps)
+        .arg(format!("-Wl,-rpath,{}", deps.display()))
+        .args(["-lchip8", "-Wall", "-Werror", "-o"])
+        .arg(&exe)
+        .status();
+    match status {
+        Ok(status) => assert!(status.success(), "{compiler} failed"),
+        Err(e) => {
+            eprintln!("Skipping, no C compiler: {e}");
+            return;
+        }
+    }
+
+    let output = Command::new(&exe).arg(manifest.join("../roms/trip8.rom")).arg("300").output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 33);
+    assert!(stdout.contains('#'));
+    assert!(stdout.ends_with("3000 instructions\n"));
+}
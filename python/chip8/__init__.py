@@ -0,0 +1,15 @@
+"""CHIP-8 interpreter.
+
+    from chip8 import Chip8
+
+    c8 = Chip8(open("roms/trip8.rom", "rb").read(), seed=1)
+    for _ in range(60):
+        c8.run_frame()
+    pixels = numpy.asarray(c8.pixels())     # (32, 64) uint8, 255 for lit pixels
+
+The Gym environment is in chip8.env and needs the `gym` extra.
+"""
+
+from ._native import Chip8, Chip8Error, Pixels
+
+__all__ = ["Chip8", "Chip8Error", "Pixels"]
@@ -0,0 +1,100 @@
+"""Gymnasium environment around a CHIP-8 ROM.
+
+Games don't report scores in a standard way, so rewards and episode ends come
+from callables that inspect the machine, usually by reading the score from
+memory with Chip8.read_memory or from a register with Chip8.v.
+"""
+
+from typing import Callable, Optional, Sequence
+
+import gymnasium
+import numpy as np
+from gymnasium import spaces
+
+from ._native import Chip8, Chip8Error
+
+
+class Chip8Env(gymnasium.Env):
+    """Observations are the (32, 64) uint8 display. Action 0 presses no key,
+    action n holds down keys[n - 1] for the whole step.
+
+    Each step runs `frame_skip` frames of `instructions_per_frame`
+    instructions. `reward_fn(chip8)` is called after every step and returns
+    the reward, `terminated_fn(chip8)` returns True when the game is over.
+    Episodes are truncated after `max_steps` steps. A program that faults ends
+    the episode with the error message in info["error"].
+    """
+
+    metadata = {"render_modes": ["rgb_array", "ansi"], "render_fps": 60}
+
+    def __init__(
+        self,
+        rom: bytes,
+        keys: Sequence[int] = tuple(range(16)),
+        frame_skip: int = 4,
+        instructions_per_frame: int = 10,
+        reward_fn: Optional[Callable[[Chip8], float]] = None,
+        terminated_fn: Optional[Callable[[Chip8], bool]] = None,
+        max_steps: Optional[int] = None,
+        render_mode: Optional[str] = None,
+    ):
+        if any(not 0 <= key < 16 for key in keys):
+            raise ValueError("Keys are 0 to 15")
+        if render_mode is not None and render_mode not in self.metadata["render_modes"]:
+            raise ValueError(f"Unsupported render mode {render_mode}")
+
+        self.rom = bytes(rom)
+        self.keys = list(keys)
+        self.frame_skip = frame_skip
+        self.instructions_per_frame = instructions_per_frame
+        self.reward_fn = reward_fn
+        self.terminated_fn = terminated_fn
+        self.max_steps = max_steps
+        self.render_mode = render_mode
+
+        self.action_space = spaces.Discrete(len(self.keys) + 1)
+        self.observation_space = spaces.Box(0, 255, (Chip8.HEIGHT, Chip8.WIDTH), np.uint8)
+        self.chip8 = Chip8(self.rom)
+        self.steps = 0
+
+    def _observation(self):
+        return np.array(self.chip8.pixels(), dtype=np.uint8)
+
+    def reset(self, *, seed=None, options=None):
+        super().reset(seed=seed)
+        # The machine's RNG is seeded from the environment's so runs repeat with the same seed
+        self.chip8 = Chip8(self.rom, seed=int(self.np_random.integers(2**63)))
+        self.steps = 0
+        return self._observation(), {}
+
+    def step(self, action):
+        if not self.action_space.contains(action):
+            raise ValueError(f"Invalid action {action}")
+
+        self.chip8.release_keys()
+        if action > 0:
+            self.chip8.set_key(self.keys[action - 1], True)
+
+        info = {}
+        terminated = False
+        try:
+            for _ in range(self.frame_skip):
+                self.chip8.run_frame(self.instructions_per_frame)
+        except Chip8Error as e:
+            info["error"] = str(e)
+            terminated = True
+
+        self.steps += 1
+        reward = float(self.reward_fn(self.chip8)) if self.reward_fn else 0.0
+        terminated = terminated or bool(self.terminated_fn and self.terminated_fn(self.chip8))
+        truncated = self.max_steps is not None and self.steps >= self.max_steps
+        return self._observation(), reward, terminated, truncated, info
+
+    def render(self):
+        if self.render_mode == "rgb_array":
+            return np.repeat(self._observation()[:, :, np.newaxis], 3, axis=2)
+        if self.render_mode == "ansi":
+            return "\n".join(
+                "".join("#" if px else "." for px in row) for row in self._observation()
+            ) + "\n"
+        return None
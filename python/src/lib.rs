@@ -0,0 +1,242 @@
+// Native half of the `chip8` Python package. The package re-exports these
+// classes from chip8/__init__.py and adds the Gym environment in chip8/env.py.
+
+use std::ffi::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyBufferError, PyRuntimeError, PyValueError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+use chip8_rs::chip8::{Bus, Chip8};
+
+create_exception!(chip8, Chip8Error, PyRuntimeError, "The program faulted or hit an unimplemented instruction");
+
+const MEM_SIZE: usize = 4096;
+
+#[pyclass(name = "Chip8", module = "chip8")]
+struct PyChip8 {
+    chip8: Chip8<'static>,
+}
+
+fn check_rom(rom: &[u8]) -> PyResult<()> {
+    Chip8::check_program_size(rom.len()).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pymethods]
+impl PyChip8 {
+    #[classattr]
+    const WIDTH: usize = Chip8::DISPLAY_WIDTH;
+    #[classattr]
+    const HEIGHT: usize = Chip8::DISPLAY_HEIGHT;
+
+    // A fixed seed makes the RND instruction, and so whole runs, reproducible
+    #[new]
+    #[pyo3(signature = (rom, seed=None))]
+    fn new(rom: &[u8], seed: Option<u64>) -> PyResult<Self> {
+        check_rom(rom)?;
+        let mut c8 = PyChip8 { chip8: Chip8::from_rom(rom.to_vec()) };
+        if let Some(seed) = seed {
+            c8.seed(seed);
+        }
+        Ok(c8)
+    }
+
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        check_rom(rom)?;
+        self.chip8.load_program(rom.to_vec());
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.chip8.reset();
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.chip8.set_rng(SmallRng::seed_from_u64(seed));
+    }
+
+    // Executes up to `cycles` instructions, raises Chip8Error if the program can't continue
+    #[pyo3(signature = (cycles=1))]
+    fn step(&mut self, cycles: usize) -> PyResult<()> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.chip8.step(cycles))) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(fault)) => Err(Chip8Error::new_err(fault.to_string())),
+            Err(payload) => {
+                let message = payload.downcast_ref::<String>().cloned()
+                    .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "Interpreter panicked".into());
+                Err(Chip8Error::new_err(message))
+            }
+        }
+    }
+
+    // Counts the timers down, once per 60Hz frame
+    fn tick(&mut self) {
+        self.chip8.tick_60hz();
+    }
+
+    // One 60Hz frame like the windowed frontend runs it: tick, then `instructions` instructions
+    #[pyo3(signature = (instructions=10))]
+    fn run_frame(&mut self, instructions: usize) -> PyResult<()> {
+        self.tick();
+        self.step(instructions)
+    }
+
+    fn set_key(&mut self, key: u8, pressed: bool) -> PyResult<()> {
+        if key >= 16 {
+            return Err(PyValueError::new_err(format!("Invalid key {key}, keys are 0 to 15")));
+        }
+        self.chip8.set_key_state(key, pressed);
+        Ok(())
+    }
+
+    // Releases every key without ending an Fx0A wait
+    fn release_keys(&mut self) {
+        self.chip8.release_keys();
+    }
+
+    fn pixels(&self) -> Pixels {
+        Pixels::new(self.chip8.pixels(), Chip8::DISPLAY_WIDTH, Chip8::DISPLAY_HEIGHT)
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.chip8.save_snapshot())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.chip8.load_snapshot(state).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    // Reads memory without side effects, e.g. to compute rewards from a score in RAM
+    fn read_memory<'py>(&self, py: Python<'py>, addr: usize, length: usize) -> PyResult<Bound<'py, PyBytes>> {
+        if addr.checked_add(length).is_none_or(|end| end > MEM_SIZE) {
+            return Err(PyValueError::new_err(format!("{length} bytes at {addr:#x} are outside of memory")));
+        }
+        let bytes: Vec<u8> = (addr..addr + length).map(|a| self.chip8.bus().peek(a)).collect();
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    #[getter]
+    fn cycles(&self) -> u64 {
+        self.chip8.cycles()
+    }
+
+    #[getter]
+    fn pc(&self) -> usize {
+        self.chip8.state().pc
+    }
+
+    #[getter]
+    fn i(&self) -> usize {
+        self.chip8.state().i
+    }
+
+    // V0 to VF
+    #[getter]
+    fn v(&self) -> [u8; 16] {
+        self.chip8.state().reg
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.chip8.state().delay_timer
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.chip8.state().sound_timer
+    }
+
+    #[getter]
+    fn waiting_for_key(&self) -> bool {
+        self.chip8.is_waiting_for_key()
+    }
+}
+
+// Read only copy of the display, one byte per pixel, 255 when lit. Implements
+// the buffer protocol with shape (height, width), so numpy.asarray and
+// memoryview use it without copying.
+#[pyclass(module = "chip8", frozen)]
+struct Pixels {
+    data: Vec<u8>,
+    shape: [ffi::Py_ssize_t; 2],
+    strides: [ffi::Py_ssize_t; 2],
+}
+
+impl Pixels {
+    fn new(data: Vec<u8>, width: usize, height: usize) -> Self {
+        Pixels {
+            data,
+            shape: [height as ffi::Py_ssize_t, width as ffi::Py_ssize_t],
+            strides: [width as ffi::Py_ssize_t, 1],
+        }
+    }
+}
+
+#[pymethods]
+impl Pixels {
+    #[getter]
+    fn width(&self) -> usize {
+        self.shape[1] as usize
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.shape[0] as usize
+    }
+
+    fn __len__(&self) -> usize {
+        self.data.len()
+    }
+
+    fn tobytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.data)
+    }
+
+    unsafe fn __getbuffer__(slf: Bound<'_, Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if flags & ffi::PyBUF_WRITABLE == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Pixels are read only"));
+        }
+
+        // The frozen object owns the data, shape and strides and the view keeps it alive
+        let pixels = slf.get();
+        let view = &mut *view;
+        view.buf = pixels.data.as_ptr() as *mut c_void;
+        view.len = pixels.data.len() as ffi::Py_ssize_t;
+        view.readonly = 1;
+        view.itemsize = 1;
+        view.format = if flags & ffi::PyBUF_FORMAT == ffi::PyBUF_FORMAT { c"B".as_ptr() as *mut _ } else { ptr::null_mut() };
+        if flags & ffi::PyBUF_ND == ffi::PyBUF_ND {
+            view.ndim = 2;
+            view.shape = pixels.shape.as_ptr() as *mut _;
+        } else {
+            view.ndim = 1;
+            view.shape = ptr::null_mut();
+        }
+        view.strides = if flags & ffi::PyBUF_STRIDES == ffi::PyBUF_STRIDES { pixels.strides.as_ptr() as *mut _ } else { ptr::null_mut() };
+        view.suboffsets = ptr::null_mut();
+        view.internal = ptr::null_mut();
+        view.obj = slf.into_any().into_ptr();
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, _view: *mut ffi::Py_buffer) {}
+}
+
+#[pymodule]
+#[pyo3(name = "_native")]
+fn chip8_native(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyChip8>()?;
+    m.add_class::<Pixels>()?;
+    m.add("Chip8Error", m.py().get_type::<Chip8Error>())?;
+    Ok(())
+}
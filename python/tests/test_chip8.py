@@ -0,0 +1,107 @@
+import os
+import unittest
+
+from chip8 import Chip8, Chip8Error
+
+ROMS = os.path.join(os.path.dirname(__file__), "..", "..", "roms")
+
+# gp0 = 0, i = glyph for gp0, draw it at (gp0, gp0), gp0 += 1, loop to the glyph
+DRAW_ZERO = bytes([0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x70, 0x01, 0x12, 0x02])
+
+
+class TestChip8(unittest.TestCase):
+    def test_step_and_registers(self):
+        c8 = Chip8(DRAW_ZERO)
+        self.assertEqual(c8.pc, 0x200)
+        c8.step(3)
+        self.assertEqual(c8.cycles, 3)
+        self.assertEqual(c8.pc, 0x206)
+        self.assertEqual(c8.i, 0)
+        self.assertEqual(c8.v[15], 0)
+
+    def test_pixels_buffer(self):
+        c8 = Chip8(DRAW_ZERO)
+        c8.step(3)
+        pixels = c8.pixels()
+        view = memoryview(pixels)
+        self.assertEqual(view.shape, (Chip8.HEIGHT, Chip8.WIDTH))
+        self.assertEqual(view.format, "B")
+        self.assertTrue(view.readonly)
+        self.assertEqual(view.tolist()[0][:5], [255, 255, 255, 255, 0])
+        self.assertEqual(len(pixels.tobytes()), 64 * 32)
+        self.assertEqual(pixels.tobytes(), bytes(view.tobytes()))
+
+    def test_pixels_numpy(self):
+        try:
+            import numpy as np
+        except ImportError:
+            self.skipTest("numpy is not installed")
+        c8 = Chip8(DRAW_ZERO)
+        c8.step(3)
+        pixels = np.asarray(c8.pixels())
+        self.assertEqual(pixels.shape, (32, 64))
+        self.assertEqual(pixels.dtype, np.uint8)
+        self.assertEqual(list(pixels[1, :5]), [255, 0, 0, 255, 0])
+
+    def test_save_and_load_state(self):
+        c8 = Chip8(DRAW_ZERO)
+        c8.step(3)
+        state = c8.save_state()
+        pixels = c8.pixels().tobytes()
+
+        c8.step(40)
+        self.assertNotEqual(c8.pixels().tobytes(), pixels)
+        c8.load_state(state)
+        self.assertEqual(c8.pixels().tobytes(), pixels)
+        self.assertEqual(c8.cycles, 3)
+
+        with self.assertRaises(ValueError):
+            c8.load_state(state[:10])
+
+    def test_keys(self):
+        # skip if key 0 is pressed, loop, gp1 = 1
+        c8 = Chip8(bytes([0xE0, 0x9E, 0x12, 0x00, 0x61, 0x01]))
+        c8.step(4)
+        self.assertEqual(c8.v[1], 0)
+        c8.set_key(0, True)
+        c8.step(2)
+        self.assertEqual(c8.v[1], 1)
+        c8.release_keys()
+        with self.assertRaises(ValueError):
+            c8.set_key(16, True)
+
+    def test_seed(self):
+        rnd = bytes([0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF])
+        runs = []
+        for _ in range(2):
+            c8 = Chip8(rnd, seed=7)
+            c8.step(3)
+            runs.append(c8.v[:3])
+        self.assertEqual(runs[0], runs[1])
+
+    def test_errors(self):
+        with self.assertRaises(ValueError):
+            Chip8(bytes(4096))
+        c8 = Chip8(bytes([0xFF, 0xFF]))
+        with self.assertRaises(Chip8Error) as e:
+            c8.step()
+        self.assertIn("Unimplemented", str(e.exception))
+
+    def test_read_memory(self):
+        c8 = Chip8(DRAW_ZERO)
+        self.assertEqual(c8.read_memory(0x200, 4), DRAW_ZERO[:4])
+        self.assertEqual(c8.read_memory(0, 5), bytes([0xF0, 0x90, 0x90, 0x90, 0xF0]))
+        with self.assertRaises(ValueError):
+            c8.read_memory(0xFFF, 2)
+
+    def test_rom(self):
+        with open(os.path.join(ROMS, "trip8.rom"), "rb") as f:
+            c8 = Chip8(f.read(), seed=1)
+        for _ in range(300):
+            c8.run_frame()
+        self.assertEqual(c8.cycles, 3000)
+        self.assertIn(255, c8.pixels().tobytes())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,109 @@
+import os
+import unittest
+
+try:
+    import numpy as np
+    from chip8.env import Chip8Env
+except ImportError:
+    Chip8Env = None
+
+ROMS = os.path.join(os.path.dirname(__file__), "..", "..", "roms")
+
+# Draws the glyph of the pressed key, one of 1, 2 or 3, at the top left
+KEY_GLYPH = bytes([
+    0x00, 0xE0,     # clear
+    0x60, 0x01,     # gp0 = 1
+    0xE0, 0x9E,     # skip if key gp0 is pressed
+    0x12, 0x0C,     # next key
+    0xF0, 0x29,     # i = glyph for gp0
+    0xD1, 0x15,     # draw it at (gp1, gp1)
+    0x70, 0x01,     # gp0 += 1
+    0x30, 0x04,     # skip if gp0 == 4
+    0x12, 0x04,     # check the key
+    0x12, 0x12,     # loop
+])
+
+# Waits for key 5 with Fx0A, then sets gp1
+WAIT_FOR_KEY = bytes([
+    0x60, 0x05,     # gp0 = 5
+    0xF0, 0x0A,     # wait for key gp0
+    0x61, 0x01,     # gp1 = 1
+    0x12, 0x06,     # loop
+])
+
+
+@unittest.skipIf(Chip8Env is None, "numpy and gymnasium are not installed")
+class TestChip8Env(unittest.TestCase):
+    def test_spaces(self):
+        env = Chip8Env(KEY_GLYPH, keys=[1, 2, 3])
+        self.assertEqual(env.action_space.n, 4)
+        obs, info = env.reset(seed=0)
+        self.assertEqual(obs.shape, (32, 64))
+        self.assertTrue(env.observation_space.contains(obs))
+
+    def test_actions_press_keys(self):
+        env = Chip8Env(KEY_GLYPH, keys=[1, 2, 3], frame_skip=1)
+        env.reset(seed=0)
+        obs, reward, terminated, truncated, info = env.step(2)
+        # Glyph 2 starts with a full row, glyph 1 doesn't
+        self.assertEqual(list(obs[0, :4]), [255] * 4)
+        self.assertEqual((reward, terminated, truncated), (0.0, False, False))
+
+    def test_noop_keeps_waiting_for_key(self):
+        env = Chip8Env(WAIT_FOR_KEY, keys=[5], frame_skip=1)
+        env.reset(seed=0)
+        for _ in range(3):
+            env.step(0)
+            self.assertTrue(env.chip8.waiting_for_key)
+            self.assertEqual(env.chip8.v[1], 0)
+        env.step(1)
+        env.step(0)
+        self.assertFalse(env.chip8.waiting_for_key)
+        self.assertEqual(env.chip8.v[1], 1)
+
+    def test_reward_and_termination(self):
+        env = Chip8Env(
+            KEY_GLYPH,
+            keys=[1, 2, 3],
+            reward_fn=lambda c8: c8.v[0],
+            terminated_fn=lambda c8: c8.cycles > 100,
+            max_steps=3,
+        )
+        env.reset(seed=0)
+        _, reward, terminated, truncated, _ = env.step(0)
+        self.assertGreater(reward, 0)
+        self.assertFalse(terminated)
+        _, _, terminated, truncated, _ = env.step(0)
+        _, _, terminated, truncated, _ = env.step(0)
+        self.assertTrue(terminated)
+        self.assertTrue(truncated)
+
+    def test_fault_ends_episode(self):
+        env = Chip8Env(bytes([0xFF, 0xFF]))
+        env.reset()
+        _, _, terminated, _, info = env.step(0)
+        self.assertTrue(terminated)
+        self.assertIn("Unimplemented", info["error"])
+
+    def test_seeded_resets_repeat(self):
+        with open(os.path.join(ROMS, "particle.rom"), "rb") as f:
+            env = Chip8Env(f.read())
+        runs = []
+        for _ in range(2):
+            env.reset(seed=3)
+            for _ in range(20):
+                obs, *_ = env.step(0)
+            runs.append(obs)
+        np.testing.assert_array_equal(runs[0], runs[1])
+
+    def test_render(self):
+        env = Chip8Env(KEY_GLYPH, render_mode="ansi")
+        env.reset()
+        self.assertEqual(len(env.render().splitlines()), 32)
+        env = Chip8Env(KEY_GLYPH, render_mode="rgb_array")
+        env.reset()
+        self.assertEqual(env.render().shape, (32, 64, 3))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,463 @@
+//! Static analysis of a ROM without running it. [`analyze`] walks the control
+//! flow from the reset vector, following jumps, calls and both sides of every
+//! skip, and reports what a run could trip over: instructions that aren't
+//! part of any platform or that [`Chip8::step`] doesn't implement, control
+//! flow that leaves the program or memory, code nothing reaches and the
+//! instructions whose behavior depends on [`Quirks`](crate::chip8::Quirks).
+//! The opcodes the program uses decide which platform it was likely written
+//! for.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use crate::chip8::{disassemble, opcode_class, Chip8, Quirks};
+
+/// The platforms a program can be written for, each a superset of the one before
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        })
+    }
+}
+
+/// The quirks of [`Quirks`](crate::chip8::Quirks) an instruction behaves differently under
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Quirk {
+    /// 8xy6 and 8xyE, `shift`
+    Shift,
+    /// Fx55 and Fx65, `memory_increment_by_x` and `memory_leave_i_unchanged`
+    MemoryIncrement,
+    /// Dxyn, `wrap`
+    Wrap,
+    /// Dxyn, `vblank`
+    VBlank,
+    /// Bnnn, `jump`
+    Jump,
+    /// 8xy1, 8xy2 and 8xy3, `logic`
+    Logic,
+}
+
+impl fmt::Display for Quirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Quirk::Shift => "shift",
+            Quirk::MemoryIncrement => "memory increment",
+            Quirk::Wrap => "wrap",
+            Quirk::VBlank => "vblank",
+            Quirk::Jump => "jump",
+            Quirk::Logic => "logic",
+        })
+    }
+}
+
+/// Something a run of the program could trip over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Issue {
+    /// No path from the reset vector reaches the bytes up to `end` and no `Annn` points at them
+    Unreachable { end: usize },
+    /// Control flow reaches `target`, which isn't part of the program, e.g. the font or past the
+    /// end of the ROM
+    LeavesProgram { target: usize },
+    /// Control flow can reach `target`, past the end of the 4K address space
+    LeavesMemory { target: usize },
+    /// Not an instruction of any platform
+    InvalidOpcode,
+    /// An instruction of `platform` that [`Chip8::step`] doesn't implement
+    Unimplemented { platform: Platform },
+    /// Bnnn jumps to an address computed at run time, the walk only follows nnn itself
+    IndirectJump,
+    /// Behaves differently depending on `quirk`
+    QuirkDependent { quirk: Quirk },
+}
+
+impl Issue {
+    /// True for the issues that stop a run of this interpreter with a fault or run into memory
+    /// that isn't code
+    pub fn is_error(&self) -> bool {
+        matches!(self, Issue::LeavesMemory { .. } | Issue::InvalidOpcode | Issue::Unimplemented { .. })
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Unreachable { end } => write!(f, "Unreachable up to {:#05x}", end - 1),
+            Issue::LeavesProgram { target } => write!(f, "Control flow leaves the program to {target:#05x}"),
+            Issue::LeavesMemory { target } => write!(f, "Control flow can leave memory to {target:#x}"),
+            Issue::InvalidOpcode => write!(f, "Invalid opcode"),
+            Issue::Unimplemented { platform } => write!(f, "{platform} instruction this interpreter doesn't implement"),
+            Issue::IndirectJump => write!(f, "Indirect jump, targets other than nnn aren't followed"),
+            Issue::QuirkDependent { quirk } => write!(f, "Depends on the {quirk} quirk"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub addr: usize,
+    /// The instruction at `addr`, None for findings about a range of bytes
+    pub instr: Option<u16>,
+    pub issue: Issue,
+}
+
+/// What [`analyze`] found out about a program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    /// The instructions reachable from the reset vector by address
+    pub code: BTreeMap<usize, u16>,
+    /// Unreached ranges of the program that start where an `Annn` points, likely sprites or tables
+    pub data: Vec<Range<usize>>,
+    /// Ordered by address
+    pub findings: Vec<Finding>,
+    /// The platform the opcodes of the reachable code belong to
+    pub platform: Platform,
+    /// Reachable instructions per family for the platforms beyond CHIP-8, e.g. `"00FF"`
+    pub extensions: BTreeMap<&'static str, usize>,
+}
+
+impl Analysis {
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|finding| finding.issue.is_error())
+    }
+
+    /// The platform, the findings with the instruction at their address, the quirks the program
+    /// depends on, the data ranges and the suggested quirks
+    pub fn write_report(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(out, "Platform: {}", self.platform)?;
+        for (class, count) in &self.extensions {
+            writeln!(out, "  {class} x{count}")?;
+        }
+        writeln!(out, "{} instructions reachable from {:#05x}", self.code.len(), Chip8::RESET_VECTOR)?;
+
+        writeln!(out, "\nFindings")?;
+        let mut quirks: BTreeMap<Quirk, Vec<usize>> = BTreeMap::new();
+        for finding in &self.findings {
+            match (finding.issue, finding.instr) {
+                (Issue::QuirkDependent { quirk }, _) => quirks.entry(quirk).or_default().push(finding.addr),
+                (issue, Some(instr)) => writeln!(out, "{:#05x}  {instr:04X}  {:<16}  {issue}", finding.addr, disassemble(instr))?,
+                (issue, None) => writeln!(out, "{:#05x}  {:<22}  {issue}", finding.addr, "")?,
+            }
+        }
+
+        if !quirks.is_empty() {
+            writeln!(out, "\nQuirks")?;
+            for (quirk, addrs) in quirks {
+                write!(out, "{quirk:>16}:")?;
+                for addr in addrs {
+                    write!(out, " {addr:#05x}")?;
+                }
+                writeln!(out)?;
+            }
+        }
+
+        if !self.data.is_empty() {
+            writeln!(out, "\nData")?;
+            for range in &self.data {
+                writeln!(out, "{:#05x}-{:#05x}  {} bytes", range.start, range.end - 1, range.len())?;
+            }
+        }
+
+        writeln!(out, "\nSuggested quirks")?;
+        let suggestion = self.suggest_quirks();
+        if suggestion.reasons.is_empty() {
+            writeln!(out, "The code hints at nothing, the defaults")?;
+        }
+        for reason in &suggestion.reasons {
+            writeln!(out, "{reason}")?;
+        }
+        writeln!(out, "{:?}", suggestion.quirks)
+    }
+}
+
+/// Quirks guessed from the code of a program, see [`Analysis::suggest_quirks`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuirkSuggestion {
+    pub quirks: Quirks,
+    /// One line per choice naming the instruction it rests on, empty when the code hints at
+    /// nothing and the quirks are the defaults
+    pub reasons: Vec<String>,
+}
+
+impl Analysis {
+    /// Guesses the quirks the program expects, for programs the database doesn't know. The
+    /// platform picks the quirks of SUPER-CHIP or XO-CHIP, then single instructions refine them:
+    ///
+    /// - 8xy6 or 8xyE with x != y in a CHIP-8 program shifts Vy into Vx like the VIP
+    /// - Bxnn right after an instruction that sets Vx but not V0 expects the jump quirk, right
+    ///   after one that sets V0 but not Vx it doesn't
+    /// - Fx55 or Fx65 right after another one expects I to move past the registers
+    pub fn suggest_quirks(&self) -> QuirkSuggestion {
+        let mut quirks = Quirks::default();
+        let mut reasons = Vec::new();
+        let describe = |addr: &usize, instr: &u16| format!("{instr:04X} ({}) at {addr:#05x}", disassemble(*instr));
+
+        if self.platform > Platform::Chip8 {
+            quirks = platform_quirks(self.platform);
+            let platform = self.platform;
+            if let Some((addr, instr)) = self.code.iter().find(|(_, instr)| decode(**instr).is_some_and(|(_, p, _)| p == platform)) {
+                reasons.push(format!("{platform} quirks: {instr:04X} at {addr:#05x} is a {platform} instruction"));
+            }
+        }
+
+        let x = |instr: u16| (instr >> 8 & 0xf) as usize;
+        let y = |instr: u16| (instr >> 4 & 0xf) as usize;
+        let shift = self.code.iter().find(|(_, instr)| matches!(**instr & 0xf00f, 0x8006 | 0x800e) && x(**instr) != y(**instr));
+        if let (Platform::Chip8, Some((addr, instr))) = (self.platform, shift) {
+            quirks.shift = false;
+            reasons.push(format!("shift off: {} shifts VY into VX like the VIP", describe(addr, instr)));
+        }
+
+        let previous = |addr: usize| addr.checked_sub(2).and_then(|addr| self.code.get(&addr)).copied();
+        let jumps = self.code.iter().filter(|(_, instr)| **instr >> 12 == 0xb && x(**instr) != 0);
+        for (addr, instr) in jumps {
+            let Some(setup) = previous(*addr) else { continue };
+            let (sets_x, sets_v0) = (sets(setup, x(*instr)), sets(setup, 0));
+            if sets_x != sets_v0 {
+                quirks.jump = sets_x;
+                let (on, register) = if sets_x { ("on", format!("V{:X}", x(*instr))) } else { ("off", "V0".into()) };
+                reasons.push(format!("jump {on}: {} follows {:04X}, which sets {register}", describe(addr, instr), setup));
+                break;
+            }
+        }
+
+        let memory = |instr: u16| matches!(instr & 0xf0ff, 0xf055 | 0xf065);
+        let repeated = self.code.iter().find(|(addr, instr)| memory(**instr) && self.code.get(&(*addr + 2)).is_some_and(|next| memory(*next)));
+        if let Some((addr, instr)) = repeated {
+            quirks.memory_leave_i_unchanged = false;
+            reasons.push(format!("memory leaves I unchanged off: {} is followed by another without setting I", describe(addr, instr)));
+        }
+
+        QuirkSuggestion { quirks, reasons }
+    }
+}
+
+// The quirks of the platform in the community database
+fn platform_quirks(platform: Platform) -> Quirks {
+    match platform {
+        Platform::Chip8 => Quirks::default(),
+        Platform::SuperChip => Quirks {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: false,
+            jump: true,
+            vblank: false,
+            logic: false,
+        },
+        Platform::XoChip => Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        },
+    }
+}
+
+// True if `instr` writes V`reg`
+fn sets(instr: u16, reg: usize) -> bool {
+    let x = (instr >> 8 & 0xf) as usize;
+    match (instr >> 12, instr & 0xff) {
+        (0x6 | 0x7 | 0x8 | 0xc, _) | (0xf, 0x07 | 0x0a) => x == reg,
+        (0xf, 0x65) => reg <= x,
+        _ => false,
+    }
+}
+
+// How control continues after an instruction
+enum Flow {
+    Next,
+    Skip,
+    Jump(usize),
+    Call(usize),
+    Indirect(usize),
+    Stop,
+}
+
+/// Walks `program` as it would be loaded at the reset vector
+pub fn analyze(program: &[u8]) -> Analysis {
+    let start = Chip8::RESET_VECTOR;
+    let end = start + program.len();
+    let word = |addr: usize| -> u16 {
+        let byte = |addr: usize| if (start..end).contains(&addr) { program[addr - start] as u16 } else { 0 };
+        byte(addr) << 8 | byte(addr + 1)
+    };
+
+    let mut code = BTreeMap::new();
+    let mut findings = Vec::new();
+    let mut data_starts = BTreeSet::new();
+    let mut extensions = BTreeMap::new();
+    let mut platform = Platform::Chip8;
+    // Bytes of the reachable instructions
+    let mut covered = BTreeSet::new();
+    let mut pending = if program.is_empty() { Vec::new() } else { Vec::from([start]) };
+
+    while let Some(pc) = pending.pop() {
+        if code.insert(pc, word(pc)).is_some() {
+            continue;
+        }
+
+        // XO-CHIP's F000 nnnn is four bytes long, skips step over all of it
+        let length = |addr: usize| if word(addr) == 0xf000 { 4 } else { 2 };
+        let next = pc + length(pc);
+        covered.extend(pc..next);
+
+        let instr = word(pc);
+        let finding = |issue| Finding { addr: pc, instr: Some(instr), issue };
+        let Some((class, instr_platform, flow)) = decode(instr) else {
+            findings.push(finding(Issue::InvalidOpcode));
+            continue;
+        };
+        if instr_platform > Platform::Chip8 {
+            *extensions.entry(class).or_insert(0) += 1;
+        }
+        platform = platform.max(instr_platform);
+        if opcode_class(instr).is_none() {
+            findings.push(finding(Issue::Unimplemented { platform: instr_platform }));
+        }
+        for quirk in quirks(instr) {
+            findings.push(finding(Issue::QuirkDependent { quirk: *quirk }));
+        }
+        if instr >> 12 == 0xa {
+            data_starts.insert((instr & 0xfff) as usize);
+        }
+
+        let mut targets = Vec::new();
+        match flow {
+            Flow::Next => targets.push(next),
+            Flow::Skip => targets.extend([next, next + length(next)]),
+            Flow::Jump(target) => targets.push(target),
+            Flow::Call(target) => targets.extend([target, next]),
+            Flow::Indirect(target) => {
+                findings.push(finding(Issue::IndirectJump));
+                if target + 0xff >= Chip8::MEM_SIZE {
+                    findings.push(finding(Issue::LeavesMemory { target: target + 0xff }));
+                }
+                targets.push(target);
+            }
+            Flow::Stop => (),
+        }
+        for target in targets {
+            if target + 1 >= Chip8::MEM_SIZE {
+                findings.push(finding(Issue::LeavesMemory { target }));
+            } else if !(start..end).contains(&target) {
+                findings.push(finding(Issue::LeavesProgram { target }));
+            } else {
+                pending.push(target);
+            }
+        }
+    }
+
+    // Split what the walk didn't reach where an Annn points into it
+    let covered = |addr: usize| covered.contains(&addr);
+    let mut data = Vec::new();
+    let mut addr = start;
+    while addr < end {
+        if covered(addr) {
+            addr += 1;
+            continue;
+        }
+        let range_start = addr;
+        while addr < end && !covered(addr) {
+            addr += 1;
+        }
+        let mut piece_start = range_start;
+        let splits = data_starts.range(range_start + 1..addr).copied().chain([addr]);
+        for split in splits {
+            if data_starts.contains(&piece_start) {
+                data.push(piece_start..split);
+            } else {
+                findings.push(Finding { addr: piece_start, instr: None, issue: Issue::Unreachable { end: split } });
+            }
+            piece_start = split;
+        }
+    }
+
+    findings.sort_by_key(|finding| finding.addr);
+    Analysis { code, data, findings, platform, extensions }
+}
+
+// The family, the platform that introduced it and the control flow, None for invalid opcodes
+fn decode(instr: u16) -> Option<(&'static str, Platform, Flow)> {
+    use Platform::*;
+    let nnn = (instr & 0xfff) as usize;
+    let decoded = match (instr >> 12, instr >> 8 & 0xf, instr >> 4 & 0xf, instr & 0xf) {
+        (0, 0, 0xe, 0x0) => ("00E0", Chip8, Flow::Next),
+        (0, 0, 0xe, 0xe) => ("00EE", Chip8, Flow::Stop),
+        (0, 0, 0xc, n) if n > 0 => ("00Cn", SuperChip, Flow::Next),
+        (0, 0, 0xd, n) if n > 0 => ("00Dn", XoChip, Flow::Next),
+        (0, 0, 0xf, 0xb) => ("00FB", SuperChip, Flow::Next),
+        (0, 0, 0xf, 0xc) => ("00FC", SuperChip, Flow::Next),
+        (0, 0, 0xf, 0xd) => ("00FD", SuperChip, Flow::Stop),
+        (0, 0, 0xf, 0xe) => ("00FE", SuperChip, Flow::Next),
+        (0, 0, 0xf, 0xf) => ("00FF", SuperChip, Flow::Next),
+        // Calls a machine code routine of the host, nothing to follow
+        (0x0, ..) if instr != 0 => ("0nnn", Chip8, Flow::Next),
+        (0x1, ..) => ("1nnn", Chip8, Flow::Jump(nnn)),
+        (0x2, ..) => ("2nnn", Chip8, Flow::Call(nnn)),
+        (0x3, ..) => ("3xnn", Chip8, Flow::Skip),
+        (0x4, ..) => ("4xnn", Chip8, Flow::Skip),
+        (0x5, _, _, 0x0) => ("5xy0", Chip8, Flow::Skip),
+        (0x5, _, _, 0x2) => ("5xy2", XoChip, Flow::Next),
+        (0x5, _, _, 0x3) => ("5xy3", XoChip, Flow::Next),
+        (0x6, ..) => ("6xnn", Chip8, Flow::Next),
+        (0x7, ..) => ("7xnn", Chip8, Flow::Next),
+        (0x8, _, _, 0x0..=0x7 | 0xe) => ("8xyn", Chip8, Flow::Next),
+        (0x9, _, _, 0x0) => ("9xy0", Chip8, Flow::Skip),
+        (0xa, ..) => ("Annn", Chip8, Flow::Next),
+        (0xb, ..) => ("Bnnn", Chip8, Flow::Indirect(nnn)),
+        (0xc, ..) => ("Cxnn", Chip8, Flow::Next),
+        (0xd, _, _, 0x0) => ("Dxy0", SuperChip, Flow::Next),
+        (0xd, ..) => ("Dxyn", Chip8, Flow::Next),
+        (0xe, _, 0x9, 0xe) => ("Ex9E", Chip8, Flow::Skip),
+        (0xe, _, 0xa, 0x1) => ("ExA1", Chip8, Flow::Skip),
+        (0xf, 0x0, 0x0, 0x0) => ("F000", XoChip, Flow::Next),
+        (0xf, _, 0x0, 0x1) => ("Fn01", XoChip, Flow::Next),
+        (0xf, 0x0, 0x0, 0x2) => ("F002", XoChip, Flow::Next),
+        (0xf, _, 0x0, 0x7) => ("Fx07", Chip8, Flow::Next),
+        (0xf, _, 0x0, 0xa) => ("Fx0A", Chip8, Flow::Next),
+        (0xf, _, 0x1, 0x5) => ("Fx15", Chip8, Flow::Next),
+        (0xf, _, 0x1, 0x8) => ("Fx18", Chip8, Flow::Next),
+        (0xf, _, 0x1, 0xe) => ("Fx1E", Chip8, Flow::Next),
+        (0xf, _, 0x2, 0x9) => ("Fx29", Chip8, Flow::Next),
+        (0xf, _, 0x3, 0x0) => ("Fx30", SuperChip, Flow::Next),
+        (0xf, _, 0x3, 0x3) => ("Fx33", Chip8, Flow::Next),
+        (0xf, _, 0x3, 0xa) => ("Fx3A", XoChip, Flow::Next),
+        (0xf, _, 0x5, 0x5) => ("Fx55", Chip8, Flow::Next),
+        (0xf, _, 0x6, 0x5) => ("Fx65", Chip8, Flow::Next),
+        (0xf, _, 0x7, 0x5) => ("Fx75", SuperChip, Flow::Next),
+        (0xf, _, 0x8, 0x5) => ("Fx85", SuperChip, Flow::Next),
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+fn quirks(instr: u16) -> &'static [Quirk] {
+    match (instr >> 12, instr & 0xf, instr & 0xff) {
+        (0x8, 0x6 | 0xe, _) => &[Quirk::Shift],
+        (0x8, 0x1..=0x3, _) => &[Quirk::Logic],
+        (0xb, ..) => &[Quirk::Jump],
+        (0xd, ..) => &[Quirk::Wrap, Quirk::VBlank],
+        (0xf, _, 0x55 | 0x65) => &[Quirk::MemoryIncrement],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests;
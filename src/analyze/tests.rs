@@ -0,0 +1,128 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{analyze, Finding, Issue, Platform, Quirk};
+use crate::chip8::Quirks;
+
+const PROGRAM: &[u8] = &[
+    0x22, 0x0a,     // 0x200: call 0x20a
+    0x30, 0x01,     // 0x202: skip if v0 == 1
+    0x12, 0x02,     // 0x204: jump 0x202
+    0x12, 0x08,     // 0x206: jump 0x208
+    0x12, 0x08,     // 0x208: loop forever
+    0xa2, 0x12,     // 0x20a: i = 0x212
+    0xd0, 0x11,     // 0x20c: draw 1 row
+    0x00, 0xee,     // 0x20e: return
+    0x60, 0x00,     // 0x210: never reached
+    0xf0,           // 0x212: sprite
+];
+
+fn issues(findings: &[Finding]) -> Vec<(usize, Issue)> {
+    findings.iter().map(|finding| (finding.addr, finding.issue)).collect()
+}
+
+#[test]
+fn test_reachable_code_and_data() {
+    let analysis = analyze(PROGRAM);
+    assert_eq!(analysis.code.keys().copied().collect::<Vec<_>>(), [0x200, 0x202, 0x204, 0x206, 0x208, 0x20a, 0x20c, 0x20e]);
+    assert_eq!(analysis.data, vec![0x212..0x213]);
+    assert_eq!(analysis.platform, Platform::Chip8);
+    assert!(!analysis.has_errors());
+    assert_eq!(
+        issues(&analysis.findings),
+        [
+            (0x20c, Issue::QuirkDependent { quirk: Quirk::Wrap }),
+            (0x20c, Issue::QuirkDependent { quirk: Quirk::VBlank }),
+            (0x210, Issue::Unreachable { end: 0x212 }),
+        ]
+    );
+}
+
+#[test]
+fn test_errors() {
+    let program = [
+        0xf0, 0x33,     // 0x200: bcd, not implemented
+        0x80, 0x16,     // 0x202: shift
+        0xf2, 0x55,     // 0x204: store
+        0xbf, 0x80,     // 0x206: jump v0 + 0xf80
+        0xff, 0xff,     // 0x208
+    ];
+    let analysis = analyze(&program);
+    assert!(analysis.has_errors());
+    let found = issues(&analysis.findings);
+    assert!(found.contains(&(0x200, Issue::Unimplemented { platform: Platform::Chip8 })));
+    assert!(found.contains(&(0x202, Issue::QuirkDependent { quirk: Quirk::Shift })));
+    assert!(found.contains(&(0x204, Issue::QuirkDependent { quirk: Quirk::MemoryIncrement })));
+    assert!(found.contains(&(0x206, Issue::IndirectJump)));
+    assert!(found.contains(&(0x206, Issue::LeavesMemory { target: 0x107f })));
+    assert!(found.contains(&(0x206, Issue::LeavesProgram { target: 0xf80 })));
+    assert!(found.contains(&(0x208, Issue::Unreachable { end: 0x20a })));
+
+    let analysis = analyze(&[0xff, 0xff]);
+    assert_eq!(issues(&analysis.findings), [(0x200, Issue::InvalidOpcode)]);
+
+    // Falling off the end of the ROM
+    let analysis = analyze(&[0x60, 0x00]);
+    assert_eq!(issues(&analysis.findings), [(0x200, Issue::LeavesProgram { target: 0x202 })]);
+}
+
+#[test]
+fn test_platform() {
+    let schip = [0x00, 0xff, 0x12, 0x02];
+    let analysis = analyze(&schip);
+    assert_eq!(analysis.platform, Platform::SuperChip);
+    assert_eq!(analysis.extensions.get("00FF"), Some(&1));
+    assert_eq!(issues(&analysis.findings), [(0x200, Issue::Unimplemented { platform: Platform::SuperChip })]);
+
+    // F000 nnnn is four bytes, the skip before it has to step over all of them
+    let xo = [
+        0x30, 0x00,                 // 0x200: skip if v0 == 0
+        0xf0, 0x00, 0x02, 0x0a,     // 0x202: i = 0x20a
+        0x00, 0xfb,                 // 0x206: scroll right
+        0x12, 0x08,                 // 0x208: loop forever
+    ];
+    let analysis = analyze(&xo);
+    assert_eq!(analysis.platform, Platform::XoChip);
+    assert_eq!(analysis.code.keys().copied().collect::<Vec<_>>(), [0x200, 0x202, 0x206, 0x208]);
+    assert!(!analysis.findings.iter().any(|finding| matches!(finding.issue, Issue::Unreachable { .. })));
+}
+
+#[test]
+fn test_suggest_quirks() {
+    let suggestion = analyze(PROGRAM).suggest_quirks();
+    assert_eq!(suggestion.quirks, Quirks::default());
+    assert!(suggestion.reasons.is_empty());
+
+    let program = [
+        0x81, 0x26,     // 0x200: v1 = v2 >> 1
+        0xf1, 0x65,     // 0x202: load v0..v1
+        0xf1, 0x65,     // 0x204: load v0..v1
+        0x62, 0x00,     // 0x206: v2 = 0
+        0xb2, 0x0a,     // 0x208: jump v2 + 0x20a
+        0x12, 0x0a,     // 0x20a: loop forever
+    ];
+    let suggestion = analyze(&program).suggest_quirks();
+    assert_eq!(suggestion.quirks, Quirks { shift: false, jump: true, ..Quirks::default() });
+    assert_eq!(suggestion.reasons.len(), 3);
+    assert!(suggestion.reasons[0].starts_with("shift off: 8126 (SHR V1, V2) at 0x200"));
+    assert!(suggestion.reasons[1].starts_with("jump on: B20A"));
+    assert!(suggestion.reasons[2].starts_with("memory leaves I unchanged off: F165 (LD V1, [I]) at 0x202"));
+
+    // SUPER-CHIP shifts in place whatever y is
+    let schip = [0x00, 0xff, 0x81, 0x26, 0x12, 0x04];
+    let suggestion = analyze(&schip).suggest_quirks();
+    assert!(suggestion.quirks.shift && suggestion.quirks.memory_leave_i_unchanged && suggestion.quirks.jump);
+    assert_eq!(suggestion.reasons, ["SUPER-CHIP quirks: 00FF at 0x200 is a SUPER-CHIP instruction"]);
+}
+
+#[test]
+fn test_report() {
+    let mut report = String::new();
+    analyze(PROGRAM).write_report(&mut report).unwrap();
+    assert!(report.starts_with("Platform: CHIP-8\n8 instructions reachable from 0x200\n"));
+    assert!(report.contains("0x210"));
+    assert!(report.contains("Unreachable up to 0x211"));
+    assert!(report.contains("            wrap: 0x20c"));
+    assert!(report.contains("0x212-0x212  1 bytes"));
+}
@@ -0,0 +1,106 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, GrayImage, ImageResult, Rgba, RgbaImage};
+
+use crate::chip8::Chip8;
+use crate::crt::CrtParams;
+
+// Scale applied to recorded framebuffers so clips are watchable without resampling
+const RECORDING_SCALE: u32 = 4;
+
+// Builds a unique file name in the working directory, e.g. "chip8-1666000000123-raw.png"
+pub fn capture_path(suffix: &str) -> PathBuf {
+    let [path] = capture_paths([suffix]);
+    path
+}
+
+// Like `capture_path` for files that belong together, all names share one stem
+pub fn capture_paths<const N: usize>(suffixes: [&str; N]) -> [PathBuf; N] {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    suffixes.map(|suffix| PathBuf::from(format!("chip8-{millis}{suffix}")))
+}
+
+// Saves the raw 64x32 framebuffer as a grayscale PNG
+pub fn save_framebuffer(pixels: &[u8], path: &Path) -> ImageResult<()> {
+    let image = GrayImage::from_raw(Chip8::DISPLAY_WIDTH as u32, Chip8::DISPLAY_HEIGHT as u32, pixels.to_vec())
+        .expect("Framebuffer has the wrong size");
+    image.save(path)
+}
+
+// Renders the framebuffer with the palette colors, each pixel scaled to a `scale` x `scale` block
+pub fn colorize(pixels: &[u8], params: &CrtParams, scale: u32) -> RgbaImage {
+    let to_rgba = |c: [f32; 4]| Rgba(c.map(|v| (v.clamp(0.0, 1.0) * 255.0) as u8));
+    let (fg, bg) = (to_rgba(params.foreground), to_rgba(params.background));
+    let width = Chip8::DISPLAY_WIDTH as u32;
+    RgbaImage::from_fn(width * scale, Chip8::DISPLAY_HEIGHT as u32 * scale, |x, y| {
+        let px = pixels[((y / scale) * width + x / scale) as usize];
+        if px != 0 { fg } else { bg }
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Gif,
+    PngSequence,
+}
+
+enum Sink {
+    Gif(Box<GifEncoder<BufWriter<File>>>),
+    PngSequence(PathBuf),
+}
+
+// Records the framebuffer every frame, either to an animated GIF or to a
+// directory of numbered PNG files.
+pub struct Recorder {
+    sink: Sink,
+    path: PathBuf,
+    frames: u32,
+}
+
+impl Recorder {
+    pub fn start(format: RecordingFormat) -> ImageResult<Recorder> {
+        let (sink, path) = match format {
+            RecordingFormat::Gif => {
+                let path = capture_path(".gif");
+                // Frames only hold two colors, so the fastest quantizer setting loses nothing
+                let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(&path)?), 30);
+                encoder.set_repeat(Repeat::Infinite)?;
+                (Sink::Gif(Box::new(encoder)), path)
+            }
+            RecordingFormat::PngSequence => {
+                let path = capture_path("");
+                fs::create_dir_all(&path)?;
+                (Sink::PngSequence(path.clone()), path)
+            }
+        };
+
+        Ok(Recorder { sink, path, frames: 0 })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn record(&mut self, pixels: &[u8], params: &CrtParams) -> ImageResult<()> {
+        let image = colorize(pixels, params, RECORDING_SCALE);
+        match &mut self.sink {
+            Sink::Gif(encoder) => {
+                let delay = Delay::from_numer_denom_ms(1000, 60);
+                encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+            }
+            Sink::PngSequence(dir) => {
+                image.save(dir.join(format!("frame-{:05}.png", self.frames)))?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
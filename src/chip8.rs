@@ -1,12 +1,44 @@
-use std::ops::{BitAnd, BitXor, BitOr};
-
-use rand::RngCore;
-
-pub struct Chip8<'program> {
-    memory: [u8; Chip8::MEM_SIZE],
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+use core::ops::{BitAnd, BitXor, BitOr};
+
+use rand::{RngCore, SeedableRng};
+use rand::rngs::SmallRng;
+
+mod breakpoints;
+mod bus;
+mod config;
+mod coverage;
+mod framebuffer;
+mod memory;
+mod opcode;
+mod profile;
+mod snapshot;
+mod state;
+pub use breakpoints::Breakpoints;
+pub use bus::{Bus, BusError, FlatRam, WriteProtect};
+pub use config::{Config, Quirks};
+pub use coverage::{Coverage, CoverageError};
+pub use framebuffer::Framebuffer;
+pub use memory::{Fault, MemoryPolicy};
+pub use opcode::{disassemble, opcode_class};
+pub use profile::{Profiler, SubroutineStats};
+pub use snapshot::SnapshotError;
+pub use state::State;
+
+/// A CHIP-8 machine running one program.
+///
+/// `B` is the bus the interpreter reads and writes memory through, plain 4K of RAM by default.
+/// The program is either borrowed from the caller or owned, see [`Chip8::new`] and [`Chip8::from_rom`].
+pub struct Chip8<'program, B: Bus = FlatRam> {
+    memory: B,
     stack: [usize; Chip8::STACK_SIZE],
-    program: &'program[u8],
-    display: [u8; Chip8::DISPLAY_SIZE],
+    program: Cow<'program, [u8]>,
+    display: Framebuffer,
     keys: [u8; 16],
     reg: [u8; 16],
     i: usize,
@@ -14,7 +46,13 @@ pub struct Chip8<'program> {
     pc: usize,
     delay_timer: u8,
     sound_timer: u8,
-    waiting_for_key: Option<u8>
+    waiting_for_key: Option<u8>,
+    waiting_for_vblank: bool,
+    config: Config,
+    rng: Box<dyn RngCore + Send + Sync>,
+    profiler: Option<Box<Profiler>>,
+    coverage: Option<Box<Coverage>>,
+    cycles: u64
 }
 
 const FONT: &[u8] = &[
@@ -40,12 +78,60 @@ impl <'program> Chip8<'program> {
     pub const DISPLAY_WIDTH: usize = 64;
     pub const DISPLAY_HEIGHT: usize = 32;
     pub const DISPLAY_SIZE: usize = (Chip8::DISPLAY_WIDTH * Chip8::DISPLAY_HEIGHT) as usize;
+    /// Largest program that fits between the reset vector and the end of memory
+    pub const MAX_PROGRAM_SIZE: usize = Chip8::MEM_SIZE - Chip8::RESET_VECTOR;
     const STACK_SIZE: usize = 16;
-    const MEM_SIZE: usize = 4096;
-    const RESET_VECTOR: usize = 0x200;
-
+    /// Bytes of memory, addresses are 12 bits wide
+    pub const MEM_SIZE: usize = 4096;
+    /// Where programs are loaded and execution starts
+    pub const RESET_VECTOR: usize = 0x200;
+    /// The hex digit sprites `Fx29` points at fill the first `FONT_SIZE` bytes of memory
+    pub const FONT_SIZE: usize = FONT.len();
+    /// Time between two [`Chip8::tick_60hz`] calls
+    pub const TICK: Duration = Duration::from_micros(16_667);
+
+    /// Creates a machine with flat RAM running a borrowed program.
+    ///
+    /// Panics if the program is larger than [`Chip8::MAX_PROGRAM_SIZE`].
     pub fn new(program: &'program[u8]) -> Self {
-        let memory = [0; Chip8::MEM_SIZE];
+        Chip8::with_bus(program, FlatRam::new())
+    }
+
+    /// Checks a ROM against [`Chip8::MAX_PROGRAM_SIZE`] before loading it
+    pub fn check_program_size(len: usize) -> Result<(), ProgramTooLarge> {
+        if len > Chip8::MAX_PROGRAM_SIZE {
+            return Err(ProgramTooLarge(len));
+        }
+        Ok(())
+    }
+}
+
+/// A program larger than [`Chip8::MAX_PROGRAM_SIZE`], with its size in bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgramTooLarge(pub usize);
+
+impl fmt::Display for ProgramTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ROM is {} bytes, at most {} fit in memory", self.0, Chip8::MAX_PROGRAM_SIZE)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProgramTooLarge {}
+
+impl Chip8<'static> {
+    /// Creates a machine with flat RAM that owns its program, e.g. a ROM read from disk.
+    pub fn from_rom(rom: Vec<u8>) -> Self {
+        Chip8::with_bus(rom, FlatRam::new())
+    }
+}
+
+impl <'program, B: Bus> Chip8<'program, B> {
+    /// Creates a machine that accesses memory through `memory`.
+    /// The font and program are loaded with [`Bus::load`], bypassing any write protection.
+    pub fn with_bus(program: impl Into<Cow<'program, [u8]>>, memory: B) -> Self {
+        let program = program.into();
+        assert!(program.len() <= Chip8::MAX_PROGRAM_SIZE, "Program too large: {} bytes", program.len());
 
         let mut c8 = Chip8 {
             reg: [0; 16],
@@ -56,28 +142,79 @@ impl <'program> Chip8<'program> {
             program,
             keys: [0; 16],
             stack: [0; Chip8::STACK_SIZE],
-            display: [0; (Chip8::DISPLAY_WIDTH * Chip8::DISPLAY_HEIGHT) as usize],
+            display: Framebuffer::new(Chip8::DISPLAY_WIDTH, Chip8::DISPLAY_HEIGHT),
             delay_timer: 0,
             sound_timer: 0,
-            waiting_for_key: None
+            waiting_for_key: None,
+            waiting_for_vblank: false,
+            config: Config::default(),
+            rng: Box::new(default_rng()),
+            profiler: None,
+            coverage: None,
+            cycles: 0
         };
         c8.reset();
         c8
     }
 
+    /// Replaces the program and resets the machine.
+    ///
+    /// Panics if the program is larger than [`Chip8::MAX_PROGRAM_SIZE`].
+    pub fn load_program(&mut self, program: impl Into<Cow<'program, [u8]>>) {
+        let program = program.into();
+        assert!(program.len() <= Chip8::MAX_PROGRAM_SIZE, "Program too large: {} bytes", program.len());
+        self.program = program;
+        self.reset();
+    }
+
+    /// Replaces the program without resetting, for reloading a ROM that was edited while it runs.
+    /// Only the bytes that differ from the old program are written, so memory the program changed
+    /// at run time keeps its value unless the new program changes it too. Bytes of the old program
+    /// past the end of the new one are cleared. Registers, timers, the stack and the display are
+    /// kept. Returns the number of bytes written.
+    ///
+    /// Panics if the program is larger than [`Chip8::MAX_PROGRAM_SIZE`].
+    pub fn patch_program(&mut self, program: impl Into<Cow<'program, [u8]>>) -> usize {
+        let program = program.into();
+        assert!(program.len() <= Chip8::MAX_PROGRAM_SIZE, "Program too large: {} bytes", program.len());
+        let mut written = 0;
+        for offset in 0..program.len().max(self.program.len()) {
+            let new = program.get(offset).copied().unwrap_or(0);
+            if self.program.get(offset) != Some(&new) {
+                self.memory.poke(Chip8::RESET_VECTOR + offset, new);
+                written += 1;
+            }
+        }
+        self.program = program;
+        written
+    }
+
+    pub fn program(&self) -> &[u8] {
+        &self.program
+    }
+
+    /// Jumps back to the reset vector and reloads the font and program.
+    /// Registers and key states are cleared, the display is left as is.
     pub fn reset(&mut self) {
         self.pc = Chip8::RESET_VECTOR;
         self.reg = [0; 16];
         self.sp = 0;
         self.keys = [0; 16];
-        self.memory[Chip8::RESET_VECTOR..Chip8::RESET_VECTOR + self.program.len()].copy_from_slice(self.program);
-        self.memory[0..FONT.len()].copy_from_slice(FONT);
+        self.waiting_for_vblank = false;
+        self.memory.load(Chip8::RESET_VECTOR, &self.program);
+        self.memory.load(0, FONT);
     }
 
+    /// Counts the timers down to 0, call at 60Hz independently of [`Chip8::step`].
     pub fn tick_60hz(&mut self) {
-        self.delay_timer = self.delay_timer.wrapping_sub(1);
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.waiting_for_vblank = false;
     }
 
+    /// Presses or releases one of the 16 keys, `key` is the hex digit on the keypad.
+    ///
+    /// Panics if `key` is not below 16.
     pub fn set_key_state(&mut self, key: u8, pressed: bool) {
         if key >= 16 {
             panic!("Invalid key");
@@ -90,30 +227,164 @@ impl <'program> Chip8<'program> {
         self.keys[key as usize] = if pressed { 255 } else { 0 };
     }
 
-    pub fn pixels(&self) -> &[u8] {
+    /// Releases every key. Unlike releasing them one by one with [`Chip8::set_key_state`] this
+    /// doesn't end an Fx0A wait, for hosts that drop the keys between inputs or on focus loss.
+    pub fn release_keys(&mut self) {
+        self.keys = [0; 16];
+    }
+
+    pub fn key_state(&self, key: u8) -> bool {
+        self.keys[key as usize] != 0
+    }
+
+    /// True while an Fx0A instruction blocks execution until a key is pressed
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key.is_some()
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
         &self.display
     }
 
-    pub fn step(&mut self, count: usize) {
-        if self.waiting_for_key.is_some() {
-            return;
+    /// One byte per pixel copy of the display, 255 for lit pixels
+    pub fn pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0; Chip8::DISPLAY_SIZE];
+        self.display.unpack(&mut pixels);
+        pixels
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.memory
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.memory
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    pub fn memory_policy(&self) -> MemoryPolicy {
+        self.config.memory_policy
+    }
+
+    pub fn set_memory_policy(&mut self, policy: MemoryPolicy) {
+        self.config.memory_policy = policy;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.config.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.config.quirks = quirks;
+    }
+
+    /// Replaces the random number generator used by Cxkk. The default is seeded from the OS
+    /// with the `std` feature and with a fixed seed without it.
+    pub fn set_rng(&mut self, rng: impl RngCore + Send + Sync + 'static) {
+        self.rng = Box::new(rng);
+    }
+
+    /// Snapshot of the registers, stack and timers
+    pub fn state(&self) -> State {
+        State {
+            pc: self.pc,
+            i: self.i,
+            sp: self.sp,
+            reg: self.reg,
+            stack: self.stack,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            waiting_for_key: self.waiting_for_key,
+            cycles: self.cycles,
         }
+    }
+
+    /// Restores registers, stack and timers from a snapshot. Memory and the display are not part of
+    /// the snapshot, use [`Chip8::bus_mut`] to restore memory or [`Chip8::save_snapshot`] to save
+    /// the whole machine.
+    pub fn set_state(&mut self, state: &State) {
+        self.pc = state.pc;
+        self.i = state.i;
+        self.sp = state.sp;
+        self.reg = state.reg;
+        self.stack = state.stack;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.waiting_for_key = state.waiting_for_key;
+        self.cycles = state.cycles;
+    }
+
+    /// Starts profiling every instruction [`Chip8::step`] executes with `profiler`, None stops.
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler.map(Box::new);
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    /// Stops profiling and returns what was counted
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take().map(|profiler| *profiler)
+    }
+
+    /// Starts marking the memory [`Chip8::step`] uses in `coverage`, None stops.
+    pub fn set_coverage(&mut self, coverage: Option<Coverage>) {
+        self.coverage = coverage.map(Box::new);
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+
+    /// Stops tracking coverage and returns it
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take().map(|coverage| *coverage)
+    }
+
+    /// Total number of instructions executed since creation
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Executes up to `count` instructions. Returns early without executing anything while waiting
+    /// for a key, or with the vblank quirk after a draw until the next [`Chip8::tick_60hz`].
+    /// On a fault pc is left at the faulting instruction.
+    pub fn step(&mut self, count: usize) -> Result<(), Fault> {
+        if self.waiting_for_key.is_some() || self.waiting_for_vblank {
+            return Ok(());
+        }
+        let quirks = self.config.quirks;
 
-        let mem = self.memory;
         for _c in 0..count {
+            // Fetch from live memory so code written by Fx55 is seen by the next fetch
             let pc = self.pc;
-            let instr: u16 = (mem[pc] as u16) << 8 | mem[pc + 1] as u16;
+            let instr: u16 = (self.read_byte(pc)? as u16) << 8 | self.read_byte(pc + 1)? as u16;
 
             let n3 = instr as usize >> 12 & 0xf;
             let n2 = instr as usize >> 8  & 0xf;
             let n1 = instr as usize >> 4  & 0xf;
             let n0 = instr as usize >> 0  & 0xf;
             let b0 = (instr & 0xff) as u8;
+            self.cycles += 1;
+            if let Some(profiler) = &mut self.profiler {
+                profiler.record(pc, instr);
+            }
+            if let Some(coverage) = &mut self.coverage {
+                coverage.mark_instruction(pc);
+            }
 
             match (n3, n2, n1, n0) {
                 // Clear display
                 (0, 0, 0xe, 0x0) => {
-                    self.display = [0; Chip8::DISPLAY_SIZE];
+                    self.display.clear();
                     self.pc += 2;
                 }
 
@@ -170,6 +441,11 @@ impl <'program> Chip8<'program> {
                 (8, ..) => {
                     let dst = self.reg[n2];
                     let src = self.reg[n1];
+                    // Without the shift quirk SHR and SHL shift Vy into Vx
+                    let shifted = if quirks.shift { dst } else { src };
+                    if quirks.logic && (1..=3).contains(&n0) {
+                        self.reg[15] = 0;
+                    }
                     self.reg[n2] = match n0 {
                         0 => src,               // LD
                         1 => dst.bitor(src),    // OR
@@ -185,20 +461,20 @@ impl <'program> Chip8<'program> {
                             dst.wrapping_sub(src)
                         },
                         6 => {  // SHR
-                            self.reg[15] = if dst & 1 != 0 { 1 } else { 0 };     // lsb
-                            dst >> 1
+                            self.reg[15] = if shifted & 1 != 0 { 1 } else { 0 };     // lsb
+                            shifted >> 1
                         },
                         7 => {  // SUBN
                             self.reg[15] = if dst > src { 1 } else { 0 };   // overflow
                             src.wrapping_sub(dst)
                         },
                         0xe => {  // SHL
-                            self.reg[15] = if dst & 0x80 != 0 { 1 } else { 0 };     // msb
-                            dst << 1
+                            self.reg[15] = if shifted & 0x80 != 0 { 1 } else { 0 };     // msb
+                            shifted << 1
                         },
 
                         _=> {
-                            panic!("{:#04x} is not an instruction", instr);
+                            return Err(Fault::Unimplemented { pc, instr });
                         }
                     };
                     self.pc += 2;
@@ -216,14 +492,15 @@ impl <'program> Chip8<'program> {
                     self.pc += 2;
                 },
 
-                // Jump to gp0 + immediate
+                // Jump to gp0 + immediate, or gpx + immediate with the jump quirk
                 (0xb, ..) => {
-                    self.pc = ((instr & 0xfff) + self.reg[0] as u16) as usize;
+                    let offset = if quirks.jump { self.reg[n2] } else { self.reg[0] };
+                    self.pc = ((instr & 0xfff) + offset as u16) as usize;
                 },
 
                 // RND
                 (0xc, ..) => {
-                    self.reg[n2] = rand::thread_rng().next_u32() as u8 & b0;
+                    self.reg[n2] = self.rng.next_u32() as u8 & b0;
                     self.pc += 2;
                 },
 
@@ -234,20 +511,22 @@ impl <'program> Chip8<'program> {
                     let ox = self.reg[n2] as usize;
                     let oy = self.reg[n1] as usize;
                     let sprite_height = n0;
-                    let mut collision = false;
-
-                    for y in 0..sprite_height {
-                        let sprite_line = self.memory[sprite_ptr + y];
-                        for x in 0..8 {
-                            let sx = (ox + x) & 63;
-                            let sy = (oy + y) & 31;
-                            let px = if (sprite_line >> (7 - x)) & 0x01 == 1 { 255 }  else { 0 };
-                            let prev = self.xor_pixel(sx, sy, px);
-                            collision |= px != 0 && prev != 0;
-                        }
+
+                    let mut sprite = [0; 15];
+                    for (y, line) in sprite[..sprite_height].iter_mut().enumerate() {
+                        *line = self.read_byte(sprite_ptr + y)?;
+                    }
+                    if let Some(coverage) = &mut self.coverage {
+                        coverage.mark_sprite(sprite_ptr, sprite_height);
                     }
+                    let collision = if quirks.wrap {
+                        self.display.xor_sprite(0, ox, oy, &sprite[..sprite_height])
+                    } else {
+                        self.display.xor_sprite_clipped(0, ox, oy, &sprite[..sprite_height])
+                    };
 
                     self.reg[15] = if collision { 1 } else { 0 };
+                    self.waiting_for_vblank = quirks.vblank;
                     self.pc += 2;
                 },
 
@@ -266,17 +545,23 @@ impl <'program> Chip8<'program> {
                 // Store registers to [i]
                 (0xf, _, 5, 5) => {
                     for i in 0..=n2 {
-                        self.memory[self.i] = self.reg[i];
-                        self.i += 1;
+                        self.write_byte(self.i + i, self.reg[i])?;
+                    }
+                    if let Some(coverage) = &mut self.coverage {
+                        coverage.mark_written(self.i, n2 + 1);
                     }
+                    self.i += quirks.memory_increment(n2);
                     self.pc += 2;
                 }
 
                 (0xf, _, 0x6, 0x5) => {
                     for i in 0..=n2 {
-                        self.reg[i] = self.memory[self.i];
-                        self.i += 1;
+                        self.reg[i] = self.read_byte(self.i + i)?;
+                    }
+                    if let Some(coverage) = &mut self.coverage {
+                        coverage.mark_read(self.i, n2 + 1);
                     }
+                    self.i += quirks.memory_increment(n2);
                     self.pc += 2;
                 }
 
@@ -312,39 +597,99 @@ impl <'program> Chip8<'program> {
                     self.i = digit * 5;
                     self.pc += 2;
                 }
-                _ => todo!("Unimplemented CPU instruction {:#04x}", instr)
+                _ => return Err(Fault::Unimplemented { pc, instr })
             }
 
+            if self.waiting_for_vblank {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes one instruction like [`Chip8::step`] for debuggers. Returns false if the machine is
+    /// waiting and nothing ran.
+    pub fn step_one(&mut self) -> Result<bool, Fault> {
+        let cycles = self.cycles;
+        self.step(1)?;
+        Ok(self.cycles != cycles)
+    }
+
+    // All memory accesses made by instructions go through here so the memory policy applies
+    fn resolve_address(&self, addr: usize) -> Result<usize, Fault> {
+        if addr < Chip8::MEM_SIZE {
+            return Ok(addr);
+        }
+
+        match self.config.memory_policy {
+            MemoryPolicy::Wrap => Ok(addr % Chip8::MEM_SIZE),
+            MemoryPolicy::Log => {
+                log::warn!("Memory access at {:#06x} wrapped around, pc {:#05x}", addr, self.pc);
+                Ok(addr % Chip8::MEM_SIZE)
+            }
+            MemoryPolicy::Fault => Err(Fault::MemoryOutOfBounds { pc: self.pc, addr })
         }
     }
 
-    fn xor_pixel(&mut self, x: usize, y: usize, px: u8) -> u8 {
-        let pixel = &mut self.display[y * Chip8::DISPLAY_WIDTH + x];
-        let old_val = *pixel;
-        *pixel = px ^ *pixel;
-        old_val
+    #[inline]
+    fn read_byte(&mut self, addr: usize) -> Result<u8, Fault> {
+        let addr = self.resolve_address(addr)?;
+        self.memory.read(addr).map_err(|error| Fault::Bus { pc: self.pc, addr, error })
     }
 
+    #[inline]
+    fn write_byte(&mut self, addr: usize, value: u8) -> Result<(), Fault> {
+        let addr = self.resolve_address(addr)?;
+        self.memory.write(addr, value).map_err(|error| Fault::Bus { pc: self.pc, addr, error })
+    }
+
+}
+
+#[cfg(feature = "std")]
+fn default_rng() -> SmallRng {
+    SmallRng::from_entropy()
+}
+
+#[cfg(not(feature = "std"))]
+fn default_rng() -> SmallRng {
+    SmallRng::seed_from_u64(0xc8)
 }
 
-pub fn dump_display(c8: &Chip8) {
+/// Writes the display as text, one line per row with `x` for lit pixels
+pub fn write_display<B: Bus>(c8: &Chip8<B>, out: &mut impl fmt::Write) -> fmt::Result {
     for y in 0..Chip8::DISPLAY_HEIGHT {
-        let mut line = String::with_capacity(Chip8::DISPLAY_WIDTH);
         for x in 0..Chip8::DISPLAY_WIDTH {
-            line.push(if c8.display[y * Chip8::DISPLAY_WIDTH + x] == 0 { '.' } else { 'x' });
+            out.write_char(if c8.display.pixel(x, y) == 0 { '.' } else { 'x' })?;
         }
-        println!("{line}");
+        out.write_char('\n')?;
     }
+    Ok(())
 }
 
-pub fn dump_machine_state(c8: &Chip8) {
-    println!("pc: ${:#06X}, instr: {:#06X}, i: {:#06X}, regs {:02x?}",
+/// Writes pc, the instruction at pc, i and the registers as one line
+pub fn write_machine_state<B: Bus>(c8: &Chip8<B>, out: &mut impl fmt::Write) -> fmt::Result {
+    writeln!(out, "pc: ${:#06X}, instr: {:#06X}, i: {:#06X}, regs {:02x?}",
         c8.pc,
-        ((c8.memory[c8.pc] as u16) << 8) | c8.memory[c8.pc + 1] as u16,
+        ((c8.memory.peek(c8.pc % Chip8::MEM_SIZE) as u16) << 8) | c8.memory.peek((c8.pc + 1) % Chip8::MEM_SIZE) as u16,
         c8.i,
         c8.reg
-    );
+    )
+}
+
+/// Prints the display to stdout, see [`write_display`]
+#[cfg(any(feature = "std", test))]
+pub fn dump_display<B: Bus>(c8: &Chip8<B>) {
+    let mut text = String::new();
+    write_display(c8, &mut text).unwrap();
+    print!("{text}");
+}
 
+/// Prints the machine state to stdout, see [`write_machine_state`]
+#[cfg(any(feature = "std", test))]
+pub fn dump_machine_state<B: Bus>(c8: &Chip8<B>) {
+    let mut text = String::new();
+    write_machine_state(c8, &mut text).unwrap();
+    print!("{text}");
 }
 
 
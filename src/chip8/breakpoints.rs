@@ -0,0 +1,53 @@
+// Breakpoints for the debugger frontends. The machine stops before the
+// instruction at a breakpoint. The instruction at the breakpoint the machine
+// continued from runs once before that breakpoint can hit again.
+
+use alloc::collections::BTreeSet;
+
+use super::{Bus, Chip8, Fault};
+
+/// Breakpoint addresses and whether the machine is continuing from one of them
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    addrs: BTreeSet<usize>,
+    resuming: bool,
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        Breakpoints::default()
+    }
+
+    pub fn insert(&mut self, addr: usize) {
+        self.addrs.insert(addr);
+    }
+
+    pub fn remove(&mut self, addr: usize) {
+        self.addrs.remove(&addr);
+    }
+
+    /// Replaces all breakpoints
+    pub fn set(&mut self, addrs: impl IntoIterator<Item = usize>) {
+        self.addrs = addrs.into_iter().collect();
+    }
+
+    /// Call when the machine continues, the breakpoint it stopped at doesn't hit right away
+    pub fn resume(&mut self) {
+        self.resuming = true;
+    }
+
+    /// True if the machine has to stop before executing the instruction at pc
+    pub fn hit<B: Bus>(&self, chip8: &Chip8<'_, B>) -> bool {
+        !self.resuming && self.addrs.contains(&chip8.state().pc)
+    }
+
+    /// Executes one instruction with [`Chip8::step_one`]. Returns false if the machine is
+    /// waiting and nothing ran.
+    pub fn step<B: Bus>(&mut self, chip8: &mut Chip8<'_, B>) -> Result<bool, Fault> {
+        let executed = chip8.step_one()?;
+        if executed {
+            self.resuming = false;
+        }
+        Ok(executed)
+    }
+}
@@ -0,0 +1,156 @@
+use core::fmt;
+use core::ops::{Index, IndexMut, Range};
+use core::slice::SliceIndex;
+
+/// Everything the interpreter sees through the 4K address space. Addresses handed
+/// to a bus are always below 4096, the memory policy has already dealt with
+/// anything past the end.
+pub trait Bus {
+    /// Read made by an instruction, devices may react to it
+    fn read(&mut self, addr: usize) -> Result<u8, BusError> {
+        Ok(self.peek(addr))
+    }
+
+    fn write(&mut self, addr: usize, value: u8) -> Result<(), BusError>;
+
+    /// Side effect free read for debuggers and state dumps
+    fn peek(&self, addr: usize) -> u8;
+
+    /// Unconditional store used to load the font and program, ignores write protection
+    fn poke(&mut self, addr: usize, value: u8);
+
+    fn load(&mut self, addr: usize, data: &[u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            self.poke(addr + offset, *byte);
+        }
+    }
+}
+
+/// Why a bus refused an access
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusError {
+    /// Write to an address mapped read only
+    ReadOnly,
+    /// Nothing answers at the address
+    Unmapped,
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusError::ReadOnly => write!(f, "read only"),
+            BusError::Unmapped => write!(f, "unmapped"),
+        }
+    }
+}
+
+/// Plain 4K of RAM, what the interpreter had before buses existed
+#[derive(Clone)]
+pub struct FlatRam {
+    bytes: [u8; FlatRam::SIZE],
+}
+
+impl FlatRam {
+    const SIZE: usize = 4096;
+
+    pub fn new() -> Self {
+        FlatRam { bytes: [0; FlatRam::SIZE] }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Default for FlatRam {
+    fn default() -> Self {
+        FlatRam::new()
+    }
+}
+
+impl Bus for FlatRam {
+    #[inline]
+    fn read(&mut self, addr: usize) -> Result<u8, BusError> {
+        Ok(self.bytes[addr])
+    }
+
+    #[inline]
+    fn write(&mut self, addr: usize, value: u8) -> Result<(), BusError> {
+        self.bytes[addr] = value;
+        Ok(())
+    }
+
+    #[inline]
+    fn peek(&self, addr: usize) -> u8 {
+        self.bytes[addr]
+    }
+
+    #[inline]
+    fn poke(&mut self, addr: usize, value: u8) {
+        self.bytes[addr] = value;
+    }
+
+    fn load(&mut self, addr: usize, data: &[u8]) {
+        self.bytes[addr..addr + data.len()].copy_from_slice(data);
+    }
+}
+
+impl<I: SliceIndex<[u8]>> Index<I> for FlatRam {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.bytes[index]
+    }
+}
+
+impl<I: SliceIndex<[u8]>> IndexMut<I> for FlatRam {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        &mut self.bytes[index]
+    }
+}
+
+/// Wraps another bus and rejects instruction writes to a range, e.g. 0x000..0x200
+/// to keep programs from overwriting the interpreter area and font
+pub struct WriteProtect<B: Bus> {
+    inner: B,
+    range: Range<usize>,
+}
+
+impl<B: Bus> WriteProtect<B> {
+    pub fn new(inner: B, range: Range<usize>) -> Self {
+        WriteProtect { inner, range }
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Bus> Bus for WriteProtect<B> {
+    fn read(&mut self, addr: usize) -> Result<u8, BusError> {
+        self.inner.read(addr)
+    }
+
+    fn write(&mut self, addr: usize, value: u8) -> Result<(), BusError> {
+        if self.range.contains(&addr) {
+            return Err(BusError::ReadOnly);
+        }
+        self.inner.write(addr, value)
+    }
+
+    fn peek(&self, addr: usize) -> u8 {
+        self.inner.peek(addr)
+    }
+
+    fn poke(&mut self, addr: usize, value: u8) {
+        self.inner.poke(addr, value)
+    }
+
+    fn load(&mut self, addr: usize, data: &[u8]) {
+        self.inner.load(addr, data)
+    }
+}
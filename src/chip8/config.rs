@@ -0,0 +1,62 @@
+use super::MemoryPolicy;
+
+/// Interpreter options. Start from `Config::default()` and change the fields you need,
+/// new options are added as fields over time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Config {
+    /// What happens when an instruction reaches past the end of memory
+    pub memory_policy: MemoryPolicy,
+    /// Behavior of the instructions that differ between CHIP-8 implementations
+    pub quirks: Quirks,
+}
+
+/// Instructions whose behavior differs between CHIP-8 implementations. The names follow the
+/// quirks of the community CHIP-8 database, `true` means the quirk is present.
+///
+/// The default is the behavior this interpreter always had, which doesn't match any single
+/// platform. Use [`crate::romdb`] or set the quirks of the platform a program was written for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6 and 8xyE shift Vx in place instead of shifting Vy into Vx
+    pub shift: bool,
+    /// Fx55 and Fx65 increment I by x instead of x + 1
+    pub memory_increment_by_x: bool,
+    /// Fx55 and Fx65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the display instead of being clipped
+    pub wrap: bool,
+    /// Bnnn jumps to xnn + Vx instead of nnn + V0
+    pub jump: bool,
+    /// Dxyn waits for the next 60Hz tick before execution continues
+    pub vblank: bool,
+    /// 8xy1, 8xy2 and 8xy3 reset VF to 0
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+impl Quirks {
+    // How far Fx55 and Fx65 move I after accessing V0 to Vx
+    pub(crate) fn memory_increment(&self, x: usize) -> usize {
+        if self.memory_leave_i_unchanged {
+            0
+        } else if self.memory_increment_by_x {
+            x
+        } else {
+            x + 1
+        }
+    }
+}
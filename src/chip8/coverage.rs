@@ -0,0 +1,242 @@
+// Coverage of memory by what Chip8::step did with each byte: fetched as an
+// instruction, read as sprite data by Dxyn, or read and written by Fx65 and
+// Fx55. Coverage of several runs merges by address, and saves to a small
+// blob so runs of a test suite can be combined later. The blob names the ROM
+// it was collected on by its length and CRC-32, so coverage of another ROM
+// isn't merged in by accident.
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{disassemble, Chip8};
+
+const MAGIC: &[u8; 4] = b"C8CV";
+const VERSION: u8 = 2;
+
+// First byte of an executed instruction
+const INSTRUCTION: u8 = 1 << 0;
+// Either byte of an executed instruction
+const CODE: u8 = 1 << 1;
+const SPRITE: u8 = 1 << 2;
+const READ: u8 = 1 << 3;
+const WRITTEN: u8 = 1 << 4;
+
+/// Why [`Coverage::from_bytes`] rejected a blob
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoverageError {
+    /// Not coverage, or coverage of another version
+    BadFormat,
+    /// Coverage of a different ROM
+    OtherProgram,
+}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoverageError::BadFormat => write!(f, "Not a coverage file"),
+            CoverageError::OtherProgram => write!(f, "Coverage of a different ROM"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CoverageError {}
+
+/// Install with [`Chip8::set_coverage`], marks every byte the machine uses until it is taken out
+/// again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage { flags: vec![0; Chip8::MEM_SIZE] }
+    }
+
+    pub(crate) fn mark_instruction(&mut self, pc: usize) {
+        self.flags[pc % Chip8::MEM_SIZE] |= INSTRUCTION | CODE;
+        self.flags[(pc + 1) % Chip8::MEM_SIZE] |= CODE;
+    }
+
+    pub(crate) fn mark_sprite(&mut self, addr: usize, len: usize) {
+        self.mark(addr, len, SPRITE);
+    }
+
+    pub(crate) fn mark_read(&mut self, addr: usize, len: usize) {
+        self.mark(addr, len, READ);
+    }
+
+    pub(crate) fn mark_written(&mut self, addr: usize, len: usize) {
+        self.mark(addr, len, WRITTEN);
+    }
+
+    fn mark(&mut self, addr: usize, len: usize, flag: u8) {
+        for offset in 0..len {
+            self.flags[(addr + offset) % Chip8::MEM_SIZE] |= flag;
+        }
+    }
+
+    fn flag(&self, addr: usize, flag: u8) -> bool {
+        self.flags.get(addr).is_some_and(|flags| flags & flag != 0)
+    }
+
+    /// An instruction starting at `addr` was executed
+    pub fn is_instruction(&self, addr: usize) -> bool {
+        self.flag(addr, INSTRUCTION)
+    }
+
+    /// `addr` is either byte of an executed instruction
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.flag(addr, CODE)
+    }
+
+    /// `Dxyn` drew the byte at `addr`
+    pub fn is_sprite(&self, addr: usize) -> bool {
+        self.flag(addr, SPRITE)
+    }
+
+    /// `Fx65` loaded the byte at `addr` into a register
+    pub fn is_read(&self, addr: usize) -> bool {
+        self.flag(addr, READ)
+    }
+
+    /// `Fx55` stored a register at `addr`
+    pub fn is_written(&self, addr: usize) -> bool {
+        self.flag(addr, WRITTEN)
+    }
+
+    pub fn is_touched(&self, addr: usize) -> bool {
+        self.flags.get(addr).is_some_and(|flags| *flags != 0)
+    }
+
+    /// Adds what `other` covered, e.g. another run of the same ROM
+    pub fn merge(&mut self, other: &Coverage) {
+        for (flags, other) in self.flags.iter_mut().zip(&other.flags) {
+            *flags |= other;
+        }
+    }
+
+    /// Saves the coverage along with the length and CRC-32 of `program`, the ROM it was collected on
+    pub fn to_bytes(&self, program: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MAGIC.len() + 9 + self.flags.len());
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&(program.len() as u32).to_le_bytes());
+        out.extend_from_slice(&crc32(program).to_le_bytes());
+        out.extend_from_slice(&self.flags);
+        out
+    }
+
+    /// Reads what [`Coverage::to_bytes`] wrote for the same `program`
+    pub fn from_bytes(data: &[u8], program: &[u8]) -> Result<Self, CoverageError> {
+        let data = data.strip_prefix(MAGIC).and_then(|data| data.strip_prefix(&[VERSION])).ok_or(CoverageError::BadFormat)?;
+        if data.len() != 8 + Chip8::MEM_SIZE {
+            return Err(CoverageError::BadFormat);
+        }
+        let (identity, flags) = data.split_at(8);
+        let len = u32::from_le_bytes(identity[..4].try_into().unwrap());
+        let crc = u32::from_le_bytes(identity[4..].try_into().unwrap());
+        if len as usize != program.len() || crc != crc32(program) {
+            return Err(CoverageError::OtherProgram);
+        }
+        Ok(Coverage { flags: flags.to_vec() })
+    }
+
+    /// Address ranges of `program`, loaded at the reset vector, that never ran as code. Each range
+    /// is `start..end`.
+    pub fn unexecuted_ranges(&self, program: &[u8]) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for addr in Chip8::RESET_VECTOR..Chip8::RESET_VECTOR + program.len() {
+            if self.is_code(addr) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some((_, end)) if *end == addr => *end = addr + 1,
+                _ => ranges.push((addr, addr + 1)),
+            }
+        }
+        ranges
+    }
+
+    /// Annotated disassembly of `program`, then totals and the ranges that never ran. Each line has
+    /// the address, the bytes, what they were used for and the instruction or the sprite row:
+    /// X executed, S drawn as a sprite, R read by Fx65, W written by Fx55.
+    pub fn write_report(&self, program: &[u8], out: &mut impl fmt::Write) -> fmt::Result {
+        let start = Chip8::RESET_VECTOR;
+        let end = start + program.len();
+        let byte = |addr: usize| program[addr - start];
+        let uses = |addr: usize| {
+            let mut uses = [b'-'; 4];
+            for (n, flag) in [CODE, SPRITE, READ, WRITTEN].into_iter().enumerate() {
+                if self.flag(addr, flag) {
+                    uses[n] = b"XSRW"[n];
+                }
+            }
+            uses
+        };
+
+        let mut addr = start;
+        while addr < end {
+            let uses = uses(addr);
+            let uses = core::str::from_utf8(&uses).unwrap_or_default();
+            // Pairs of untouched bytes are disassembled too, they may be code that never ran
+            let as_instruction = self.is_instruction(addr) || !self.is_touched(addr) && !self.is_touched(addr + 1);
+            if as_instruction && addr + 1 < end {
+                let instr = (byte(addr) as u16) << 8 | byte(addr + 1) as u16;
+                writeln!(out, "{addr:#05x}  {instr:04X}  {uses}  {}", disassemble(instr))?;
+                addr += 2;
+            } else {
+                let value = byte(addr);
+                write!(out, "{addr:#05x}  {value:02X}    {uses}  ")?;
+                for bit in 0..8 {
+                    out.write_char(if value & 0x80 >> bit != 0 { '#' } else { '.' })?;
+                }
+                writeln!(out)?;
+                addr += 1;
+            }
+        }
+
+        let count = |flag: u8| (start..end).filter(|addr| self.flag(*addr, flag)).count();
+        let untouched = (start..end).filter(|addr| !self.is_touched(*addr)).count();
+        let percent = |n: usize| if program.is_empty() { 0.0 } else { n as f64 * 100.0 / program.len() as f64 };
+        writeln!(out, "\n{} bytes", program.len())?;
+        writeln!(out, "{:>6} executed ({:.1}%)", count(CODE), percent(count(CODE)))?;
+        writeln!(out, "{:>6} drawn as sprites ({:.1}%)", count(SPRITE), percent(count(SPRITE)))?;
+        writeln!(out, "{:>6} read or written as data ({:.1}%)", count(READ | WRITTEN), percent(count(READ | WRITTEN)))?;
+        writeln!(out, "{untouched:>6} never touched ({:.1}%)", percent(untouched))?;
+
+        writeln!(out, "\nNever executed")?;
+        for (range_start, range_end) in self.unexecuted_ranges(program) {
+            let used = (range_start..range_end).filter(|addr| self.is_touched(*addr)).count();
+            let note = match used {
+                0 => "never touched".to_string(),
+                n if n == range_end - range_start => "data".to_string(),
+                n => format!("{n} bytes used as data"),
+            };
+            writeln!(out, "{range_start:#05x}-{:#05x}  {:>5} bytes  {note}", range_end - 1, range_end - range_start)?;
+        }
+        Ok(())
+    }
+}
+
+// CRC-32 as used by zip and PNG
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { crc >> 1 ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage::new()
+    }
+}
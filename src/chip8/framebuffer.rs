@@ -0,0 +1,173 @@
+// Bit-packed display planes. Every row of a plane is a run of u64 words with
+// the leftmost pixel in the most significant of the `width` used bits, so a
+// sprite row is drawn with a single shift and XOR, and clearing is a memset of
+// a few hundred bytes. At 64x32 a row is exactly one word; rows wider than 64
+// pixels take two, most significant first, which covers the 128x64 high
+// resolution mode. XO-CHIP draws to up to two planes, kept side by side.
+
+/// Display planes of a machine, see the module comment for the layout
+#[derive(Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    planes: [[u64; Framebuffer::MAX_HEIGHT * 2]; Framebuffer::PLANES],
+}
+
+impl Framebuffer {
+    pub const MAX_WIDTH: usize = 128;
+    pub const MAX_HEIGHT: usize = 64;
+    pub const PLANES: usize = 2;
+
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!((8..=Framebuffer::MAX_WIDTH).contains(&width) && height <= Framebuffer::MAX_HEIGHT,
+            "Unsupported display size {width}x{height}");
+        Framebuffer { width, height, planes: [[0; Framebuffer::MAX_HEIGHT * 2]; Framebuffer::PLANES] }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn clear(&mut self) {
+        self.planes = [[0; Framebuffer::MAX_HEIGHT * 2]; Framebuffer::PLANES];
+    }
+
+    /// Raw words of a plane, one per row up to 64 pixels wide and two per row beyond, most
+    /// significant first. Only the low `width` bits of each row are used.
+    pub fn rows(&self, plane: usize) -> &[u64] {
+        &self.planes[plane][..self.height * self.words()]
+    }
+
+    /// Row `y` of a plane as one integer, only the low `width` bits are used
+    pub fn row(&self, plane: usize, y: usize) -> u128 {
+        match self.words() {
+            1 => self.planes[plane][y] as u128,
+            _ => (self.planes[plane][2 * y] as u128) << 64 | self.planes[plane][2 * y + 1] as u128,
+        }
+    }
+
+    pub(crate) fn set_row(&mut self, plane: usize, y: usize, row: u128) {
+        match self.words() {
+            1 => self.planes[plane][y] = row as u64,
+            _ => {
+                self.planes[plane][2 * y] = (row >> 64) as u64;
+                self.planes[plane][2 * y + 1] = row as u64;
+            }
+        }
+    }
+
+    /// Bitmask of the planes that have the pixel lit
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let bit = self.width - 1 - x;
+        (0..Framebuffer::PLANES).fold(0, |mask, p| mask | ((self.row(p, y) >> bit) as u8 & 1) << p)
+    }
+
+    /// XORs an 8 pixel wide sprite onto a plane with its top left corner at (x, y),
+    /// one byte per row, wrapping around the edges. Returns true if a lit pixel was turned off.
+    pub fn xor_sprite(&mut self, plane: usize, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let shift = (x % self.width) as u32;
+        let mut y = y % self.height;
+        let mut collision = false;
+        for bits in sprite {
+            if self.width == 64 {
+                let line = ((*bits as u64) << 56).rotate_right(shift);
+                let row = &mut self.planes[plane][y];
+                collision |= *row & line != 0;
+                *row ^= line;
+            } else {
+                let line = self.sprite_line(*bits, shift);
+                let row = self.row(plane, y);
+                collision |= row & line != 0;
+                self.set_row(plane, y, row ^ line);
+            }
+
+            y += 1;
+            if y == self.height {
+                y = 0;
+            }
+        }
+        collision
+    }
+
+    /// Like [`Framebuffer::xor_sprite`], but only the top left corner wraps. Pixels that would
+    /// wrap past the right or bottom edge are clipped.
+    pub fn xor_sprite_clipped(&mut self, plane: usize, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let shift = (x % self.width) as u32;
+        let y = y % self.height;
+        let mut collision = false;
+        let rows = sprite.len().min(self.height - y);
+        for (y, bits) in (y..y + rows).zip(sprite) {
+            let line = ((*bits as u128) << (self.width - 8)) >> shift;
+            let row = self.row(plane, y);
+            collision |= row & line != 0;
+            self.set_row(plane, y, row ^ line);
+        }
+        collision
+    }
+
+    pub fn xor_sprite_row(&mut self, plane: usize, x: usize, y: usize, bits: u8) -> bool {
+        self.xor_sprite(plane, x, y, &[bits])
+    }
+
+    /// Expands the planes to one byte per pixel for renderers, 255 where any plane is lit
+    pub fn unpack(&self, out: &mut [u8]) {
+        assert_eq!(out.len(), self.width * self.height);
+        for (y, line) in out.chunks_exact_mut(self.width).enumerate() {
+            let row = (0..Framebuffer::PLANES).fold(0, |row, p| row | self.row(p, y));
+            let mut chunks = line.chunks_exact_mut(8);
+            for (i, chunk) in (&mut chunks).enumerate() {
+                let byte = (row >> (self.width - 8 - i * 8)) as u8;
+                chunk.copy_from_slice(&EXPANDED_BYTES[byte as usize]);
+            }
+            // The last few pixels of a width that isn't a multiple of 8 are the lowest bits
+            let tail = chunks.into_remainder();
+            let len = tail.len();
+            for (i, pixel) in tail.iter_mut().enumerate() {
+                *pixel = if row >> (len - 1 - i) & 1 != 0 { 255 } else { 0 };
+            }
+        }
+    }
+
+    // Words per row
+    fn words(&self) -> usize {
+        if self.width <= 64 { 1 } else { 2 }
+    }
+
+    // Sprite row positioned `shift` pixels from the left edge, wrapped within the row width
+    fn sprite_line(&self, bits: u8, shift: u32) -> u128 {
+        match self.width {
+            128 => ((bits as u128) << 120).rotate_right(shift),
+            width => {
+                let line = (bits as u128) << (width - 8);
+                if shift == 0 { line } else { (line >> shift | line << (width as u32 - shift)) & self.row_mask() }
+            }
+        }
+    }
+
+    fn row_mask(&self) -> u128 {
+        if self.width == 128 { u128::MAX } else { (1 << self.width) - 1 }
+    }
+}
+
+// Each byte of packed pixels expanded to eight bytes of 0 or 255, most significant bit first
+const EXPANDED_BYTES: [[u8; 8]; 256] = expand_bytes();
+
+const fn expand_bytes() -> [[u8; 8]; 256] {
+    let mut table = [[0; 8]; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut bit = 0;
+        while bit < 8 {
+            if byte >> (7 - bit) & 1 != 0 {
+                table[byte][bit] = 255;
+            }
+            bit += 1;
+        }
+        byte += 1;
+    }
+    table
+}
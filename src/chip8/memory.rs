@@ -0,0 +1,42 @@
+use core::fmt;
+
+use super::BusError;
+
+/// What happens when an instruction reaches past the end of the 4K address space
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MemoryPolicy {
+    /// Addresses wrap around at 4K like they did on the COSMAC VIP
+    #[default]
+    Wrap,
+    /// The access stops execution with a Fault
+    Fault,
+    /// The access wraps and a warning is logged
+    Log,
+}
+
+/// Error that stopped execution, returned by `Chip8::step`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Access outside the address space while running with MemoryPolicy::Fault
+    MemoryOutOfBounds { pc: usize, addr: usize },
+    /// The bus refused an access
+    Bus { pc: usize, addr: usize, error: BusError },
+    /// The instruction at pc isn't part of the instruction set
+    Unimplemented { pc: usize, instr: u16 },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::MemoryOutOfBounds { pc, addr } =>
+                write!(f, "Memory access at {addr:#06x} out of bounds, pc {pc:#05x}"),
+            Fault::Bus { pc, addr, error } =>
+                write!(f, "Memory access at {addr:#05x} failed: {error}, pc {pc:#05x}"),
+            Fault::Unimplemented { pc, instr } =>
+                write!(f, "Unimplemented instruction {instr:#06x} at {pc:#05x}")
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Fault {}
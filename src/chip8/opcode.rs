@@ -0,0 +1,94 @@
+// Instruction families by their pattern, shared by the profiler and tools that
+// look at programs without running them.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+
+/// The family `instr` belongs to as its pattern, e.g. `"8xy4"` for `0x8124`, or None for the
+/// instructions [`Chip8::step`](super::Chip8::step) doesn't implement and faults on.
+pub fn opcode_class(instr: u16) -> Option<&'static str> {
+    let class = match (instr >> 12, instr >> 8 & 0xf, instr >> 4 & 0xf, instr & 0xf) {
+        (0, 0, 0xe, 0x0) => "00E0",
+        (0, 0, 0xe, 0xe) => "00EE",
+        (0x1, ..) => "1nnn",
+        (0x2, ..) => "2nnn",
+        (0x3, ..) => "3xnn",
+        (0x4, ..) => "4xnn",
+        (0x5, ..) => "5xy0",
+        (0x6, ..) => "6xnn",
+        (0x7, ..) => "7xnn",
+        (0x8, _, _, 0x0) => "8xy0",
+        (0x8, _, _, 0x1) => "8xy1",
+        (0x8, _, _, 0x2) => "8xy2",
+        (0x8, _, _, 0x3) => "8xy3",
+        (0x8, _, _, 0x4) => "8xy4",
+        (0x8, _, _, 0x5) => "8xy5",
+        (0x8, _, _, 0x6) => "8xy6",
+        (0x8, _, _, 0x7) => "8xy7",
+        (0x8, _, _, 0xe) => "8xyE",
+        (0x9, ..) => "9xy0",
+        (0xa, ..) => "Annn",
+        (0xb, ..) => "Bnnn",
+        (0xc, ..) => "Cxnn",
+        (0xd, ..) => "Dxyn",
+        (0xe, _, 0x9, 0xe) => "Ex9E",
+        (0xe, _, 0xa, 0x1) => "ExA1",
+        (0xf, _, 0x0, 0x7) => "Fx07",
+        (0xf, _, 0x0, 0xa) => "Fx0A",
+        (0xf, _, 0x1, 0x5) => "Fx15",
+        (0xf, _, 0x1, 0x8) => "Fx18",
+        (0xf, _, 0x1, 0xe) => "Fx1E",
+        (0xf, _, 0x2, 0x9) => "Fx29",
+        (0xf, _, 0x5, 0x5) => "Fx55",
+        (0xf, _, 0x6, 0x5) => "Fx65",
+        _ => return None,
+    };
+    Some(class)
+}
+
+/// `instr` in the mnemonics of Cowgod's reference, e.g. `"DRW V1, V2, 5"`. Instructions outside the
+/// CHIP-8 set come out as `"DW 0x1234"`. Disassembles the whole set, including instructions
+/// [`opcode_class`] says [`Chip8::step`](super::Chip8::step) doesn't implement.
+pub fn disassemble(instr: u16) -> String {
+    let (x, y, n) = (instr >> 8 & 0xf, instr >> 4 & 0xf, instr & 0xf);
+    let nnn = instr & 0xfff;
+    let nn = instr & 0xff;
+    match (instr >> 12, x, y, n) {
+        (0, 0, 0xe, 0x0) => "CLS".to_string(),
+        (0, 0, 0xe, 0xe) => "RET".to_string(),
+        (0x0, ..) => format!("SYS {nnn:#05x}"),
+        (0x1, ..) => format!("JP {nnn:#05x}"),
+        (0x2, ..) => format!("CALL {nnn:#05x}"),
+        (0x3, ..) => format!("SE V{x:X}, {nn:#04x}"),
+        (0x4, ..) => format!("SNE V{x:X}, {nn:#04x}"),
+        (0x5, _, _, 0) => format!("SE V{x:X}, V{y:X}"),
+        (0x6, ..) => format!("LD V{x:X}, {nn:#04x}"),
+        (0x7, ..) => format!("ADD V{x:X}, {nn:#04x}"),
+        (0x8, _, _, 0x0) => format!("LD V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x1) => format!("OR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x2) => format!("AND V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x3) => format!("XOR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x4) => format!("ADD V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x5) => format!("SUB V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x6) => format!("SHR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x7) => format!("SUBN V{x:X}, V{y:X}"),
+        (0x8, _, _, 0xe) => format!("SHL V{x:X}, V{y:X}"),
+        (0x9, _, _, 0) => format!("SNE V{x:X}, V{y:X}"),
+        (0xa, ..) => format!("LD I, {nnn:#05x}"),
+        (0xb, ..) => format!("JP V0, {nnn:#05x}"),
+        (0xc, ..) => format!("RND V{x:X}, {nn:#04x}"),
+        (0xd, ..) => format!("DRW V{x:X}, V{y:X}, {n}"),
+        (0xe, _, 0x9, 0xe) => format!("SKP V{x:X}"),
+        (0xe, _, 0xa, 0x1) => format!("SKNP V{x:X}"),
+        (0xf, _, 0x0, 0x7) => format!("LD V{x:X}, DT"),
+        (0xf, _, 0x0, 0xa) => format!("LD V{x:X}, K"),
+        (0xf, _, 0x1, 0x5) => format!("LD DT, V{x:X}"),
+        (0xf, _, 0x1, 0x8) => format!("LD ST, V{x:X}"),
+        (0xf, _, 0x1, 0xe) => format!("ADD I, V{x:X}"),
+        (0xf, _, 0x2, 0x9) => format!("LD F, V{x:X}"),
+        (0xf, _, 0x3, 0x3) => format!("LD B, V{x:X}"),
+        (0xf, _, 0x5, 0x5) => format!("LD [I], V{x:X}"),
+        (0xf, _, 0x6, 0x5) => format!("LD V{x:X}, [I]"),
+        _ => format!("DW {instr:#06x}"),
+    }
+}
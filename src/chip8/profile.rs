@@ -0,0 +1,189 @@
+// Execution profiler fed by Chip8::step: executions per address and per
+// instruction family, and cycles per subroutine from matched 2nnn/00EE pairs.
+// A cycle is one executed instruction. Reports are plain text, JSON or the
+// folded stacks flamegraph tools read.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{opcode_class, Chip8};
+
+// Hotspots listed by the text report
+const REPORT_ADDRESSES: usize = 20;
+
+/// Cycles spent in one subroutine, keyed by its entry address
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubroutineStats {
+    /// Completed calls, a call still running when the report is made isn't counted
+    pub calls: u64,
+    /// Cycles from the call to the return including the subroutines it called
+    pub inclusive: u64,
+    /// Cycles spent in the subroutine itself, including calls that haven't returned yet
+    pub exclusive: u64,
+}
+
+/// Install with [`Chip8::set_profiler`](super::Chip8::set_profiler), every executed instruction is
+/// counted until it is taken out again.
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    total: u64,
+    addresses: Vec<u64>,
+    // Last instruction executed at each address, for the report
+    instructions: Vec<u16>,
+    classes: BTreeMap<&'static str, u64>,
+    subroutines: BTreeMap<usize, SubroutineStats>,
+    // Entry addresses of the running calls from the outermost in, and the total when each started
+    path: Vec<usize>,
+    starts: Vec<u64>,
+    // Exclusive cycles by call path, entry addresses from the outermost call in
+    folded: BTreeMap<Vec<usize>, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            total: 0,
+            addresses: vec![0; Chip8::MEM_SIZE],
+            instructions: vec![0; Chip8::MEM_SIZE],
+            classes: BTreeMap::new(),
+            subroutines: BTreeMap::new(),
+            path: Vec::new(),
+            starts: Vec::new(),
+            folded: BTreeMap::new(),
+        }
+    }
+
+    /// Counts `instr` at `pc`, called before it executes
+    pub(crate) fn record(&mut self, pc: usize, instr: u16) {
+        let pc = pc % Chip8::MEM_SIZE;
+        self.total += 1;
+        self.addresses[pc] += 1;
+        self.instructions[pc] = instr;
+        *self.classes.entry(opcode_class(instr).unwrap_or("????")).or_insert(0) += 1;
+
+        // The call counts for the caller and the return for the subroutine
+        match self.folded.get_mut(self.path.as_slice()) {
+            Some(cycles) => *cycles += 1,
+            None => {
+                self.folded.insert(self.path.clone(), 1);
+            }
+        }
+        if let Some(entry) = self.path.last() {
+            self.subroutines.entry(*entry).or_default().exclusive += 1;
+        }
+
+        if instr >> 12 == 0x2 {
+            self.path.push((instr & 0xfff) as usize);
+            self.starts.push(self.total);
+        } else if instr == 0x00ee {
+            // A return without a call seen, e.g. profiling started inside a subroutine, is ignored
+            if let (Some(entry), Some(start)) = (self.path.pop(), self.starts.pop()) {
+                let stats = self.subroutines.entry(entry).or_default();
+                stats.calls += 1;
+                stats.inclusive += self.total - start;
+            }
+        }
+    }
+
+    /// Instructions executed while profiling
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Executions of the instruction at `addr`
+    pub fn count(&self, addr: usize) -> u64 {
+        self.addresses.get(addr).copied().unwrap_or(0)
+    }
+
+    /// Executions per instruction family, see [`opcode_class`]. Unimplemented instructions count as `"????"`.
+    pub fn classes(&self) -> &BTreeMap<&'static str, u64> {
+        &self.classes
+    }
+
+    pub fn subroutines(&self) -> &BTreeMap<usize, SubroutineStats> {
+        &self.subroutines
+    }
+
+    /// Executed addresses with their counts, most executed first
+    pub fn hotspots(&self) -> Vec<(usize, u64)> {
+        let mut hotspots: Vec<(usize, u64)> = self.addresses.iter().copied().enumerate().filter(|(_, count)| *count > 0).collect();
+        hotspots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hotspots
+    }
+
+    /// Hotspots, instruction families and subroutines as text tables
+    pub fn write_report(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        let percent = |count: u64| if self.total == 0 { 0.0 } else { count as f64 * 100.0 / self.total as f64 };
+        writeln!(out, "{} instructions executed", self.total)?;
+
+        writeln!(out, "\nHotspots")?;
+        writeln!(out, "{:>7} {:>6} {:>12} {:>7}", "address", "instr", "count", "%")?;
+        for (addr, count) in self.hotspots().into_iter().take(REPORT_ADDRESSES) {
+            writeln!(out, "{:>7} {:>6} {:>12} {:>7.2}", format!("{addr:#05x}"), format!("{:04X}", self.instructions[addr]), count, percent(count))?;
+        }
+
+        writeln!(out, "\nInstructions")?;
+        writeln!(out, "{:>7} {:>12} {:>7}", "class", "count", "%")?;
+        let mut classes: Vec<(&str, u64)> = self.classes.iter().map(|(class, count)| (*class, *count)).collect();
+        classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (class, count) in classes {
+            writeln!(out, "{class:>7} {count:>12} {:>7.2}", percent(count))?;
+        }
+
+        if !self.subroutines.is_empty() {
+            writeln!(out, "\nSubroutines")?;
+            writeln!(out, "{:>7} {:>8} {:>12} {:>7} {:>12} {:>7}", "entry", "calls", "inclusive", "%", "exclusive", "%")?;
+            let mut subroutines: Vec<(&usize, &SubroutineStats)> = self.subroutines.iter().collect();
+            subroutines.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+            for (entry, stats) in subroutines {
+                writeln!(
+                    out, "{:>7} {:>8} {:>12} {:>7.2} {:>12} {:>7.2}",
+                    format!("{entry:#05x}"), stats.calls, stats.inclusive, percent(stats.inclusive), stats.exclusive, percent(stats.exclusive)
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The counts as a JSON object with `total`, `addresses`, `classes` and `subroutines`
+    pub fn write_json(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        write!(out, "{{\"total\":{},\"addresses\":{{", self.total)?;
+        let executed = self.addresses.iter().enumerate().filter(|(_, count)| **count > 0);
+        for (n, (addr, count)) in executed.enumerate() {
+            let separator = if n == 0 { "" } else { "," };
+            write!(out, "{separator}\"{addr:#05x}\":{{\"count\":{count},\"instr\":\"{:04X}\"}}", self.instructions[addr])?;
+        }
+        write!(out, "}},\"classes\":{{")?;
+        for (n, (class, count)) in self.classes.iter().enumerate() {
+            let separator = if n == 0 { "" } else { "," };
+            write!(out, "{separator}\"{class}\":{count}")?;
+        }
+        write!(out, "}},\"subroutines\":{{")?;
+        for (n, (entry, stats)) in self.subroutines.iter().enumerate() {
+            let separator = if n == 0 { "" } else { "," };
+            write!(out, "{separator}\"{entry:#05x}\":{{\"calls\":{},\"inclusive\":{},\"exclusive\":{}}}", stats.calls, stats.inclusive, stats.exclusive)?;
+        }
+        writeln!(out, "}}}}")
+    }
+
+    /// Folded stacks, one call path and its cycles per line, e.g. `main;sub_0x2a4;sub_0x310 120`
+    pub fn write_folded(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        for (path, cycles) in &self.folded {
+            write!(out, "main")?;
+            for entry in path {
+                write!(out, ";sub_{entry:#05x}")?;
+            }
+            writeln!(out, " {cycles}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
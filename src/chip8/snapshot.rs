@@ -0,0 +1,152 @@
+// Save states: the whole machine except the program and key states as a
+// versioned little endian blob, for hosts that store or send them around.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{Bus, Chip8, Framebuffer};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 2;
+
+/// Why [`Chip8::load_snapshot`] rejected a blob
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// Not a snapshot
+    BadMagic,
+    /// Written by a newer version of the interpreter
+    UnsupportedVersion(u8),
+    /// Shorter than the snapshot format requires
+    Truncated,
+    /// A field holds a value the machine can't be in, e.g. a display size
+    Invalid,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "Not a CHIP-8 snapshot"),
+            SnapshotError::UnsupportedVersion(v) => write!(f, "Unsupported snapshot version {v}"),
+            SnapshotError::Truncated => write!(f, "Snapshot is truncated"),
+            SnapshotError::Invalid => write!(f, "Snapshot holds an invalid machine state"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnapshotError {}
+
+impl <'program, B: Bus> Chip8<'program, B> {
+    /// Serializes registers, timers, memory and the display
+    pub fn save_snapshot(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64 + Chip8::MEM_SIZE);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&(self.pc as u32).to_le_bytes());
+        out.extend_from_slice(&(self.i as u32).to_le_bytes());
+        out.push(self.sp as u8);
+        out.extend_from_slice(&self.reg);
+        for addr in self.stack {
+            out.extend_from_slice(&(addr as u32).to_le_bytes());
+        }
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.push(self.waiting_for_key.unwrap_or(0xff));
+        out.push(self.waiting_for_vblank as u8);
+        out.extend_from_slice(&self.cycles.to_le_bytes());
+
+        out.extend((0..Chip8::MEM_SIZE).map(|addr| self.memory.peek(addr)));
+
+        out.push(self.display.width() as u8);
+        out.push(self.display.height() as u8);
+        // Rows are stored as 128 bits whatever the width
+        for plane in 0..Framebuffer::PLANES {
+            for y in 0..self.display.height() {
+                out.extend_from_slice(&self.display.row(plane, y).to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// Restores a blob from [`Chip8::save_snapshot`]. The machine is left untouched on error.
+    pub fn load_snapshot(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+        let mut r = Reader { bytes };
+        if r.take(4)? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let pc = r.u32()? as usize;
+        let i = r.u32()? as usize;
+        let sp = r.u8()? as usize;
+        let reg: [u8; 16] = r.take(16)?.try_into().unwrap();
+        let mut stack = [0; Chip8::STACK_SIZE];
+        for addr in stack.iter_mut() {
+            *addr = r.u32()? as usize;
+        }
+        let delay_timer = r.u8()?;
+        let sound_timer = r.u8()?;
+        let waiting_for_key = match r.u8()? {
+            0xff => None,
+            key => Some(key),
+        };
+        let waiting_for_vblank = match r.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(SnapshotError::Invalid),
+        };
+        let cycles = u64::from_le_bytes(r.take(8)?.try_into().unwrap());
+        let memory = r.take(Chip8::MEM_SIZE)?;
+
+        let (width, height) = (r.u8()? as usize, r.u8()? as usize);
+        if pc >= Chip8::MEM_SIZE || sp > Chip8::STACK_SIZE || width != self.display.width() || height != self.display.height() {
+            return Err(SnapshotError::Invalid);
+        }
+        let mut display = Framebuffer::new(width, height);
+        for plane in 0..Framebuffer::PLANES {
+            for y in 0..height {
+                display.set_row(plane, y, u128::from_le_bytes(r.take(16)?.try_into().unwrap()));
+            }
+        }
+
+        self.pc = pc;
+        self.i = i;
+        self.sp = sp;
+        self.reg = reg;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.waiting_for_key = waiting_for_key;
+        self.waiting_for_vblank = waiting_for_vblank;
+        self.cycles = cycles;
+        self.memory.load(0, memory);
+        self.display = display;
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl <'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
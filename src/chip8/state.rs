@@ -0,0 +1,17 @@
+/// Registers, stack and timers of a machine, see `Chip8::state` and `Chip8::set_state`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct State {
+    pub pc: usize,
+    pub i: usize,
+    /// Number of return addresses on the stack
+    pub sp: usize,
+    /// V0 to VF
+    pub reg: [u8; 16],
+    pub stack: [usize; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Register an Fx0A instruction is waiting on
+    pub waiting_for_key: Option<u8>,
+    /// Instructions executed since creation
+    pub cycles: u64,
+}
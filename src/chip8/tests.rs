@@ -1,7 +1,10 @@
 use crate::chip8;
-use chip8::{Chip8};
+use chip8::{Chip8, Framebuffer};
 
-use super::dump_display;
+use super::{disassemble, Breakpoints, ProgramTooLarge, dump_display, opcode_class, Coverage, CoverageError, Profiler, SnapshotError, SubroutineStats, write_display, write_machine_state, Bus, BusError, Fault, FlatRam, MemoryPolicy, Quirks, WriteProtect, FONT};
+
+#[cfg(feature = "romdb")]
+mod golden;
 
 #[test]
 fn test_reset() {
@@ -52,7 +55,7 @@ fn test_instr_ld_immediate_to_gp() {
     let mut c8 = Chip8::new(test_program);
     c8.reg = [255; 16];
     let pc_before = c8.pc;
-    c8.step(16);
+    c8.step(16).unwrap();
     assert_eq!(c8.pc, pc_before + test_program.len());
 
     for i in 0..16 {
@@ -66,13 +69,19 @@ fn test_instr_clear_display() {
         0x00, 0xe0
     ];
     let mut c8 = Chip8::new(test_program);
-    c8.display = [255; Chip8::DISPLAY_SIZE];
+    for y in 0..Chip8::DISPLAY_HEIGHT {
+        for x in (0..Chip8::DISPLAY_WIDTH).step_by(8) {
+            c8.display.xor_sprite_row(0, x, y, 0xff);
+        }
+    }
+    assert!(c8.pixels().iter().all(|px| *px == 255));
 
     let pc_before = c8.pc;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc_before + test_program.len());
+    let pixels = c8.pixels();
     for i in 0..Chip8::DISPLAY_SIZE {
-        assert_eq!(c8.display[i], 0);
+        assert_eq!(pixels[i], 0);
     }
 }
 
@@ -82,7 +91,7 @@ fn test_instr_jump() {
         0x1a, 0xbc
     ];
     let mut c8 = Chip8::new(test_program);
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, 0xabc);
 }
 
@@ -93,7 +102,7 @@ fn test_instr_call() {
     ];
     let mut c8 = Chip8::new(test_program);
     let pc_before = c8.pc;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, 0xabc);
     assert_eq!(c8.sp, 1);
     assert_eq!(c8.stack[0], pc_before + 2);
@@ -108,7 +117,7 @@ fn test_instr_stack_underflow() {
     ];
     let mut c8 = Chip8::new(test_program);
     assert_eq!(c8.sp, 0);
-    c8.step(1);
+    c8.step(1).unwrap();
     assert!(false);     // should never get here
 }
 
@@ -137,11 +146,11 @@ fn test_instr_stack_overflow() {
     let mut c8 = Chip8::new(test_program);
     assert_eq!(c8.sp, 0);
     for i in 0..Chip8::STACK_SIZE {
-        c8.step(1);
+        c8.step(1).unwrap();
         assert_eq!(c8.sp, i + 1)
     }
 
-    c8.step(1);         // panic
+    c8.step(1).unwrap();         // panic
     assert!(false);     // we should never get here
 }
 
@@ -154,12 +163,12 @@ fn test_instr_skip_next_if_equals_immediate() {
     let mut c8 = Chip8::new(test_program);
     let pc = c8.pc;
     c8.reg[0xf] = 0xff;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 4);
 
     c8.reset();
     c8.reg[0xf] = 0x0;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 2);
 }
 
@@ -172,12 +181,12 @@ fn test_instr_skip_next_if_not_equals_immediate() {
     let mut c8 = Chip8::new(test_program);
     let pc = c8.pc;
     c8.reg[0xf] = 0x00;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 4);
 
     c8.reset();
     c8.reg[0xf] = 0xff;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 2);
 }
 
@@ -191,14 +200,14 @@ fn test_instr_skip_next_if_not_equals_register() {
     let pc = c8.pc;
     c8.reg[0x1] = 0x55;
     c8.reg[0x2] = 0x55;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 4);
 
     c8.reset();
     assert_eq!(pc, c8.pc);
     c8.reg[0x1] = 0x55;
     c8.reg[0x2] = 0x44;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 2);
 }
 
@@ -211,7 +220,7 @@ fn test_instr_add_immediate() {
     let mut c8 = Chip8::new(test_program);
     let pc = c8.pc;
     c8.reg[1] = 0x12;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[1], 0x12 + 0x34);
     assert_eq!(c8.pc, pc + 2);
 
@@ -223,7 +232,7 @@ fn test_instr_add_immediate() {
     let mut c8 = Chip8::new(test_program);
     let _pc = c8.pc;
     c8.reg[0] = 0xff;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 1);
 }
 
@@ -237,7 +246,7 @@ fn test_instr_alu_nop() {
     let pc = c8.pc;
     c8.reg[0] = 0x12;
     c8.reg[1] = 0x34;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 0x12);
     assert_eq!(c8.reg[1], 0x12);
     assert_eq!(c8.pc, pc + 2);
@@ -253,7 +262,7 @@ fn test_instr_alu_and() {
     let pc = c8.pc;
     c8.reg[0] = 0xaa;
     c8.reg[1] = 0xf0;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 0xaa);
     assert_eq!(c8.reg[1], 0xa0);
     assert_eq!(c8.pc, pc + 2);
@@ -269,7 +278,7 @@ fn test_instr_alu_or() {
     let pc = c8.pc;
     c8.reg[0] = 0xaa;
     c8.reg[1] = 0x55;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 0xaa);
     assert_eq!(c8.reg[1], 0xff);
     assert_eq!(c8.pc, pc + 2);
@@ -285,7 +294,7 @@ fn test_instr_alu_xor() {
     let pc = c8.pc;
     c8.reg[0] = 0xaa;
     c8.reg[1] = 0x0f;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 0xaa);
     assert_eq!(c8.reg[1], 0xa5);
     assert_eq!(c8.pc, pc + 2);
@@ -301,7 +310,7 @@ fn test_instr_alu_add() {
     let pc = c8.pc;
     c8.reg[0] = 0x12;
     c8.reg[1] = 0x3;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 0x12);
     assert_eq!(c8.reg[1], 0x12 + 0x3);
     assert_eq!(c8.pc, pc + 2);
@@ -310,7 +319,7 @@ fn test_instr_alu_add() {
     c8.reset();
     c8.reg[0] = 0xff;
     c8.reg[1] = 0x3;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 0xff);
     assert_eq!(c8.reg[1], 0x2);
     assert_eq!(c8.pc, pc + 2);
@@ -328,7 +337,7 @@ fn test_instr_alu_sub() {
     let pc = c8.pc;
     c8.reg[0] = 2;
     c8.reg[1] = 5;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 2);
     assert_eq!(c8.reg[1], 5 - 2);
     assert_eq!(c8.pc, pc + 2);
@@ -337,7 +346,7 @@ fn test_instr_alu_sub() {
     c8.reset();
     c8.reg[0] = 5;
     c8.reg[1] = 2;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 5);
     assert_eq!(c8.reg[1], 0xfd);
     assert_eq!(c8.pc, pc + 2);
@@ -354,14 +363,14 @@ fn test_instr_alu_shift_right() {
     let mut c8 = Chip8::new(test_program);
     let pc = c8.pc;
     c8.reg[0] = 2;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 1);
     assert_eq!(c8.pc, pc + 2);
     assert_eq!(c8.reg[15], 0);
 
     c8.reset();
     c8.reg[0] = 0x81;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 0x40);
     assert_eq!(c8.pc, pc + 2);
     assert_eq!(c8.reg[15], 1);
@@ -378,7 +387,7 @@ fn test_instr_alu_subn() {
     let pc = c8.pc;
     c8.reg[0] = 5;
     c8.reg[1] = 2;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 5);
     assert_eq!(c8.reg[1], 5 - 2);
     assert_eq!(c8.pc, pc + 2);
@@ -387,7 +396,7 @@ fn test_instr_alu_subn() {
     c8.reset();
     c8.reg[0] = 2;
     c8.reg[1] = 5;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0], 2);
     assert_eq!(c8.reg[1], 0xfd);
     assert_eq!(c8.pc, pc + 2);
@@ -409,18 +418,18 @@ fn test_instr_rnd() {
     let mut c8 = Chip8::new(test_program);
     let pc = c8.pc;
     c8.reg[0] = 0xff;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 2);
     assert_eq!(c8.reg[0] & 0xf, 0);
     c8.reg[0] = 0xff;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[0] & 0xf0, 0);
 
     // make sure there's no more than 3 consequtive same numbers..
-    c8.step(1);
+    c8.step(1).unwrap();
     let r = c8.reg[1];
     for i in 1..4 {
-        c8.step(1);
+        c8.step(1).unwrap();
         if r != c8.reg[i] {
             break;
         }
@@ -430,6 +439,40 @@ fn test_instr_rnd() {
     }
 }
 
+#[test]
+fn test_instr_rnd_injected_rng() {
+    let test_program: &[u8] = &[
+        0xc0, 0xff,
+        0xc1, 0x0f,
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.set_rng(rand::rngs::mock::StepRng::new(0xab, 1));
+    c8.step(2).unwrap();
+    assert_eq!(c8.reg[0], 0xab);
+    assert_eq!(c8.reg[1], 0x0c);
+}
+
+#[test]
+fn test_write_dumps() {
+    let test_program: &[u8] = &[
+        0xd0, 0x01,     // draw the top row of the '0' glyph
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    let mut text = String::new();
+    write_machine_state(&c8, &mut text).unwrap();
+    assert!(text.starts_with("pc: $0x0200, instr: 0xD001, i: 0x0000"));
+
+    c8.step(1).unwrap();
+    text.clear();
+    write_display(&c8, &mut text).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), Chip8::DISPLAY_HEIGHT);
+    assert_eq!(&lines[0][..8], "xxxx....");
+    assert!(lines[1].chars().all(|c| c == '.'));
+}
+
 #[test]
 fn test_instr_draw() {
     let test_program: &[u8] = &[
@@ -444,43 +487,46 @@ fn test_instr_draw() {
     c8.memory[0x300..0x308].copy_from_slice(&[0xff; 8]);
 
     // Light up 8 top left pixels
-    c8.step(1);
+    c8.step(1).unwrap();
+    let pixels = c8.pixels();
 
     for i in 0..8 {
-        assert_eq!(c8.display[i], 255);
+        assert_eq!(pixels[i], 255);
     }
 
     for i in 0..8 {
-        assert_eq!(c8.display[Chip8::DISPLAY_WIDTH + i], 0);
+        assert_eq!(pixels[Chip8::DISPLAY_WIDTH + i], 0);
     }
 
     assert_eq!(c8.pc, pc + 2);
-    assert_eq!(c8.display[9], 0);
+    assert_eq!(pixels[9], 0);
     assert_eq!(c8.reg[15], 0);      // no collision
 
-    c8.step(1);
+    c8.step(1).unwrap();
+    let pixels = c8.pixels();
     assert_eq!(c8.reg[15], 1);      // should have collision
     for i in 0..9 {
-        assert_eq!(c8.display[i], 0);
+        assert_eq!(pixels[i], 0);
     }
 
     for i in 0..8 {
-        assert_eq!(c8.display[Chip8::DISPLAY_WIDTH + i], 0);
+        assert_eq!(pixels[Chip8::DISPLAY_WIDTH + i], 0);
     }
 
     // Test wrapping
 
-    c8.display = [0; Chip8::DISPLAY_SIZE];
+    c8.display.clear();
     c8.reg[0] = 61;
     c8.reg[1] = 30;
 
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[15], 0);
 //    dump_display(&c8);
-    assert_rect(&c8.display, (0, 0, 5, 6), 255);
-    assert_rect(&c8.display, (61, 0, 3, 6), 255);
-    assert_rect(&c8.display, (61, 30, 3, 2), 255);
-    assert_rect(&c8.display, (0, 30, 5, 2), 255);
+    let pixels = c8.pixels();
+    assert_rect(&pixels, (0, 0, 5, 6), 255);
+    assert_rect(&pixels, (61, 0, 3, 6), 255);
+    assert_rect(&pixels, (61, 30, 3, 2), 255);
+    assert_rect(&pixels, (0, 30, 5, 2), 255);
 
 
 }
@@ -497,12 +543,13 @@ fn test_instr_draw_orientation() {
     c8.memory[0x300] = 0x0f;
 
     // Top left should be 00001111
-    c8.step(1);
+    c8.step(1).unwrap();
 
     dump_display(&c8);
-    assert_rect(&c8.display, (0, 0, 4, 2), 0);
-    assert_rect(&c8.display, (4, 0, 4, 1), 255);
-    assert_rect(&c8.display, (4, 1, 4, 1), 0);
+    let pixels = c8.pixels();
+    assert_rect(&pixels, (0, 0, 4, 2), 0);
+    assert_rect(&pixels, (4, 0, 4, 1), 255);
+    assert_rect(&pixels, (4, 1, 4, 1), 0);
 }
 
 #[test]
@@ -519,7 +566,7 @@ fn test_instr_st_regs() {
     }
 
     c8.memory[0x300..0x308].copy_from_slice(&[0xff; 8]);
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 2);
     assert_eq!(c8.i, 0x300 + 4);
     for i in 0..4 {
@@ -531,6 +578,319 @@ fn test_instr_st_regs() {
 
 
 
+#[test]
+fn test_self_modifying_code() {
+    let test_program: &[u8] = &[
+        0xa2, 0x08,     // i = 0x208
+        0x60, 0x6a,     // gp0 = 0x6a
+        0x61, 0x42,     // gp1 = 0x42
+        0xf1, 0x55,     // store gp0..gp1 to [i], rewriting the next instruction
+        0x00, 0xe0,     // becomes 6a 42: gpa = 0x42
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.step(5).unwrap();
+    assert_eq!(c8.memory[0x208..0x20a], [0x6a, 0x42]);
+    assert_eq!(c8.reg[0xa], 0x42);
+    assert_eq!(c8.pc, 0x20a);
+}
+
+#[test]
+fn test_memory_policy_wrap() {
+    let test_program: &[u8] = &[
+        0xf1, 0x65,     // load gp0..gp1 from [i]
+        0xd0, 0x12,     // draw 2 rows from [i]
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    assert_eq!(c8.memory_policy(), MemoryPolicy::Wrap);
+    c8.memory[0xfff] = 0xaa;
+    c8.i = 0xfff;
+    c8.step(1).unwrap();
+    assert_eq!(c8.reg[0], 0xaa);
+    assert_eq!(c8.reg[1], 0xf0);    // top row of the '0' glyph at 0x000
+
+    c8.i = 0xfff;
+    c8.reg[0] = 0;
+    c8.reg[1] = 0;
+    c8.step(1).unwrap();
+    let pixels = c8.pixels();
+    for (x, px) in pixels[..8].iter().enumerate() {
+        assert_eq!(*px, if 0xaa >> (7 - x) & 1 != 0 { 255 } else { 0 });
+    }
+    for (x, px) in pixels[Chip8::DISPLAY_WIDTH..Chip8::DISPLAY_WIDTH + 8].iter().enumerate() {
+        assert_eq!(*px, if x < 4 { 255 } else { 0 });
+    }
+}
+
+#[test]
+fn test_memory_policy_fault() {
+    let test_program: &[u8] = &[
+        0xf1, 0x55,     // store gp0..gp1 to [i]
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.set_memory_policy(MemoryPolicy::Fault);
+    c8.i = 0xfff;
+    c8.reg[0] = 0x12;
+    c8.reg[1] = 0x34;
+    assert_eq!(c8.step(1), Err(Fault::MemoryOutOfBounds { pc: 0x200, addr: 0x1000 }));
+    assert_eq!(c8.memory[0xfff], 0x12);
+    assert_eq!(c8.memory[0x000], FONT[0]);
+    assert_eq!(c8.pc, 0x200);
+
+    c8.set_memory_policy(MemoryPolicy::Log);
+    c8.i = 0xfff;
+    c8.step(1).unwrap();
+    assert_eq!(c8.memory[0x000], 0x34);
+    assert_eq!(c8.pc, 0x202);
+}
+
+#[test]
+fn test_fetch_wraps_at_end_of_memory() {
+    let test_program: &[u8] = &[
+        0x1f, 0xff,     // jump to 0xfff
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.memory[0xfff] = 0x60;        // gp0 = [0x000], the first byte of the font
+    c8.step(2).unwrap();
+    assert_eq!(c8.reg[0], FONT[0]);
+
+    c8.reset();
+    c8.set_memory_policy(MemoryPolicy::Fault);
+    assert_eq!(c8.step(2), Err(Fault::MemoryOutOfBounds { pc: 0xfff, addr: 0x1000 }));
+}
+
+#[test]
+fn test_bus_write_protect() {
+    let test_program: &[u8] = &[
+        0xf0, 0x55,     // store gp0 to [i]
+    ];
+
+    let bus = WriteProtect::new(FlatRam::new(), 0..Chip8::RESET_VECTOR);
+    let mut c8 = Chip8::with_bus(test_program, bus);
+    assert_eq!(c8.bus().peek(0), FONT[0]);
+
+    c8.i = 0x1ff;
+    c8.reg[0] = 0xaa;
+    assert_eq!(c8.step(1), Err(Fault::Bus { pc: 0x200, addr: 0x1ff, error: BusError::ReadOnly }));
+    assert_eq!(c8.bus().peek(0x1ff), 0);
+
+    c8.i = 0x300;
+    c8.step(1).unwrap();
+    assert_eq!(c8.bus().inner()[0x300], 0xaa);
+}
+
+#[test]
+fn test_unimplemented_instruction_fault() {
+    let test_program: &[u8] = &[
+        0x60, 0x01,     // gp0 = 1
+        0xf0, 0x33,     // not implemented
+        0x80, 0x18,     // not an instruction
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    let fault = c8.step(3).unwrap_err();
+    assert_eq!(fault, Fault::Unimplemented { pc: 0x202, instr: 0xf033 });
+    assert_eq!(fault.to_string(), "Unimplemented instruction 0xf033 at 0x202");
+    assert_eq!(c8.pc, 0x202);
+
+    c8.pc = 0x204;
+    assert_eq!(c8.step(1), Err(Fault::Unimplemented { pc: 0x204, instr: 0x8018 }));
+}
+
+// Counter mapped at 0xff0 that increments on every read
+struct CounterBus {
+    ram: FlatRam,
+    counter: u8,
+}
+
+impl Bus for CounterBus {
+    fn read(&mut self, addr: usize) -> Result<u8, BusError> {
+        if addr == 0xff0 {
+            self.counter += 1;
+            return Ok(self.counter);
+        }
+        self.ram.read(addr)
+    }
+
+    fn write(&mut self, addr: usize, value: u8) -> Result<(), BusError> {
+        if addr == 0xff0 {
+            return Err(BusError::ReadOnly);
+        }
+        self.ram.write(addr, value)
+    }
+
+    fn peek(&self, addr: usize) -> u8 {
+        if addr == 0xff0 { self.counter } else { self.ram.peek(addr) }
+    }
+
+    fn poke(&mut self, addr: usize, value: u8) {
+        self.ram.poke(addr, value)
+    }
+}
+
+#[test]
+fn test_bus_mapped_device() {
+    let test_program: &[u8] = &[
+        0xaf, 0xf0,     // i = 0xff0
+        0xf0, 0x65,     // load gp0 from [i]
+        0xaf, 0xf0,     // i = 0xff0
+        0xf0, 0x65,     // load gp0 from [i]
+    ];
+
+    let mut c8 = Chip8::with_bus(test_program, CounterBus { ram: FlatRam::new(), counter: 0 });
+    c8.step(2).unwrap();
+    assert_eq!(c8.reg[0], 1);
+    c8.step(2).unwrap();
+    assert_eq!(c8.reg[0], 2);
+    assert_eq!(c8.bus().peek(0xff0), 2);
+    assert_eq!(c8.bus_mut().counter, 2);
+}
+
+#[test]
+fn test_state_roundtrip() {
+    let test_program: &[u8] = &[
+        0x60, 0x01,     // gp0 = 1
+        0x22, 0x06,     // call 0x206
+        0x12, 0x04,     // loop
+        0x70, 0x01,     // gp0 += 1
+        0x00, 0xee,     // return
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.step(3).unwrap();
+    let state = c8.state();
+    assert_eq!(state.pc, 0x208);
+    assert_eq!(state.sp, 1);
+    assert_eq!(state.stack[0], 0x204);
+    assert_eq!(state.reg[0], 2);
+    assert_eq!(state.cycles, 3);
+
+    c8.step(2).unwrap();
+    assert_eq!(c8.state().pc, 0x204);
+    c8.set_state(&state);
+    assert_eq!(c8.state(), state);
+    c8.step(1).unwrap();
+    assert_eq!(c8.state().pc, 0x204);
+    assert_eq!(c8.state().sp, 0);
+}
+
+#[test]
+fn test_snapshot_roundtrip() {
+    let test_program: &[u8] = &[
+        0x60, 0x05,     // gp0 = 5
+        0xf0, 0x29,     // i = glyph for gp0
+        0xd0, 0x05,     // draw it
+        0x70, 0x01,     // gp0 += 1
+        0x12, 0x02,     // loop
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.step(4).unwrap();
+    let snapshot = c8.save_snapshot();
+    let state = c8.state();
+    let pixels = c8.pixels();
+
+    c8.step(40).unwrap();
+    c8.memory[0x300] = 0xaa;
+    assert_ne!(c8.pixels(), pixels);
+
+    c8.load_snapshot(&snapshot).unwrap();
+    assert_eq!(c8.state(), state);
+    assert_eq!(c8.pixels(), pixels);
+    assert_eq!(c8.memory[0x300], 0);
+    assert_eq!(c8.save_snapshot(), snapshot);
+
+    let mut other = Chip8::new(&[]);
+    other.load_snapshot(&snapshot).unwrap();
+    other.step(3).unwrap();
+    c8.step(3).unwrap();
+    assert_eq!(other.state(), c8.state());
+    assert_eq!(other.pixels(), c8.pixels());
+}
+
+#[test]
+fn test_snapshot_waiting_for_vblank() {
+    let test_program: &[u8] = &[
+        0xd0, 0x01,     // draw
+        0x60, 0x01,     // gp0 = 1
+        0x12, 0x04,     // loop
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.set_quirks(Quirks { vblank: true, ..Quirks::default() });
+    c8.step(10).unwrap();
+    let snapshot = c8.save_snapshot();
+
+    let mut other = Chip8::new(test_program);
+    other.set_quirks(Quirks { vblank: true, ..Quirks::default() });
+    other.load_snapshot(&snapshot).unwrap();
+    other.step(10).unwrap();
+    assert_eq!(other.state().reg[0], 0);
+    other.tick_60hz();
+    other.step(10).unwrap();
+    assert_eq!(other.state().reg[0], 1);
+}
+
+#[test]
+fn test_snapshot_errors() {
+    let mut c8 = Chip8::new(&[0x60, 0x01]);
+    let mut snapshot = c8.save_snapshot();
+    c8.step(1).unwrap();
+    let state = c8.state();
+
+    assert_eq!(c8.load_snapshot(&snapshot[..snapshot.len() - 1]), Err(SnapshotError::Truncated));
+    assert_eq!(c8.load_snapshot(b"nope"), Err(SnapshotError::BadMagic));
+    snapshot[4] = 99;
+    assert_eq!(c8.load_snapshot(&snapshot), Err(SnapshotError::UnsupportedVersion(99)));
+    snapshot[4] = c8.save_snapshot()[4];
+    snapshot[5..9].copy_from_slice(&(Chip8::MEM_SIZE as u32).to_le_bytes());
+    assert_eq!(c8.load_snapshot(&snapshot), Err(SnapshotError::Invalid));
+    assert_eq!(c8.state(), state);
+}
+
+#[test]
+fn test_load_program() {
+    let mut c8 = Chip8::from_rom(vec![0x60, 0x01]);
+    c8.step(1).unwrap();
+    assert_eq!(c8.state().reg[0], 1);
+
+    c8.load_program(vec![0x61, 0x02]);
+    assert_eq!(c8.program(), [0x61, 0x02]);
+    assert_eq!(c8.state().pc, 0x200);
+    assert_eq!(c8.state().reg[0], 0);
+    c8.step(1).unwrap();
+    assert_eq!(c8.state().reg[1], 2);
+}
+
+#[test]
+fn test_patch_program() {
+    let mut c8 = Chip8::from_rom(vec![0x60, 0x01, 0x12, 0x02, 0xaa, 0xbb, 0xcc]);
+    c8.step(2).unwrap();
+    c8.bus_mut().poke(0x205, 0x55);
+
+    // Only changed bytes are written, the machine keeps running where it was
+    assert_eq!(c8.patch_program(vec![0x60, 0x01, 0x12, 0x02, 0xab, 0xbb]), 2);
+    assert_eq!(c8.program(), [0x60, 0x01, 0x12, 0x02, 0xab, 0xbb]);
+    assert_eq!(c8.state().pc, 0x202);
+    assert_eq!(c8.state().reg[0], 1);
+    assert_eq!(c8.bus().peek(0x204), 0xab);
+    assert_eq!(c8.bus().peek(0x205), 0x55);
+    assert_eq!(c8.bus().peek(0x206), 0x00);
+
+    // A reset loads the patched program
+    c8.reset();
+    assert_eq!(c8.bus().peek(0x205), 0xbb);
+}
+
+#[test]
+#[should_panic(expected = "Program too large")]
+fn test_program_too_large() {
+    Chip8::from_rom(vec![0; Chip8::MAX_PROGRAM_SIZE + 1]);
+}
+
 #[test]
 fn test_instr_ld_regs() {
     let test_program: &[u8] = &[
@@ -545,7 +905,7 @@ fn test_instr_ld_regs() {
     }
 
     c8.reg = [0xff; 16];
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 2);
     assert_eq!(c8.i, 0x300 + 4);
     for i in 0..4 {
@@ -565,7 +925,7 @@ fn test_instr_add_reg_to_i() {
     let pc = c8.pc;
     c8.i = 0x123;
     c8.reg[14] = 2;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.reg[14], 2);
     assert_eq!(c8.pc, pc + 2);
     assert_eq!(c8.i, 0x123 + 2);
@@ -580,16 +940,74 @@ fn test_instr_skip_next_if_key_not_pressed() {
     let mut c8 = Chip8::new(test_program);
     let pc = c8.pc;
     c8.reg[0x0] = 'a' as u8;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 4);
 
     c8.reset();
     c8.reg[0x0] = 'a' as u8;
     c8.keys['a' as usize] = 255;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 2);
 }
 
+#[test]
+fn test_release_keys_keeps_waiting_for_key() {
+    let test_program: &[u8] = &[
+        0x60, 0x05,   // gp0 = 5
+        0xf0, 0x0a,   // wait for key gp0
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.step(2).unwrap();
+    assert!(c8.is_waiting_for_key());
+    c8.set_key_state(3, true);
+    c8.release_keys();
+    assert!(!c8.key_state(3));
+    assert!(c8.is_waiting_for_key());
+
+    c8.set_key_state(5, true);
+    assert!(!c8.is_waiting_for_key());
+}
+
+#[test]
+fn test_step_one_and_breakpoints() {
+    let test_program: &[u8] = &[
+        0x60, 0x05,   // gp0 = 5
+        0x12, 0x04,   // jump 0x204
+        0xf0, 0x0a,   // wait for key gp0
+        0x12, 0x04,   // jump 0x204
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    let mut breakpoints = Breakpoints::new();
+    breakpoints.insert(0x202);
+    assert!(!breakpoints.hit(&c8));
+    assert_eq!(breakpoints.step(&mut c8), Ok(true));
+    assert!(breakpoints.hit(&c8));
+
+    // Continuing runs the instruction at the breakpoint first
+    breakpoints.resume();
+    assert!(!breakpoints.hit(&c8));
+    assert_eq!(breakpoints.step(&mut c8), Ok(true));
+
+    breakpoints.set([0x206]);
+    assert_eq!(c8.step_one(), Ok(true));
+    assert!(c8.is_waiting_for_key());
+    assert_eq!(c8.step_one(), Ok(false));
+    assert_eq!(c8.state().pc, 0x206);
+    assert!(breakpoints.hit(&c8));
+    breakpoints.remove(0x206);
+    assert!(!breakpoints.hit(&c8));
+}
+
+#[test]
+fn test_check_program_size() {
+    assert_eq!(Chip8::check_program_size(Chip8::MAX_PROGRAM_SIZE), Ok(()));
+    let e = Chip8::check_program_size(4000).unwrap_err();
+    assert_eq!(e, ProgramTooLarge(4000));
+    assert_eq!(e.to_string(), "ROM is 4000 bytes, at most 3584 fit in memory");
+}
+
 #[test]
 fn test_instr_skip_next_if_key_pressed() {
     let test_program: &[u8] = &[
@@ -599,13 +1017,13 @@ fn test_instr_skip_next_if_key_pressed() {
     let mut c8 = Chip8::new(test_program);
     let pc = c8.pc;
     c8.reg[0x0] = 'a' as u8;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 2);
 
     c8.reset();
     c8.reg[0x0] = 'a' as u8;
     c8.keys['a' as usize] = 255;
-    c8.step(1);
+    c8.step(1).unwrap();
     assert_eq!(c8.pc, pc + 4);
 }
 
@@ -617,4 +1035,301 @@ fn assert_rect(d: &[u8], rect: (usize, usize, usize, usize), v: u8) {
             assert_eq!(d[y * Chip8::DISPLAY_WIDTH + x], v);
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_framebuffer_xor_sprite_row() {
+    let mut fb = Framebuffer::new(Chip8::DISPLAY_WIDTH, Chip8::DISPLAY_HEIGHT);
+
+    // Wraps around the right edge
+    assert!(!fb.xor_sprite_row(0, 60, 3, 0b1100_0011));
+    assert_eq!(fb.rows(0)[3], 0b11u64 << 60 | 0b1100);
+    assert_eq!(fb.pixel(60, 3), 1);
+    assert_eq!(fb.pixel(62, 3), 0);
+    assert_eq!(fb.pixel(1, 3), 0);
+    assert_eq!(fb.pixel(2, 3), 1);
+    assert_eq!(fb.pixel(3, 3), 1);
+
+    // Drawing the same row again erases it and reports a collision
+    assert!(fb.xor_sprite_row(0, 60, 3, 0b1100_0011));
+    assert!(fb.rows(0).iter().all(|row| *row == 0));
+
+    // Lit pixels of any plane are unpacked
+    fb.xor_sprite_row(1, 0, Chip8::DISPLAY_HEIGHT, 0x80);
+    assert_eq!(fb.pixel(0, 0), 2);
+    let mut pixels = vec![0; Chip8::DISPLAY_SIZE];
+    fb.unpack(&mut pixels);
+    assert_eq!(pixels[0], 255);
+    assert!(pixels[1..].iter().all(|px| *px == 0));
+}
+
+#[test]
+fn test_framebuffer_hires() {
+    let mut fb = Framebuffer::new(128, 64);
+    fb.xor_sprite_row(0, 124, 63, 0xff);
+    assert_eq!(fb.row(0, 63), 0xfu128 | 0xf << 124);
+    assert_eq!(fb.rows(0)[126..], [0xf << 60, 0xf]);
+    assert_eq!(fb.pixel(127, 63), 1);
+    assert_eq!(fb.pixel(0, 63), 1);
+    assert_eq!(fb.pixel(4, 63), 0);
+}
+
+#[test]
+fn test_framebuffer_odd_width() {
+    // 20 pixels, the last unpacked byte holds only 4 of them
+    let mut fb = Framebuffer::new(20, 2);
+    assert!(!fb.xor_sprite_row(0, 14, 1, 0b1011_0001));
+    assert_eq!(fb.row(0, 1), 0b10_1100 | 0b01 << 18);
+    assert_eq!(fb.pixel(14, 1), 1);
+    assert_eq!(fb.pixel(15, 1), 0);
+    assert_eq!(fb.pixel(1, 1), 1);
+
+    let mut pixels = vec![0; 40];
+    fb.unpack(&mut pixels);
+    let lit: Vec<usize> = (0..20).filter(|x| pixels[20 + x] == 255).collect();
+    assert_eq!(lit, [1, 14, 16, 17]);
+    assert!(pixels[..20].iter().all(|px| *px == 0));
+}
+
+#[test]
+fn test_framebuffer_xor_sprite_clipped() {
+    let mut fb = Framebuffer::new(Chip8::DISPLAY_WIDTH, Chip8::DISPLAY_HEIGHT);
+    assert!(!fb.xor_sprite_clipped(0, 60, 30, &[0xff, 0xff, 0xff]));
+    assert_eq!(fb.rows(0)[30], 0xf);
+    assert_eq!(fb.rows(0)[31], 0xf);
+    assert_eq!(fb.rows(0)[0], 0);
+
+    // Only the position wraps
+    fb.clear();
+    fb.xor_sprite_clipped(0, 64 + 2, 32 + 1, &[0x80]);
+    assert_eq!(fb.pixel(2, 1), 1);
+}
+
+#[test]
+fn test_quirk_shift() {
+    let test_program: &[u8] = &[
+        0x80, 0x16,     // SHR gp0, gp1
+        0x82, 0x3e,     // SHL gp2, gp3
+    ];
+    let mut c8 = Chip8::new(test_program);
+    c8.reg[..4].copy_from_slice(&[0x80, 0x03, 0x01, 0x81]);
+    c8.step(2).unwrap();
+    assert_eq!(c8.reg[..4], [0x40, 0x03, 0x02, 0x81]);
+    assert_eq!(c8.reg[15], 0);
+
+    c8.reset();
+    c8.set_quirks(Quirks { shift: false, ..Quirks::default() });
+    c8.reg[..4].copy_from_slice(&[0x80, 0x03, 0x01, 0x81]);
+    c8.step(2).unwrap();
+    assert_eq!(c8.reg[..4], [0x01, 0x03, 0x02, 0x81]);
+    assert_eq!(c8.reg[15], 1);
+}
+
+#[test]
+fn test_quirk_memory() {
+    let test_program: &[u8] = &[
+        0xa3, 0x00,     // LD I, 0x300
+        0xf2, 0x55,     // LD [I], gp0..gp2
+    ];
+    let cases = [
+        (Quirks::default(), 0x303),
+        (Quirks { memory_increment_by_x: true, ..Quirks::default() }, 0x302),
+        (Quirks { memory_leave_i_unchanged: true, ..Quirks::default() }, 0x300),
+    ];
+    for (quirks, i) in cases {
+        let mut c8 = Chip8::new(test_program);
+        c8.set_quirks(quirks);
+        c8.reg[..3].copy_from_slice(&[1, 2, 3]);
+        c8.step(2).unwrap();
+        assert_eq!(c8.i, i);
+        assert_eq!(c8.memory[0x300..0x303], [1, 2, 3]);
+    }
+}
+
+#[test]
+fn test_quirk_jump() {
+    let test_program: &[u8] = &[0xb3, 0x00];
+    let mut c8 = Chip8::new(test_program);
+    c8.reg[0] = 0x10;
+    c8.reg[3] = 0x20;
+    c8.step(1).unwrap();
+    assert_eq!(c8.pc, 0x310);
+
+    c8.reset();
+    c8.set_quirks(Quirks { jump: true, ..Quirks::default() });
+    c8.reg[0] = 0x10;
+    c8.reg[3] = 0x20;
+    c8.step(1).unwrap();
+    assert_eq!(c8.pc, 0x320);
+}
+
+#[test]
+fn test_quirk_logic() {
+    let test_program: &[u8] = &[0x80, 0x11];
+    let mut c8 = Chip8::new(test_program);
+    c8.reg[15] = 1;
+    c8.step(1).unwrap();
+    assert_eq!(c8.reg[15], 1);
+
+    c8.reset();
+    c8.set_quirks(Quirks { logic: true, ..Quirks::default() });
+    c8.reg[15] = 1;
+    c8.step(1).unwrap();
+    assert_eq!(c8.reg[15], 0);
+}
+
+#[test]
+fn test_quirk_wrap_and_vblank() {
+    let test_program: &[u8] = &[
+        0x60, 0x3c,     // LD gp0, 60
+        0xd0, 0x11,     // DRW gp0, gp1, 1
+        0x70, 0x01,     // ADD gp0, 1
+    ];
+    let mut c8 = Chip8::new(test_program);
+    c8.i = 0x100;
+    c8.memory[0x100] = 0xff;
+    c8.set_quirks(Quirks { wrap: false, vblank: true, ..Quirks::default() });
+
+    // Execution stops after the draw until the next tick
+    c8.step(3).unwrap();
+    assert_eq!(c8.pc, 0x204);
+    assert_eq!(c8.framebuffer().rows(0)[0], 0xf);
+    c8.step(1).unwrap();
+    assert_eq!(c8.pc, 0x204);
+
+    c8.tick_60hz();
+    c8.step(1).unwrap();
+    assert_eq!(c8.reg[0], 61);
+}
+
+#[test]
+fn test_timers_stop_at_zero() {
+    let test_program: &[u8] = &[
+        0x60, 0x01,   // gp0 = 1
+        0xf0, 0x15,   // delay timer = gp0
+        0xf0, 0x18,   // sound timer = gp0
+    ];
+
+    let mut c8 = Chip8::new(test_program);
+    c8.step(3).unwrap();
+    c8.tick_60hz();
+    assert_eq!((c8.delay_timer, c8.sound_timer), (0, 0));
+    c8.tick_60hz();
+    assert_eq!((c8.delay_timer, c8.sound_timer), (0, 0));
+}
+
+#[test]
+fn test_opcode_class() {
+    assert_eq!(opcode_class(0x00e0), Some("00E0"));
+    assert_eq!(opcode_class(0x8124), Some("8xy4"));
+    assert_eq!(opcode_class(0xf229), Some("Fx29"));
+    assert_eq!(opcode_class(0x8128), None);
+    assert_eq!(opcode_class(0x0123), None);
+    assert_eq!(opcode_class(0xf033), None);
+}
+
+#[test]
+fn test_profiler() {
+    let program = [
+        0x22, 0x06,     // 0x200: call 0x206
+        0x22, 0x0a,     // 0x202: call 0x20a
+        0x12, 0x04,     // 0x204: loop forever
+        0x22, 0x0a,     // 0x206: call 0x20a
+        0x00, 0xee,     // 0x208: return
+        0x70, 0x01,     // 0x20a: v0 += 1
+        0x00, 0xee,     // 0x20c: return
+    ];
+    let mut c8 = Chip8::new(&program);
+    assert!(c8.profiler().is_none());
+    c8.set_profiler(Some(Profiler::new()));
+    c8.step(10).unwrap();
+
+    let profiler = c8.take_profiler().unwrap();
+    assert!(c8.profiler().is_none());
+    assert_eq!(profiler.total(), 10);
+    assert_eq!(profiler.count(0x20a), 2);
+    assert_eq!(profiler.count(0x204), 2);
+    assert_eq!(profiler.hotspots()[0], (0x204, 2));
+    assert_eq!(profiler.classes()["2nnn"], 3);
+    assert_eq!(profiler.classes()["00EE"], 3);
+
+    // 0x206 runs its own call and return and the two instructions of 0x20a
+    assert_eq!(profiler.subroutines()[&0x206], SubroutineStats { calls: 1, inclusive: 4, exclusive: 2 });
+    assert_eq!(profiler.subroutines()[&0x20a], SubroutineStats { calls: 2, inclusive: 4, exclusive: 4 });
+
+    let mut folded = String::new();
+    profiler.write_folded(&mut folded).unwrap();
+    assert_eq!(folded, "main 4\nmain;sub_0x206 2\nmain;sub_0x206;sub_0x20a 2\nmain;sub_0x20a 2\n");
+
+    let mut json = String::new();
+    profiler.write_json(&mut json).unwrap();
+    assert!(json.starts_with("{\"total\":10,\"addresses\":{\"0x200\":{\"count\":1,\"instr\":\"2206\"},"), "{json}");
+    assert!(json.contains("\"subroutines\":{\"0x206\":{\"calls\":1,\"inclusive\":4,\"exclusive\":2},"), "{json}");
+
+    let mut report = String::new();
+    profiler.write_report(&mut report).unwrap();
+    assert!(report.starts_with("10 instructions executed"));
+    assert!(report.contains("  0x204   1204            2   20.00"), "{report}");
+}
+
+#[test]
+fn test_disassemble() {
+    assert_eq!(disassemble(0x00e0), "CLS");
+    assert_eq!(disassemble(0x0123), "SYS 0x123");
+    assert_eq!(disassemble(0x6a2f), "LD VA, 0x2f");
+    assert_eq!(disassemble(0x8126), "SHR V1, V2");
+    assert_eq!(disassemble(0xd125), "DRW V1, V2, 5");
+    assert_eq!(disassemble(0xf333), "LD B, V3");
+    assert_eq!(disassemble(0xf065), "LD V0, [I]");
+    assert_eq!(disassemble(0x5121), "DW 0x5121");
+    assert_eq!(disassemble(0xe1ff), "DW 0xe1ff");
+}
+
+#[test]
+fn test_coverage() {
+    let program = [
+        0xa2, 0x0a,     // 0x200: i = 0x20a
+        0xd0, 0x02,     // 0x202: draw 2 rows
+        0x30, 0x01,     // 0x204: skip if v0 == 1
+        0x12, 0x04,     // 0x206: loop
+        0x00, 0xe0,     // 0x208: never runs
+        0xf0, 0x90,     // 0x20a: sprite
+        0x00,           // 0x20c: untouched
+    ];
+    let mut c8 = Chip8::new(&program);
+    c8.set_coverage(Some(Coverage::new()));
+    c8.step(5).unwrap();
+    let coverage = c8.take_coverage().unwrap();
+    assert!(c8.coverage().is_none());
+
+    assert!(coverage.is_instruction(0x206));
+    assert!(coverage.is_code(0x207) && !coverage.is_instruction(0x207));
+    assert!(!coverage.is_touched(0x208));
+    assert!(coverage.is_sprite(0x20b) && !coverage.is_code(0x20b));
+    assert_eq!(coverage.unexecuted_ranges(&program), [(0x208, 0x20d)]);
+
+    let mut report = String::new();
+    coverage.write_report(&program, &mut report).unwrap();
+    assert!(report.starts_with("0x200  A20A  X---  LD I, 0x20a\n"), "{report}");
+    assert!(report.contains("0x208  00E0  ----  CLS\n0x20a  F0    -S--  ####....\n0x20b  90    -S--  #..#....\n0x20c  00    ----  ........\n"), "{report}");
+    assert!(report.contains("     8 executed (61.5%)"), "{report}");
+    assert!(report.contains("0x208-0x20c      5 bytes  2 bytes used as data"), "{report}");
+
+    // Another run that stores registers merges in
+    let mut c8 = Chip8::new(&program);
+    c8.set_coverage(Some(Coverage::from_bytes(&coverage.to_bytes(&program), &program).unwrap()));
+    c8.bus_mut().load(0x204, &[0xf1, 0x55, 0x00, 0xe0]);
+    c8.step(4).unwrap();
+    let mut merged = c8.take_coverage().unwrap();
+    merged.merge(&coverage);
+    assert!(merged.is_written(0x20b) && merged.is_sprite(0x20b));
+    assert!(merged.is_instruction(0x206));
+    assert_eq!(Coverage::from_bytes(b"C8CV", &program), Err(CoverageError::BadFormat));
+
+    // Coverage of one ROM doesn't load for another
+    let mut other = program;
+    other[1] = 0x0c;
+    assert_eq!(Coverage::from_bytes(&coverage.to_bytes(&program), &other), Err(CoverageError::OtherProgram));
+    assert_eq!(Coverage::from_bytes(&coverage.to_bytes(&program), &program[..4]), Err(CoverageError::OtherProgram));
+    assert_eq!(&coverage.to_bytes(b"123456789")[9..13], 0xcbf4_3926u32.to_le_bytes());
+}
@@ -0,0 +1,186 @@
+// Golden image tests for the bundled ROMs. Each ROM runs for a number of frames with
+// scripted key presses, then its display is compared with a golden image in the text
+// format of `write_display`, stored in roms/golden. On a mismatch the actual display and a
+// diff are written to target/golden. Run with CHIP8_BLESS=1 to store the displays as the
+// new goldens.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+use crate::analyze;
+use crate::chip8::{write_display, Chip8, Fault};
+use crate::romdb::RomDatabase;
+
+const BLESS_VAR: &str = "CHIP8_BLESS";
+
+// Instructions per frame for ROMs the database has no speed for, as in the frontend
+const DEFAULT_SPEED: usize = 10;
+
+// A key pressed or released at the start of a frame
+#[derive(Clone, Copy, Debug)]
+struct Input {
+    frame: usize,
+    key: u8,
+    pressed: bool,
+}
+
+const fn press(frame: usize, key: u8) -> Input {
+    Input { frame, key, pressed: true }
+}
+
+const fn release(frame: usize, key: u8) -> Input {
+    Input { frame, key, pressed: false }
+}
+
+fn manifest_path(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+// Runs roms/`name`.rom like the frontend would, with the quirks and speed of the database and a
+// fixed seed for Cxnn. Stops early at a fault and returns it with its frame.
+fn run(name: &str, frames: usize, inputs: &[Input]) -> (Chip8<'static>, Option<(usize, Fault)>) {
+    let rom = fs::read(manifest_path(&format!("roms/{name}.rom"))).unwrap();
+    let mut c8 = Chip8::from_rom(rom);
+    c8.set_rng(SmallRng::seed_from_u64(0xc8));
+    let info = RomDatabase::bundled().identify(c8.program());
+    let quirks = match &info {
+        Some(info) => info.quirks,
+        None => analyze::analyze(c8.program()).suggest_quirks().quirks,
+    };
+    c8.set_quirks(quirks);
+    let speed = info.and_then(|info| info.tickrate).map_or(DEFAULT_SPEED, |t| t as usize);
+
+    for frame in 0..frames {
+        for input in inputs.iter().filter(|input| input.frame == frame) {
+            c8.set_key_state(input.key, input.pressed);
+        }
+        if let Err(fault) = c8.step(speed) {
+            return (c8, Some((frame, fault)));
+        }
+        c8.tick_60hz();
+    }
+    (c8, None)
+}
+
+// The display with the pixels only in `actual` as '+' and only in `golden` as '-'
+fn diff(actual: &str, golden: &str) -> (String, usize) {
+    let mut out = String::new();
+    let mut differences = 0;
+    for (actual, golden) in actual.lines().zip(golden.lines()) {
+        for (a, g) in actual.chars().zip(golden.chars()) {
+            let c = match (a, g) {
+                _ if a == g => a,
+                ('.', _) => '-',
+                _ => '+',
+            };
+            differences += (a != g) as usize;
+            out.push(c);
+        }
+        out.push('\n');
+    }
+    (out, differences)
+}
+
+fn assert_golden(name: &str, c8: &Chip8) {
+    let actual = display(c8);
+    let golden_path = manifest_path(&format!("roms/golden/{name}.txt"));
+    if env::var_os(BLESS_VAR).is_some() {
+        fs::create_dir_all(golden_path.parent().unwrap()).unwrap();
+        fs::write(&golden_path, &actual).unwrap();
+        return;
+    }
+
+    let Ok(golden) = fs::read_to_string(&golden_path) else {
+        panic!("No golden image {}, run with {BLESS_VAR}=1 to create it", golden_path.display());
+    };
+    if actual == golden {
+        return;
+    }
+    let (diff, differences) = diff(&actual, &golden);
+    let out_dir = manifest_path("target/golden");
+    fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{name}.txt"));
+    let diff_path = out_dir.join(format!("{name}.diff.txt"));
+    fs::write(&actual_path, &actual).unwrap();
+    fs::write(&diff_path, &diff).unwrap();
+
+    let mut message = String::new();
+    writeln!(message, "{name}: {differences} pixels differ from {}, + only in the actual display, - only in the golden image", golden_path.display()).unwrap();
+    writeln!(message, "Wrote {} and {}, run with {BLESS_VAR}=1 if the change is intended", actual_path.display(), diff_path.display()).unwrap();
+    message.push_str(&diff);
+    panic!("{message}");
+}
+
+fn display(c8: &Chip8) -> String {
+    let mut out = String::new();
+    write_display(c8, &mut out).unwrap();
+    out
+}
+
+// Runs the ROM and checks it doesn't fault or end up waiting for a key before comparing its
+// display. Every scripted key has to change the display, or it doesn't test anything.
+fn check(name: &str, frames: usize, inputs: &[Input]) {
+    let (c8, fault) = run(name, frames, inputs);
+    if let Some((frame, fault)) = fault {
+        panic!("{name} faulted in frame {frame}: {fault}");
+    }
+    assert!(!c8.is_waiting_for_key(), "{name} is waiting for a key after {frames} frames at {:#05x}", c8.state().pc);
+    let keys: BTreeSet<u8> = inputs.iter().map(|input| input.key).collect();
+    for key in keys {
+        let without: Vec<Input> = inputs.iter().copied().filter(|input| input.key != key).collect();
+        let (other, _) = run(name, frames, &without);
+        assert_ne!(display(&c8), display(&other), "{name}: key {key:X} doesn't change the display");
+    }
+    assert_golden(name, &c8);
+}
+
+#[test]
+fn test_golden_diff() {
+    let (diff, differences) = diff("x.x.\n..xx\n", "x..x\n..xx\n");
+    assert_eq!(diff, "x.+-\n..xx\n");
+    assert_eq!(differences, 2);
+}
+
+#[test]
+fn test_golden_airplane() {
+    check("airplane", 300, &[press(120, 0x8), release(125, 0x8)]);
+}
+
+#[test]
+fn test_golden_blinky() {
+    check("blinky", 900, &[press(620, 0x7), release(700, 0x7)]);
+}
+
+#[test]
+fn test_golden_breakout() {
+    // Key 0 serves the ball, the ROM waits for it again after a miss
+    check("breakout", 360, &[
+        press(60, 0x0), release(65, 0x0),
+        press(100, 0x4), release(160, 0x4),
+        press(240, 0x0), release(245, 0x0),
+    ]);
+}
+
+#[test]
+fn test_golden_particle() {
+    check("particle", 300, &[]);
+}
+
+#[test]
+fn test_golden_stars() {
+    check("stars", 300, &[]);
+}
+
+#[test]
+fn test_golden_trip8() {
+    check("trip8", 600, &[]);
+}
+
+
+
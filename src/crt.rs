@@ -0,0 +1,138 @@
+// Parameters for the CRT look of the display shader. The layout of `CrtParams`
+// matches the `Params` uniform struct in display_fs.wgsl.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    Green,
+    Amber,
+    White,
+    Lcd,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [Palette::Green, Palette::Amber, Palette::White, Palette::Lcd];
+
+    // (foreground, background)
+    pub fn colors(self) -> ([f32; 4], [f32; 4]) {
+        match self {
+            Palette::Green => ([0.1, 0.5, 0.1, 1.0], [0.0, 0.004, 0.002, 1.0]),
+            Palette::Amber => ([0.6, 0.35, 0.0, 1.0], [0.004, 0.002, 0.0, 1.0]),
+            Palette::White => ([0.7, 0.7, 0.75, 1.0], [0.003, 0.003, 0.004, 1.0]),
+            Palette::Lcd => ([0.06, 0.22, 0.06, 1.0], [0.55, 0.67, 0.06, 1.0]),
+        }
+    }
+
+    pub fn next(self) -> Palette {
+        let i = Palette::ALL.iter().position(|p| *p == self).unwrap();
+        Palette::ALL[(i + 1) % Palette::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrtParams {
+    pub foreground: [f32; 4],
+    pub background: [f32; 4],
+    pub distortion: f32,
+    pub scanline_density: f32,
+    pub scanline_intensity: f32,
+    pub rolling_bar: f32,
+    pub noise: f32,
+    pub vignette: f32,
+    pub enabled: bool,
+}
+
+impl CrtParams {
+    // Size of the uniform buffer, padded to 16 bytes
+    pub const UNIFORM_SIZE: usize = 64;
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        (self.foreground, self.background) = palette.colors();
+    }
+
+    pub fn to_uniform_bytes(self) -> [u8; CrtParams::UNIFORM_SIZE] {
+        let mut bytes = [0; CrtParams::UNIFORM_SIZE];
+        let words = self.foreground.iter()
+            .chain(self.background.iter())
+            .map(|f| f.to_bits())
+            .chain([
+                self.distortion.to_bits(),
+                self.scanline_density.to_bits(),
+                self.scanline_intensity.to_bits(),
+                self.enabled as u32,
+                self.rolling_bar.to_bits(),
+                self.noise.to_bits(),
+                self.vignette.to_bits()
+            ]);
+
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_ne_bytes());
+        }
+        bytes
+    }
+}
+
+impl Default for CrtParams {
+    fn default() -> Self {
+        let (foreground, background) = Palette::Green.colors();
+        CrtParams {
+            foreground,
+            background,
+            distortion: 0.15,
+            scanline_density: 600.0,
+            scanline_intensity: 1.0,
+            rolling_bar: 0.05,
+            noise: 0.04,
+            vignette: 0.5,
+            enabled: true,
+        }
+    }
+}
+
+// Contents of the `Frame` uniform struct shared by both display shaders
+pub fn frame_uniform_bytes(time: f32, delta_time: f32, width: f32, height: f32, pixel_perfect: bool) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    let words = [time.to_bits(), delta_time.to_bits(), width.to_bits(), height.to_bits(), pixel_perfect as u32];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    bytes
+}
+
+// CPU side phosphor persistence. Pixels that turn off fade out linearly over
+// `frames` updates instead of disappearing at once, which hides most of the
+// flicker caused by XOR sprite redraws.
+pub struct Phosphor {
+    frames: u32,
+    levels: Vec<u8>,
+}
+
+impl Phosphor {
+    pub const MAX_FRAMES: u32 = 30;
+
+    pub fn new(size: usize, frames: u32) -> Self {
+        Phosphor { frames: frames.min(Phosphor::MAX_FRAMES), levels: vec![0; size] }
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn set_frames(&mut self, frames: u32) {
+        self.frames = frames.min(Phosphor::MAX_FRAMES);
+    }
+
+    // Blends the new framebuffer into the persisted levels and returns them
+    pub fn update(&mut self, pixels: &[u8]) -> &[u8] {
+        let decay = if self.frames == 0 { 255 } else { 255u32.div_ceil(self.frames) as u8 };
+        for (level, px) in self.levels.iter_mut().zip(pixels) {
+            *level = if *px != 0 { 255 } else { level.saturating_sub(decay) };
+        }
+        &self.levels
+    }
+}
+
+#[cfg(test)]
+mod tests;
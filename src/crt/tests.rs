@@ -0,0 +1,77 @@
+use super::{frame_uniform_bytes, CrtParams, Palette, Phosphor};
+
+// The f32 or u32 at byte `offset` of a uniform buffer
+fn word(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn test_uniform_layout() {
+    let mut params = CrtParams {
+        distortion: 0.25,
+        scanline_density: 480.0,
+        scanline_intensity: 0.75,
+        rolling_bar: 0.125,
+        noise: 0.5,
+        vignette: 0.0625,
+        enabled: true,
+        ..CrtParams::default()
+    };
+    params.set_palette(Palette::Amber);
+    let bytes = params.to_uniform_bytes();
+    assert_eq!(bytes.len(), 64);
+
+    // Offsets of the fields of `Params` in display_fs.wgsl
+    let (foreground, background) = Palette::Amber.colors();
+    for i in 0..4 {
+        assert_eq!(f32::from_bits(word(&bytes, i * 4)), foreground[i]);
+        assert_eq!(f32::from_bits(word(&bytes, 16 + i * 4)), background[i]);
+    }
+    assert_eq!(f32::from_bits(word(&bytes, 32)), 0.25);
+    assert_eq!(f32::from_bits(word(&bytes, 36)), 480.0);
+    assert_eq!(f32::from_bits(word(&bytes, 40)), 0.75);
+    assert_eq!(word(&bytes, 44), 1);
+    assert_eq!(f32::from_bits(word(&bytes, 48)), 0.125);
+    assert_eq!(f32::from_bits(word(&bytes, 52)), 0.5);
+    assert_eq!(f32::from_bits(word(&bytes, 56)), 0.0625);
+    // Padding up to 16 bytes
+    assert_eq!(word(&bytes, 60), 0);
+
+    params.enabled = false;
+    assert_eq!(word(&params.to_uniform_bytes(), 44), 0);
+}
+
+#[test]
+fn test_frame_uniform_layout() {
+    // The fields of `Frame` in the display shaders
+    let bytes = frame_uniform_bytes(1.5, 0.25, 640.0, 320.0, true);
+    assert_eq!(f32::from_bits(word(&bytes, 0)), 1.5);
+    assert_eq!(f32::from_bits(word(&bytes, 4)), 0.25);
+    assert_eq!(f32::from_bits(word(&bytes, 8)), 640.0);
+    assert_eq!(f32::from_bits(word(&bytes, 12)), 320.0);
+    assert_eq!(word(&bytes, 16), 1);
+    assert!(bytes[20..].iter().all(|b| *b == 0));
+    assert_eq!(word(&frame_uniform_bytes(0.0, 0.0, 0.0, 0.0, false), 16), 0);
+}
+
+#[test]
+fn test_phosphor_decay() {
+    let mut phosphor = Phosphor::new(2, 4);
+    assert_eq!(phosphor.update(&[1, 0]), [255, 0]);
+    // Pixels that turn off fade out linearly over `frames` updates, lit pixels are at full level
+    assert_eq!(phosphor.update(&[0, 1]), [191, 255]);
+    assert_eq!(phosphor.update(&[0, 0]), [127, 191]);
+    assert_eq!(phosphor.update(&[0, 0]), [63, 127]);
+    assert_eq!(phosphor.update(&[0, 0]), [0, 63]);
+    assert_eq!(phosphor.update(&[1, 0]), [255, 0]);
+
+    // Without persistence pixels go out at once
+    phosphor.set_frames(0);
+    assert_eq!(phosphor.update(&[0, 0]), [0, 0]);
+
+    phosphor.set_frames(100);
+    assert_eq!(phosphor.frames(), Phosphor::MAX_FRAMES);
+    phosphor.update(&[1, 1]);
+    let updates = (1..).find(|_| phosphor.update(&[0, 0])[0] == 0).unwrap();
+    assert!(updates <= Phosphor::MAX_FRAMES);
+}
@@ -0,0 +1,582 @@
+// Debug Adapter Protocol server. Editors launch the emulator with --dap and
+// talk to it over stdin and stdout: JSON messages behind a Content-Length
+// header. The machine runs headless at 60 ticks per second.
+//
+// Breakpoints are set by address (instruction breakpoints, or function
+// breakpoints naming an address or label) and by source line when a symbol
+// map is present, see `symbols`. Stack frames come from the return addresses
+// on the CHIP-8 stack, the registers and the stack are the variables and
+// memory is readable through readMemory.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+use serde_json::{json, Value};
+
+use crate::chip8::{Breakpoints, Bus, Chip8, Fault};
+
+mod symbols;
+pub use symbols::{SourceLocation, SymbolMap};
+use symbols::parse_address;
+
+const THREAD_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+const STACK_REFERENCE: u64 = 2;
+const DEFAULT_SPEED: usize = 10;
+
+/// Reads one message, None at the end of the input
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Message without Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+/// Serves one client until it disconnects or closes `input`
+pub fn serve(input: impl Read + Send + 'static, mut output: impl Write) -> io::Result<()> {
+    // Messages are read on a thread of their own so they can arrive while the machine runs
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = DapSession::new();
+    let mut next_tick = Instant::now();
+    while !session.is_finished() {
+        let message = if session.is_running() {
+            match receiver.try_recv() {
+                Ok(message) => Some(message),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        } else {
+            match receiver.recv() {
+                Ok(message) => Some(message),
+                Err(_) => break,
+            }
+        };
+
+        let outgoing = match message {
+            Some(message) => session.handle(&message),
+            None => {
+                let outgoing = session.tick();
+                next_tick += Chip8::TICK;
+                match next_tick.checked_duration_since(Instant::now()) {
+                    Some(wait) => thread::sleep(wait),
+                    None => next_tick = Instant::now(),
+                }
+                outgoing
+            }
+        };
+        for message in outgoing {
+            write_message(&mut output, &message)?;
+        }
+        if !session.is_running() {
+            next_tick = Instant::now();
+        }
+    }
+    Ok(())
+}
+
+// How a step that may take more than one instruction ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    // Until a called subroutine returns to `pc` with the stack back at `sp`
+    Over { pc: usize, sp: usize },
+    // Until the current subroutine returns
+    Out { sp: usize },
+}
+
+/// The state of one debugging session, independent of the transport. Requests go in through
+/// [`DapSession::handle`], the machine advances one frame per [`DapSession::tick`] while it runs.
+pub struct DapSession {
+    seq: u64,
+    chip8: Option<Chip8<'static>>,
+    symbols: SymbolMap,
+    instructions_per_frame: usize,
+    stop_on_entry: bool,
+    source_breakpoints: BTreeMap<PathBuf, Vec<usize>>,
+    instruction_breakpoints: Vec<usize>,
+    function_breakpoints: Vec<usize>,
+    breakpoints: Breakpoints,
+    running: bool,
+    step: Option<Step>,
+    finished: bool,
+}
+
+impl DapSession {
+    pub fn new() -> Self {
+        DapSession {
+            seq: 0,
+            chip8: None,
+            symbols: SymbolMap::default(),
+            instructions_per_frame: DEFAULT_SPEED,
+            stop_on_entry: false,
+            source_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: Vec::new(),
+            function_breakpoints: Vec::new(),
+            breakpoints: Breakpoints::new(),
+            running: false,
+            step: None,
+            finished: false,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// True after a disconnect request
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn chip8(&self) -> Option<&Chip8<'static>> {
+        self.chip8.as_ref()
+    }
+
+    /// Answers a request, returns the response followed by any events
+    pub fn handle(&mut self, request: &Value) -> Vec<Value> {
+        let command = request["command"].as_str().unwrap_or_default().to_string();
+        let args = &request["arguments"];
+        let mut events = Vec::new();
+        let result = match command.as_str() {
+            "initialize" => {
+                events.push(self.event("initialized", json!({})));
+                Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsFunctionBreakpoints": true,
+                    "supportsInstructionBreakpoints": true,
+                    "supportsReadMemoryRequest": true,
+                    "supportsSetVariable": true,
+                    "supportsTerminateRequest": true,
+                }))
+            }
+            "launch" => self.launch(args),
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    events.push(self.stopped("entry", None));
+                } else {
+                    self.resume();
+                }
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => Ok(self.set_source_breakpoints(args)),
+            "setInstructionBreakpoints" => Ok(self.set_instruction_breakpoints(args)),
+            "setFunctionBreakpoints" => Ok(self.set_function_breakpoints(args)),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => self.machine().map(|chip8| self.stack_trace(chip8)),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                { "name": "Stack", "variablesReference": STACK_REFERENCE, "expensive": false },
+            ] })),
+            "variables" => self.machine().map(|chip8| variables(chip8, args["variablesReference"].as_u64().unwrap_or(0))),
+            "setVariable" => self.set_variable(args),
+            "readMemory" => self.machine().and_then(|chip8| read_memory(chip8, args)),
+            "continue" => {
+                self.resume();
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" | "stepIn" | "stepOut" => self.start_step(&command, &mut events),
+            "pause" => {
+                if self.running {
+                    events.push(self.stopped("pause", None));
+                }
+                Ok(Value::Null)
+            }
+            "disconnect" | "terminate" => {
+                self.finished = true;
+                self.running = false;
+                if command == "terminate" {
+                    events.push(self.event("terminated", json!({})));
+                }
+                Ok(Value::Null)
+            }
+            _ => Err(format!("Unsupported request {command}")),
+        };
+
+        let response = self.response(request, &command, result);
+        self.number(std::iter::once(response).chain(events).collect())
+    }
+
+    /// Runs one 60Hz frame while the machine runs, returns the stopped event if it stopped
+    pub fn tick(&mut self) -> Vec<Value> {
+        let stopped = self.run_frame().into_iter().collect();
+        self.number(stopped)
+    }
+
+    fn run_frame(&mut self) -> Option<Value> {
+        if !self.running {
+            return None;
+        }
+        let chip8 = self.chip8.as_mut()?;
+        chip8.tick_60hz();
+
+        for _ in 0..self.instructions_per_frame {
+            if self.breakpoints.hit(chip8) {
+                return Some(self.stopped("breakpoint", None));
+            }
+            match self.breakpoints.step(chip8) {
+                Ok(true) => (),
+                // Waiting for a key or the next tick
+                Ok(false) => break,
+                Err(fault) => return Some(self.stopped("exception", Some(fault))),
+            }
+
+            let after = chip8.state();
+            let done = match self.step {
+                Some(Step::Over { pc, sp }) => after.pc == pc && after.sp == sp,
+                Some(Step::Out { sp }) => after.sp < sp,
+                None => false,
+            };
+            if done {
+                return Some(self.stopped("step", None));
+            }
+        }
+        None
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let program = args["program"].as_str().ok_or("launch needs a program")?;
+        let program = Path::new(program);
+        let rom = fs::read(program).map_err(|e| format!("Couldn't read {}: {e}", program.display()))?;
+        Chip8::check_program_size(rom.len()).map_err(|e| format!("{}: {e}", program.display()))?;
+
+        // The symbol map is the given file or one named like the ROM with a .sym extension
+        let symbols = match args["symbols"].as_str() {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(program.with_extension("sym")).filter(|path| path.exists()),
+        };
+        self.symbols = match symbols {
+            Some(path) => SymbolMap::load(&path).map_err(|e| format!("Couldn't load symbols: {e}"))?,
+            None => SymbolMap::default(),
+        };
+
+        #[allow(unused_mut)]
+        let mut chip8 = Chip8::from_rom(rom);
+        #[allow(unused_mut)]
+        let mut tickrate = None;
+        // Known ROMs run with their quirks and speed
+        #[cfg(feature = "romdb")]
+        if let Some(info) = crate::romdb::RomDatabase::bundled().identify(chip8.program()) {
+            chip8.set_quirks(info.quirks);
+            tickrate = info.tickrate.map(|t| t as usize);
+        }
+
+        self.instructions_per_frame = args["instructionsPerFrame"].as_u64().map(|n| n as usize).or(tickrate).unwrap_or(DEFAULT_SPEED);
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.chip8 = Some(chip8);
+        Ok(Value::Null)
+    }
+
+    fn machine(&self) -> Result<&Chip8<'static>, String> {
+        self.chip8.as_ref().ok_or_else(|| "No program is running".to_string())
+    }
+
+    fn resume(&mut self) {
+        self.running = self.chip8.is_some();
+        self.breakpoints.resume();
+        self.step = None;
+    }
+
+    fn start_step(&mut self, command: &str, events: &mut Vec<Value>) -> Result<Value, String> {
+        let chip8 = self.chip8.as_mut().ok_or("No program is running")?;
+        let state = chip8.state();
+        let is_call = chip8.bus().peek(state.pc) >> 4 == 0x2;
+        let step = match command {
+            "next" if is_call => Some(Step::Over { pc: state.pc + 2, sp: state.sp }),
+            "stepOut" if state.sp > 0 => Some(Step::Out { sp: state.sp }),
+            _ => None,
+        };
+
+        match step {
+            // Runs in ticks like continue and stops when the step is done
+            Some(step) => {
+                self.resume();
+                self.step = Some(step);
+            }
+            None => {
+                let fault = chip8.step_one().err();
+                let reason = if fault.is_some() { "exception" } else { "step" };
+                events.push(self.stopped(reason, fault));
+            }
+        }
+        Ok(Value::Null)
+    }
+
+    fn set_source_breakpoints(&mut self, args: &Value) -> Value {
+        let path = PathBuf::from(args["source"]["path"].as_str().unwrap_or_default());
+        let mut addresses = Vec::new();
+        let mut breakpoints = Vec::new();
+        for bp in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = bp["line"].as_u64().unwrap_or(0) as u32;
+            match self.symbols.address_of_line(&path, line) {
+                Some(addr) => {
+                    addresses.push(addr);
+                    breakpoints.push(json!({ "verified": true, "line": line, "instructionReference": format_address(addr) }));
+                }
+                None => breakpoints.push(json!({ "verified": false, "line": line, "message": "No code at this line in the symbol map" })),
+            }
+        }
+        self.source_breakpoints.insert(path, addresses);
+        self.update_breakpoints();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Value {
+        let mut breakpoints = Vec::new();
+        self.instruction_breakpoints.clear();
+        for bp in args["breakpoints"].as_array().into_iter().flatten() {
+            let addr = bp["instructionReference"].as_str().and_then(parse_address)
+                .map(|addr| addr as i64 + bp["offset"].as_i64().unwrap_or(0))
+                .filter(|addr| (0..Chip8::MEM_SIZE as i64).contains(addr))
+                .map(|addr| addr as usize);
+            breakpoints.push(self.address_breakpoint(addr));
+            self.instruction_breakpoints.extend(addr);
+        }
+        self.update_breakpoints();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    // Function breakpoints name a label of the symbol map or an address
+    fn set_function_breakpoints(&mut self, args: &Value) -> Value {
+        let mut breakpoints = Vec::new();
+        self.function_breakpoints.clear();
+        for bp in args["breakpoints"].as_array().into_iter().flatten() {
+            let name = bp["name"].as_str().unwrap_or_default();
+            let addr = self.symbols.address_of(name).or_else(|| parse_address(name)).filter(|addr| *addr < Chip8::MEM_SIZE);
+            breakpoints.push(self.address_breakpoint(addr));
+            self.function_breakpoints.extend(addr);
+        }
+        self.update_breakpoints();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn address_breakpoint(&self, addr: Option<usize>) -> Value {
+        match addr {
+            Some(addr) => {
+                let mut bp = json!({ "verified": true, "instructionReference": format_address(addr) });
+                if let Some(loc) = self.symbols.location(addr) {
+                    bp["line"] = json!(loc.line);
+                    bp["source"] = source(loc);
+                }
+                bp
+            }
+            None => json!({ "verified": false, "message": "Not an address in memory" }),
+        }
+    }
+
+    fn update_breakpoints(&mut self) {
+        self.breakpoints.set(self.source_breakpoints.values().flatten()
+            .chain(&self.instruction_breakpoints)
+            .chain(&self.function_breakpoints)
+            .copied());
+    }
+
+    // The innermost frame is at pc, each return address on the stack adds the frame of its call
+    fn stack_trace(&self, chip8: &Chip8<'static>) -> Value {
+        let state = chip8.state();
+        let mut addresses = vec![state.pc];
+        addresses.extend(state.stack[..state.sp.min(state.stack.len())].iter().rev().map(|ret| ret.saturating_sub(2)));
+
+        let frames: Vec<Value> = addresses.iter().enumerate().map(|(id, &addr)| {
+            let name = match self.symbols.enclosing_label(addr) {
+                Some((start, label)) if start == addr => label.to_string(),
+                Some((start, label)) => format!("{label}+{:#x}", addr - start),
+                None => format_address(addr),
+            };
+            let mut frame = json!({
+                "id": id,
+                "name": name,
+                "line": 0,
+                "column": 0,
+                "instructionPointerReference": format_address(addr),
+            });
+            if let Some(loc) = self.symbols.location(addr) {
+                frame["line"] = json!(loc.line);
+                frame["source"] = source(loc);
+            }
+            frame
+        }).collect();
+        json!({ "stackFrames": frames, "totalFrames": addresses.len() })
+    }
+
+    fn set_variable(&mut self, args: &Value) -> Result<Value, String> {
+        let chip8 = self.chip8.as_mut().ok_or("No program is running")?;
+        let name = args["name"].as_str().unwrap_or_default();
+        let value = args["value"].as_str().unwrap_or_default().trim();
+        let value = match value.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16),
+            None => value.parse::<usize>(),
+        }.map_err(|_| format!("{value} is not a number"))?;
+
+        let mut state = chip8.state();
+        match (args["variablesReference"].as_u64(), name) {
+            (Some(REGISTERS_REFERENCE), "I") => state.i = value & 0xfff,
+            (Some(REGISTERS_REFERENCE), "PC") => state.pc = value & 0xfff,
+            (Some(REGISTERS_REFERENCE), "DT") => state.delay_timer = value as u8,
+            (Some(REGISTERS_REFERENCE), "ST") => state.sound_timer = value as u8,
+            (Some(REGISTERS_REFERENCE), name) if name.len() == 2 && name.starts_with('V') => {
+                let n = usize::from_str_radix(&name[1..], 16).map_err(|_| format!("No register {name}"))?;
+                state.reg[n] = value as u8;
+            }
+            _ => return Err(format!("{name} can't be changed")),
+        }
+        chip8.set_state(&state);
+        let variables = variables(chip8, REGISTERS_REFERENCE);
+        let changed = variables["variables"].as_array().into_iter().flatten().find(|v| v["name"] == name).cloned();
+        Ok(json!({ "value": changed.map_or(Value::Null, |v| v["value"].clone()) }))
+    }
+
+    fn stopped(&mut self, reason: &str, fault: Option<Fault>) -> Value {
+        self.running = false;
+        self.step = None;
+        let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Some(fault) = fault {
+            body["description"] = json!("Fault");
+            body["text"] = json!(fault.to_string());
+        }
+        self.event("stopped", body)
+    }
+
+    // Messages are numbered in the order they are sent
+    fn number(&mut self, mut messages: Vec<Value>) -> Vec<Value> {
+        for message in &mut messages {
+            self.seq += 1;
+            message["seq"] = json!(self.seq);
+        }
+        messages
+    }
+
+    fn event(&self, event: &str, body: Value) -> Value {
+        json!({ "type": "event", "event": event, "body": body })
+    }
+
+    fn response(&self, request: &Value, command: &str, result: Result<Value, String>) -> Value {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => (),
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        response
+    }
+}
+
+impl Default for DapSession {
+    fn default() -> Self {
+        DapSession::new()
+    }
+}
+
+fn format_address(addr: usize) -> String {
+    format!("{addr:#05x}")
+}
+
+fn source(loc: &SourceLocation) -> Value {
+    let name = loc.path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    json!({ "name": name, "path": loc.path.to_string_lossy() })
+}
+
+fn variables(chip8: &Chip8<'static>, reference: u64) -> Value {
+    let state = chip8.state();
+    let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+    let mut variables = Vec::new();
+    match reference {
+        REGISTERS_REFERENCE => {
+            for (n, value) in state.reg.iter().enumerate() {
+                variables.push(variable(format!("V{n:X}"), format!("{value:#04x}")));
+            }
+            // Addresses can be opened in a memory view
+            for (name, addr) in [("I", state.i), ("PC", state.pc)] {
+                let mut v = variable(name.to_string(), format_address(addr));
+                v["memoryReference"] = json!(format_address(addr));
+                variables.push(v);
+            }
+            variables.push(variable("SP".to_string(), state.sp.to_string()));
+            variables.push(variable("DT".to_string(), state.delay_timer.to_string()));
+            variables.push(variable("ST".to_string(), state.sound_timer.to_string()));
+        }
+        STACK_REFERENCE => {
+            for (n, ret) in state.stack[..state.sp.min(state.stack.len())].iter().enumerate() {
+                variables.push(variable(format!("[{n}]"), format_address(*ret)));
+            }
+        }
+        _ => (),
+    }
+    json!({ "variables": variables })
+}
+
+fn read_memory(chip8: &Chip8<'static>, args: &Value) -> Result<Value, String> {
+    let base = args["memoryReference"].as_str().and_then(parse_address).ok_or("Invalid memory reference")?;
+    let start = base as i64 + args["offset"].as_i64().unwrap_or(0);
+    let count = args["count"].as_u64().unwrap_or(0) as usize;
+    if !(0..Chip8::MEM_SIZE as i64).contains(&start) {
+        return Ok(json!({ "address": format_address(start.max(0) as usize), "unreadableBytes": count }));
+    }
+    let start = start as usize;
+    let end = start.saturating_add(count).min(Chip8::MEM_SIZE);
+    let bytes: Vec<u8> = (start..end).map(|addr| chip8.bus().peek(addr)).collect();
+    Ok(json!({
+        "address": format_address(start),
+        "data": base64(&bytes),
+        "unreadableBytes": count - bytes.len(),
+    }))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,97 @@
+// Symbol maps tie addresses to labels and source lines. The format is plain
+// text, one address per line followed by a source location, a label or both:
+//
+//     # comments and blank lines are ignored
+//     0x200 game.8o:3 main
+//     0x206 game.8o:5
+//     0x2a4 draw_player
+//
+// Source paths are relative to the symbol map.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A source line an address was assembled from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub path: PathBuf,
+    pub line: u32,
+}
+
+/// Labels and source lines by address
+#[derive(Clone, Debug, Default)]
+pub struct SymbolMap {
+    labels: BTreeMap<usize, String>,
+    locations: BTreeMap<usize, SourceLocation>,
+}
+
+impl SymbolMap {
+    /// Reads a symbol map, source paths are resolved against its directory
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        SymbolMap::parse(&text, dir).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", path.display())))
+    }
+
+    /// Parses the text of a symbol map, `dir` is prepended to relative source paths
+    pub fn parse(text: &str, dir: &Path) -> Result<Self, String> {
+        let mut map = SymbolMap::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let addr = fields.next().and_then(parse_address).ok_or_else(|| format!("line {}: expected an address", n + 1))?;
+            for field in fields {
+                match field.rsplit_once(':').and_then(|(path, line)| Some((path, line.parse::<u32>().ok()?))) {
+                    Some((path, line)) => {
+                        map.locations.insert(addr, SourceLocation { path: dir.join(path), line });
+                    }
+                    None => {
+                        map.labels.insert(addr, field.to_string());
+                    }
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.labels.get(&addr).map(|s| s.as_str())
+    }
+
+    pub fn address_of(&self, label: &str) -> Option<usize> {
+        self.labels.iter().find(|(_, l)| *l == label).map(|(addr, _)| *addr)
+    }
+
+    /// The closest label at or before `addr`, i.e. the routine `addr` is part of
+    pub fn enclosing_label(&self, addr: usize) -> Option<(usize, &str)> {
+        self.labels.range(..=addr).next_back().map(|(a, l)| (*a, l.as_str()))
+    }
+
+    /// The source line of the closest address at or before `addr` that has one
+    pub fn location(&self, addr: usize) -> Option<&SourceLocation> {
+        self.locations.range(..=addr).next_back().map(|(_, loc)| loc)
+    }
+
+    /// First address assembled from `line` of `source`. `source` may be absolute, it matches
+    /// when it ends with the path in the map.
+    pub fn address_of_line(&self, source: &Path, line: u32) -> Option<usize> {
+        self.locations.iter()
+            .find(|(_, loc)| loc.line == line && same_file(source, &loc.path))
+            .map(|(addr, _)| *addr)
+    }
+}
+
+fn same_file(source: &Path, path: &Path) -> bool {
+    source == path || source.ends_with(path) || path.ends_with(source)
+}
+
+// "0x2a4", "$2a4" or "2a4"
+pub(crate) fn parse_address(s: &str) -> Option<usize> {
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix('$')).unwrap_or(s);
+    usize::from_str_radix(hex, 16).ok()
+}
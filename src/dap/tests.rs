@@ -0,0 +1,219 @@
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use super::{base64, read_message, write_message, DapSession, SymbolMap};
+
+const PROGRAM: &[u8] = &[
+    0x60, 0x05,     // 0x200: v0 = 5
+    0x22, 0x08,     // 0x202: call 0x208
+    0x12, 0x04,     // 0x204: loop forever
+    0x00, 0x00,
+    0x70, 0x01,     // 0x208: v0 += 1
+    0x00, 0xee,     // 0x20a: return
+];
+
+const SYMBOLS: &str = "\
+# game.8o
+0x200 game.8o:1 main
+0x202 game.8o:2
+0x204 game.8o:3 loop
+0x208 game.8o:5 sub
+0x20a game.8o:6
+";
+
+// A ROM and its symbol map in a directory of their own
+fn write_rom(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chip8-dap-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("game.ch8"), PROGRAM).unwrap();
+    fs::write(dir.join("game.sym"), SYMBOLS).unwrap();
+    dir
+}
+
+struct Client {
+    session: DapSession,
+    seq: u64,
+}
+
+impl Client {
+    fn new() -> Self {
+        Client { session: DapSession::new(), seq: 0 }
+    }
+
+    // The response to the request and the events that followed it
+    fn request(&mut self, command: &str, arguments: Value) -> (Value, Vec<Value>) {
+        self.seq += 1;
+        let mut messages = self.session.handle(&json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments }));
+        let response = messages.remove(0);
+        assert_eq!(response["type"], "response");
+        assert_eq!(response["request_seq"], self.seq);
+        assert_eq!(response["command"], command);
+        (response, messages)
+    }
+
+    fn body(&mut self, command: &str, arguments: Value) -> Value {
+        let (response, _) = self.request(command, arguments);
+        assert_eq!(response["success"], true, "{response}");
+        response["body"].clone()
+    }
+
+    // Runs frames until the machine stops
+    fn stopped(&mut self) -> Value {
+        for _ in 0..100 {
+            if let Some(event) = self.session.tick().pop() {
+                assert_eq!(event["event"], "stopped");
+                assert!(!self.session.is_running());
+                return event["body"].clone();
+            }
+        }
+        panic!("the machine didn't stop");
+    }
+
+    fn register(&mut self, name: &str) -> String {
+        let variables = self.body("variables", json!({ "variablesReference": 1 }));
+        let variable = variables["variables"].as_array().unwrap().iter().find(|v| v["name"] == name).unwrap();
+        variable["value"].as_str().unwrap().to_string()
+    }
+
+    fn launch(&mut self, dir: &Path, stop_on_entry: bool) {
+        let (response, events) = self.request("initialize", json!({ "adapterID": "chip8" }));
+        assert_eq!(response["body"]["supportsInstructionBreakpoints"], true);
+        assert_eq!(events[0]["event"], "initialized");
+        assert_eq!(events[0]["seq"], response["seq"].as_u64().unwrap() + 1);
+        let program = dir.join("game.ch8");
+        self.body("launch", json!({ "program": program, "stopOnEntry": stop_on_entry }));
+    }
+}
+
+#[test]
+fn test_dap_session() {
+    let dir = write_rom("session");
+    let mut client = Client::new();
+    client.launch(&dir, true);
+
+    // Line 4 is empty, the others map to addresses
+    let source = dir.join("game.8o");
+    let breakpoints = client.body("setBreakpoints", json!({ "source": { "path": source }, "breakpoints": [{ "line": 5 }, { "line": 4 }] }));
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    assert_eq!(breakpoints["breakpoints"][0]["instructionReference"], "0x208");
+    assert_eq!(breakpoints["breakpoints"][1]["verified"], false);
+
+    let (_, events) = client.request("configurationDone", json!({}));
+    assert_eq!(events[0]["body"]["reason"], "entry");
+    assert_eq!(client.register("PC"), "0x200");
+
+    // A plain instruction steps at once
+    let (_, events) = client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(events[0]["body"]["reason"], "step");
+    assert_eq!(client.register("PC"), "0x202");
+    assert_eq!(client.register("V0"), "0x05");
+
+    // Stepping over the call stops at the breakpoint inside it
+    let (_, events) = client.request("next", json!({ "threadId": 1 }));
+    assert!(events.is_empty());
+    assert!(client.session.is_running());
+    assert_eq!(client.stopped()["reason"], "breakpoint");
+
+    let trace = client.body("stackTrace", json!({ "threadId": 1 }));
+    let frames = trace["stackFrames"].as_array().unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0]["name"], "sub");
+    assert_eq!(frames[0]["line"], 5);
+    assert_eq!(frames[0]["source"]["path"], source.to_str().unwrap());
+    assert_eq!(frames[1]["name"], "main+0x2");
+    assert_eq!(frames[1]["instructionPointerReference"], "0x202");
+    assert_eq!(frames[1]["line"], 2);
+
+    let stack = client.body("variables", json!({ "variablesReference": 2 }));
+    assert_eq!(stack["variables"], json!([{ "name": "[0]", "value": "0x204", "variablesReference": 0 }]));
+
+    client.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.register("V0"), "0x06");
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped()["reason"], "step");
+    assert_eq!(client.register("PC"), "0x204");
+    assert_eq!(client.register("SP"), "0");
+
+    // Registers and memory
+    let value = client.body("setVariable", json!({ "variablesReference": 1, "name": "VA", "value": "0x2a" }));
+    assert_eq!(value["value"], "0x2a");
+    assert_eq!(client.session.chip8().unwrap().state().reg[0xa], 0x2a);
+    let memory = client.body("readMemory", json!({ "memoryReference": "0x200", "count": 4 }));
+    assert_eq!(memory["data"], "YAUiCA==");
+    assert_eq!(memory["unreadableBytes"], 0);
+    let memory = client.body("readMemory", json!({ "memoryReference": "0xffe", "count": 4 }));
+    assert_eq!(memory["unreadableBytes"], 2);
+
+    let (response, _) = client.request("evaluate", json!({ "expression": "v0" }));
+    assert_eq!(response["success"], false);
+
+    client.request("disconnect", json!({}));
+    assert!(client.session.is_finished());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_dap_address_breakpoints() {
+    let dir = write_rom("address");
+    let mut client = Client::new();
+    client.launch(&dir, false);
+
+    let breakpoints = client.body("setFunctionBreakpoints", json!({ "breakpoints": [{ "name": "loop" }, { "name": "nowhere" }] }));
+    assert_eq!(breakpoints["breakpoints"][0]["instructionReference"], "0x204");
+    assert_eq!(breakpoints["breakpoints"][1]["verified"], false);
+    let breakpoints = client.body("setInstructionBreakpoints", json!({ "breakpoints": [{ "instructionReference": "0x208", "offset": 2 }] }));
+    assert_eq!(breakpoints["breakpoints"][0]["line"], 6);
+
+    client.request("configurationDone", json!({}));
+    assert!(client.session.is_running());
+    assert_eq!(client.stopped()["reason"], "breakpoint");
+    assert_eq!(client.register("PC"), "0x20a");
+
+    // Continuing runs the instruction at the breakpoint before stopping at the next
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped()["reason"], "breakpoint");
+    assert_eq!(client.register("PC"), "0x204");
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped()["reason"], "breakpoint");
+    assert_eq!(client.register("PC"), "0x204");
+
+    // Faults stop the machine with the reason
+    client.body("setVariable", json!({ "variablesReference": 1, "name": "PC", "value": "0x206" }));
+    client.request("continue", json!({ "threadId": 1 }));
+    let stopped = client.stopped();
+    assert_eq!(stopped["reason"], "exception");
+    assert!(stopped["text"].as_str().unwrap().contains("0x206"), "{stopped}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_symbol_map() {
+    let map = SymbolMap::parse(SYMBOLS, Path::new("roms")).unwrap();
+    assert_eq!(map.label(0x208), Some("sub"));
+    assert_eq!(map.address_of("loop"), Some(0x204));
+    assert_eq!(map.enclosing_label(0x20a), Some((0x208, "sub")));
+    assert_eq!(map.location(0x206).unwrap().line, 3);
+    assert_eq!(map.address_of_line(Path::new("/home/me/roms/game.8o"), 6), Some(0x20a));
+    assert_eq!(map.address_of_line(Path::new("other.8o"), 6), None);
+    assert!(SymbolMap::parse("main 0x200", Path::new("")).is_err());
+}
+
+#[test]
+fn test_message_framing() {
+    let mut out = Vec::new();
+    write_message(&mut out, &json!({ "seq": 1, "type": "event", "event": "initialized" })).unwrap();
+    write_message(&mut out, &json!({ "seq": 2, "type": "event", "event": "terminated" })).unwrap();
+    assert!(out.starts_with(b"Content-Length: 46\r\n\r\n{"));
+
+    let mut reader = BufReader::new(&out[..]);
+    assert_eq!(read_message(&mut reader).unwrap().unwrap()["event"], "initialized");
+    assert_eq!(read_message(&mut reader).unwrap().unwrap()["seq"], 2);
+    assert!(read_message(&mut reader).unwrap().is_none());
+    assert!(read_message(&mut BufReader::new(&b"Content-Type: json\r\n\r\n{}"[..])).is_err());
+
+    assert_eq!(base64(b"chip8"), "Y2hpcDg=");
+    assert_eq!(base64(b""), "");
+}
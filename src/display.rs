@@ -6,19 +6,56 @@ use wgpu::{
     RenderPassColorAttachment, PipelineLayoutDescriptor, BindGroupLayoutDescriptor,
     BindGroupLayoutEntry, SamplerBindingType, ShaderStages, TextureSampleType, BindGroup,
     BindGroupEntry, SamplerDescriptor, FilterMode, BindingResource, util::DeviceExt,
-    TextureDescriptor, TextureUsages, TextureViewDescriptor, Extent3d
+    TextureDescriptor, TextureUsages, TextureViewDescriptor, Extent3d, BufferUsages,
+    BufferBindingType, util::BufferInitDescriptor, BufferDescriptor, ImageCopyBuffer
 };
 
 use std::num::NonZeroU32;
-use crate::chip8::Chip8;
-use winit::{dpi::LogicalSize, platform::macos::WindowBuilderExtMacOS};
+use std::time::Instant;
+use crate::chip8::{Chip8, Framebuffer};
+use crate::crt::{frame_uniform_bytes, CrtParams, Phosphor};
+use crate::menu::{MENU_HEIGHT, MENU_WIDTH};
+use crate::osd::{Osd, Panel};
+use crate::stats::FrameStats;
+use crate::text::TextCanvas;
+use winit::{dpi::{LogicalSize, PhysicalSize}, window::Fullscreen, platform::macos::WindowBuilderExtMacOS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleMode {
+    // CRT inside the bezel, scaled to fit the window keeping the bezel's aspect ratio
+    Bezel,
+    // Bare framebuffer at the largest integer scale that fits the window
+    IntegerScale,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Viewport {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
 
 pub struct Chip8Display {
     pipeline: wgpu::RenderPipeline,
     context: gpu::RenderContext,
     bind_group: BindGroup,
     backing_texture: wgpu::Texture,
+    crt_params: CrtParams,
+    crt_params_buffer: wgpu::Buffer,
+    phosphor: Phosphor,
+    pixels: Vec<u8>,
+    frame_buffer: wgpu::Buffer,
+    menu_texture: wgpu::Texture,
+    osd: Osd,
+    stats: FrameStats,
+    show_stats: bool,
+    start_time: Instant,
     time: f32,
+    delta_time: f32,
+    scale_mode: ScaleMode,
+    overlay_size: (u32, u32),
+    viewport: Viewport,
     window: winit::window::Window,
 }
 
@@ -55,6 +92,7 @@ impl Chip8Display {
             write_mask: ColorWrites::default()
         };
 
+        let (width, height) = (overlay.width(), overlay.height());
         let overlay_texture = device.create_texture_with_data(queue, &TextureDescriptor {
             label: Some("Overlay texture"),
             size: Extent3d { width, height, depth_or_array_layers: 1 },
@@ -99,6 +137,36 @@ impl Chip8Display {
                         multisampled: false
                     },
                     visibility: ShaderStages::FRAGMENT
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    visibility: ShaderStages::FRAGMENT
+                },
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT
+                },
+                BindGroupLayoutEntry {
+                    binding: 6,
+                    count: None,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    visibility: ShaderStages::FRAGMENT
                 }
             ]
         });
@@ -129,6 +197,36 @@ impl Chip8Display {
             pixels.as_ref()
         );
 
+        let crt_params = CrtParams::default();
+        let crt_params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("CRT parameters"),
+            contents: &crt_params.to_uniform_bytes(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        });
+
+        let scale_mode = ScaleMode::Bezel;
+        let overlay_size = (width, height);
+        let viewport = fit_viewport(window.inner_size(), scale_mode, overlay_size);
+        let frame_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Frame uniforms"),
+            contents: &frame_uniform_bytes(0.0, 0.0, viewport.width, viewport.height, false),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        });
+
+        let menu_texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: Some("Menu texture"),
+                size: wgpu::Extent3d { width: MENU_WIDTH as u32, height: MENU_HEIGHT as u32, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING
+            },
+            &vec![0; MENU_WIDTH * MENU_HEIGHT]
+        );
+
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
             layout: &bind_group_layout,
@@ -148,8 +246,19 @@ impl Chip8Display {
                 BindGroupEntry {
                     binding: 3,
                     resource: BindingResource::TextureView(&overlay_texture.create_view(&TextureViewDescriptor::default()))
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: crt_params_buffer.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: frame_buffer.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: BindingResource::TextureView(&menu_texture.create_view(&TextureViewDescriptor::default()))
                 }
-
             ]
         });
 
@@ -169,13 +278,29 @@ impl Chip8Display {
             fragment: Some(wgpu::FragmentState { module: &fs, entry_point: "main", targets: &[Some(target_state)] }),
         });
 
+        let osd = Osd::new(device, queue, context.surface_format);
+
         Chip8Display {
             pipeline,
             bind_group,
             backing_texture,
+            crt_params,
+            crt_params_buffer,
+            phosphor: Phosphor::new(Chip8::DISPLAY_SIZE, 0),
+            pixels: vec![0; Chip8::DISPLAY_SIZE],
+            frame_buffer,
+            menu_texture,
+            osd,
+            stats: FrameStats::new(),
+            show_stats: false,
             context,
             window,
-            time: 0.0
+            start_time: Instant::now(),
+            time: 0.0,
+            delta_time: 0.0,
+            scale_mode,
+            overlay_size,
+            viewport
         }
 
     }
@@ -184,27 +309,128 @@ impl Chip8Display {
         return &self.window;
     }
 
-    pub fn update(&mut self, pixels: &[u8]) {
-        let context = &self.context;
-        let current_surface = context.surface.get_current_texture().unwrap();
-        let current_texture_view = current_surface.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.context.resize(size.width, size.height);
+        self.viewport = fit_viewport(size, self.scale_mode, self.overlay_size);
+    }
 
-        let color_attachment = RenderPassColorAttachment {
-            view: &current_texture_view,
-            resolve_target: None,
-            ops: gpu::LOAD_STORE_TRANSPARENT_OPS
+    pub fn scale_mode(&self) -> ScaleMode {
+        self.scale_mode
+    }
+
+    pub fn set_scale_mode(&mut self, mode: ScaleMode) {
+        self.scale_mode = mode;
+        self.viewport = fit_viewport(self.window.inner_size(), mode, self.overlay_size);
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        let fullscreen = match self.window.fullscreen() {
+            Some(_) => None,
+            None => Some(Fullscreen::Borderless(None))
         };
+        self.window.set_fullscreen(fullscreen);
+    }
 
-        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    pub fn crt_params(&self) -> CrtParams {
+        self.crt_params
+    }
+
+    pub fn set_crt_params(&mut self, params: CrtParams) {
+        self.crt_params = params;
+        self.context.queue.write_buffer(&self.crt_params_buffer, 0, &params.to_uniform_bytes());
+    }
+
+    pub fn persistence_frames(&self) -> u32 {
+        self.phosphor.frames()
+    }
+
+    // Number of frames it takes for a pixel that turned off to fade out, 0 disables persistence
+    pub fn set_persistence_frames(&mut self, frames: u32) {
+        self.phosphor.set_frames(frames);
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
 
-        context.queue.write_texture(
+    pub fn show_stats(&self) -> bool {
+        self.show_stats
+    }
+
+    pub fn set_show_stats(&mut self, show: bool) {
+        self.show_stats = show;
+        self.update_hud();
+    }
+
+    fn update_hud(&mut self) {
+        let mut lines = Vec::new();
+        if self.show_stats {
+            let stats = &self.stats;
+            lines = vec![
+                format!("FPS {:.1}", stats.fps),
+                format!("FRAME {:.2} ms", stats.frame_time_ms),
+                format!("IPF {:.1}", stats.instructions_per_frame),
+                format!("IPS {:.0}", stats.instructions_per_second),
+            ];
+        }
+        self.osd.set_hud(lines);
+    }
+
+    // Shows `text` in the corner of the window for a few seconds, e.g. "State saved"
+    pub fn show_message(&mut self, text: &str) {
+        self.osd.show_message(text);
+    }
+
+    // Shows `panel` in the top right corner until it is replaced, None hides it
+    pub fn set_panel(&mut self, panel: Option<Panel>) {
+        self.osd.set_panel(panel);
+    }
+
+    // Shows the menu drawn into `menu`, a canvas of MENU_WIDTH x MENU_HEIGHT. An all zero canvas hides it.
+    pub fn set_menu(&self, menu: &TextCanvas) {
+        assert_eq!((menu.width(), menu.height()), (MENU_WIDTH, MENU_HEIGHT), "Menu canvas has the wrong size");
+        self.write_canvas(&self.menu_texture, menu);
+    }
+
+    fn write_canvas(&self, texture: &wgpu::Texture, canvas: &TextCanvas) {
+        self.context.queue.write_texture(
+            ImageCopyTexture {
+                texture,
+                aspect: wgpu::TextureAspect::All,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: 0 }
+            },
+            canvas.pixels(),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(canvas.width() as u32),
+                rows_per_image: NonZeroU32::new(canvas.height() as u32)
+            },
+            Extent3d { width: canvas.width() as u32, height: canvas.height() as u32, depth_or_array_layers: 1 }
+        );
+    }
+
+    // Presents `framebuffer` and records `instructions`, the number of instructions executed for this frame
+    pub fn update(&mut self, framebuffer: &Framebuffer, instructions: u64) {
+        let (delta, refreshed) = self.stats.record_frame(instructions);
+        if refreshed && self.show_stats {
+            self.update_hud();
+        }
+
+        self.time = self.start_time.elapsed().as_secs_f32();
+        self.delta_time = delta.as_secs_f32();
+        self.write_frame_uniforms();
+
+        framebuffer.unpack(&mut self.pixels);
+        let pixels = self.phosphor.update(&self.pixels);
+        self.context.queue.write_texture(
             ImageCopyTexture {
                 texture: &self.backing_texture,
                 aspect: wgpu::TextureAspect::All,
                 mip_level: 0,
                 origin: wgpu::Origin3d { x: 0, y: 0, z: 0 }
             },
-            &pixels,
+            pixels,
             ImageDataLayout {
                 offset: 0,
                 bytes_per_row: NonZeroU32::new(64),
@@ -213,23 +439,158 @@ impl Chip8Display {
             Extent3d { width: 64, height: 32, depth_or_array_layers: 1 }
         );
 
+        let size = self.window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        let context = &self.context;
+        let current_surface = context.surface.get_current_texture().unwrap();
+        let current_texture_view = current_surface.texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        self.draw(&mut encoder, &current_texture_view, self.viewport);
+
+        // The OSD covers the whole window, not just the viewport, and stays out of captures
+        self.osd.prepare(&context.device, &context.queue, size.width, size.height);
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &current_texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: true }
+                })],
+                label: Some("OSD pass"),
+                depth_stencil_attachment: None
+            });
+            self.osd.draw(&mut render_pass);
+        }
+
+        let command_buffer = encoder.finish();
+        context.queue.submit([command_buffer]);
+
+        current_surface.present();
+
+    }
+
+    // Renders the current frame, CRT effects and bezel included, to an offscreen texture and reads it back
+    pub fn capture_frame(&self) -> image::RgbaImage {
+        let context = &self.context;
+        let width = self.viewport.width as u32;
+        let height = self.viewport.height as u32;
+
+        let texture = context.device.create_texture(&TextureDescriptor {
+            label: Some("Capture texture"),
+            size: Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: context.surface_format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC
+        });
+
+        // Rows in buffer copies must be aligned to COPY_BYTES_PER_ROW_ALIGNMENT
+        let row_bytes = width * 4;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("Capture buffer"),
+            size: (padded_row_bytes * height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        self.draw(&mut encoder, &view, Viewport { x: 0.0, y: 0.0, ..self.viewport });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row_bytes),
+                    rows_per_image: None
+                }
+            },
+            Extent3d { width, height, depth_or_array_layers: 1 }
+        );
+        context.queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.expect("Couldn't map capture buffer"));
+        context.device.poll(wgpu::Maintain::Wait);
+
+        let data = slice.get_mapped_range();
+        let bgra = matches!(context.surface_format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb);
+        let mut image = image::RgbaImage::new(width, height);
+        for (y, row) in data.chunks_exact(padded_row_bytes as usize).enumerate() {
+            for (x, px) in row[..row_bytes as usize].chunks_exact(4).enumerate() {
+                let rgba = if bgra { [px[2], px[1], px[0], px[3]] } else { [px[0], px[1], px[2], px[3]] };
+                image.put_pixel(x as u32, y as u32, image::Rgba(rgba));
+            }
+        }
+        drop(data);
+        buffer.unmap();
+        image
+    }
+
+    fn write_frame_uniforms(&self) {
+        let bytes = frame_uniform_bytes(
+            self.time,
+            self.delta_time,
+            self.viewport.width,
+            self.viewport.height,
+            self.scale_mode == ScaleMode::IntegerScale
+        );
+        self.context.queue.write_buffer(&self.frame_buffer, 0, &bytes);
+    }
+
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, viewport: Viewport) {
+        // The bezel has transparent corners, the letterbox bars of the bare framebuffer should not
+        let ops = match self.scale_mode {
+            ScaleMode::Bezel => gpu::LOAD_STORE_TRANSPARENT_OPS,
+            ScaleMode::IntegerScale => gpu::LOAD_STORE_BLACK_OPS
+        };
+
+        let color_attachment = RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops
+        };
+
         let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
             color_attachments: &[Some(color_attachment)],
             label: None,
             depth_stencil_attachment: None
         });
 
-        let pipeline = &self.pipeline;
-        render_pass.set_pipeline(pipeline);
+        render_pass.set_viewport(viewport.x, viewport.y, viewport.width, viewport.height, 0.0, 1.0);
+        render_pass.set_pipeline(&self.pipeline);
         render_pass.set_bind_group(0, &self.bind_group, &[]);
         render_pass.draw(0..4, 0..1);
-        drop(render_pass);
-
-        let command_buffer = encoder.finish();
-        context.queue.submit([command_buffer]);
+    }
+}
 
-        current_surface.present();
-        self.time += 0.01666666;
+// Centers the content in the window, keeping its aspect ratio
+fn fit_viewport(window: PhysicalSize<u32>, mode: ScaleMode, overlay_size: (u32, u32)) -> Viewport {
+    let (window_w, window_h) = (window.width.max(1) as f32, window.height.max(1) as f32);
+    let (width, height) = match mode {
+        ScaleMode::Bezel => {
+            let (content_w, content_h) = (overlay_size.0 as f32, overlay_size.1 as f32);
+            let scale = (window_w / content_w).min(window_h / content_h);
+            (content_w * scale, content_h * scale)
+        }
+        ScaleMode::IntegerScale => {
+            let (content_w, content_h) = (Chip8::DISPLAY_WIDTH as f32, Chip8::DISPLAY_HEIGHT as f32);
+            let scale = (window_w / content_w).min(window_h / content_h).floor().max(1.0);
+            (content_w * scale, content_h * scale)
+        }
+    };
 
+    Viewport {
+        x: ((window_w - width) / 2.0).max(0.0).floor(),
+        y: ((window_h - height) / 2.0).max(0.0).floor(),
+        width: width.floor().max(1.0),
+        height: height.floor().max(1.0)
     }
 }
@@ -0,0 +1,526 @@
+// GDB remote serial protocol stub. A debugger connects over TCP or a Unix
+// socket and gets the registers and memory of a `Chip8`, breakpoints, single
+// step and continue. The registers are described by gdb/target.xml, which the
+// debugger fetches with qXfer:features:read.
+//
+// The stub doesn't own the machine. Hosts with their own main loop call
+// `GdbStub::poll` every frame and `GdbStub::run` instead of `Chip8::step`,
+// headless hosts hand the machine to `GdbStub::serve`.
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::thread;
+use std::time::Instant;
+
+use crate::chip8::{Breakpoints, Bus, Chip8, Fault};
+
+/// Target description of the registers, served as target.xml
+pub const TARGET_XML: &str = include_str!("gdb/target.xml");
+
+// Register numbers of the p and P packets, V0 to VF come first
+const REGISTER_COUNT: usize = 21;
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+
+// Signals of stop replies, in GDB's numbering
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGBUS: u8 = 10;
+const SIGSEGV: u8 = 11;
+
+// Sent by the debugger outside of packets to stop a running machine
+const INTERRUPT: u8 = 0x03;
+const PACKET_SIZE: usize = 4096;
+// The machine is reported as a single thread
+const THREAD_ID: usize = 1;
+
+/// A byte stream to a debugger
+pub trait Connection: Read + Write {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+impl<C: Connection + ?Sized> Connection for Box<C> {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        (**self).set_nonblocking(nonblocking)
+    }
+}
+
+/// Waits for a debugger to connect to `addr`, a TCP address like "127.0.0.1:9001" or the path of
+/// a Unix socket to create
+pub fn accept(addr: &str) -> io::Result<GdbStub<Box<dyn Connection>>> {
+    let conn: Box<dyn Connection> = if addr.to_socket_addrs().is_ok() {
+        Box::new(accept_tcp(addr)?.conn)
+    } else {
+        accept_path(addr)?
+    };
+    Ok(GdbStub::new(conn))
+}
+
+#[cfg(unix)]
+fn accept_path(path: &str) -> io::Result<Box<dyn Connection>> {
+    Ok(Box::new(accept_unix(path)?.conn))
+}
+
+#[cfg(not(unix))]
+fn accept_path(path: &str) -> io::Result<Box<dyn Connection>> {
+    Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{path} is not a TCP address")))
+}
+
+/// Waits for a debugger to connect to `addr`, e.g. "127.0.0.1:9001"
+pub fn accept_tcp(addr: impl ToSocketAddrs) -> io::Result<GdbStub<TcpStream>> {
+    let (stream, _) = TcpListener::bind(addr)?.accept()?;
+    stream.set_nodelay(true)?;
+    Ok(GdbStub::new(stream))
+}
+
+/// Waits for a debugger to connect to a Unix socket created at `path`
+#[cfg(unix)]
+pub fn accept_unix(path: impl AsRef<Path>) -> io::Result<GdbStub<UnixStream>> {
+    let (stream, _) = UnixListener::bind(path)?.accept()?;
+    Ok(GdbStub::new(stream))
+}
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+/// One debugger session. The machine starts halted, like a process under a debugger.
+pub struct GdbStub<C: Connection> {
+    conn: C,
+    input: Vec<u8>,
+    breakpoints: Breakpoints,
+    running: bool,
+    connected: bool,
+    no_ack: bool,
+}
+
+impl<C: Connection> GdbStub<C> {
+    pub fn new(conn: C) -> Self {
+        GdbStub {
+            conn,
+            input: Vec::new(),
+            breakpoints: Breakpoints::new(),
+            running: false,
+            connected: true,
+            no_ack: false,
+        }
+    }
+
+    /// False once the debugger detached, killed the program or closed the connection
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// True while the debugger lets the machine run, call [`GdbStub::run`] instead of
+    /// [`Chip8::step`] then. A halted machine shouldn't execute or tick its timers.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Answers the packets that arrived so far without blocking
+    pub fn poll<B: Bus>(&mut self, chip8: &mut Chip8<'_, B>) -> io::Result<()> {
+        self.conn.set_nonblocking(true)?;
+        let result = self.read_available();
+        self.conn.set_nonblocking(false)?;
+        result?;
+        self.process(chip8)
+    }
+
+    /// Executes up to `count` instructions while the machine is running. Stops at breakpoints
+    /// and faults and reports them to the debugger, pc is left at the instruction.
+    pub fn run<B: Bus>(&mut self, chip8: &mut Chip8<'_, B>, count: usize) -> io::Result<()> {
+        for _ in 0..count {
+            if !self.running {
+                break;
+            }
+            if self.breakpoints.hit(chip8) {
+                return self.stop(SIGTRAP);
+            }
+            match self.breakpoints.step(chip8) {
+                Ok(true) => (),
+                // Waiting for a key or the next 60Hz tick
+                Ok(false) => break,
+                Err(fault) => return self.stop(fault_signal(&fault)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the machine for the debugger until it disconnects, executing `instructions_per_tick`
+    /// instructions per 60Hz tick
+    pub fn serve<B: Bus>(&mut self, chip8: &mut Chip8<'_, B>, instructions_per_tick: usize) -> io::Result<()> {
+        let mut next_tick = Instant::now();
+        while self.connected {
+            if self.running {
+                self.poll(chip8)?;
+                self.run(chip8, instructions_per_tick)?;
+                chip8.tick_60hz();
+
+                next_tick += Chip8::TICK;
+                match next_tick.checked_duration_since(Instant::now()) {
+                    Some(wait) => thread::sleep(wait),
+                    None => next_tick = Instant::now(),
+                }
+            } else {
+                self.read_blocking()?;
+                self.process(chip8)?;
+                next_tick = Instant::now();
+            }
+        }
+        Ok(())
+    }
+
+    fn read_available(&mut self) -> io::Result<()> {
+        let mut buf = [0; 1024];
+        loop {
+            match self.conn.read(&mut buf) {
+                Ok(0) => {
+                    self.disconnect();
+                    return Ok(());
+                }
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn read_blocking(&mut self) -> io::Result<()> {
+        let mut buf = [0; 1024];
+        match self.conn.read(&mut buf) {
+            Ok(0) => self.disconnect(),
+            Ok(n) => self.input.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        self.connected = false;
+        self.running = false;
+    }
+
+    fn process<B: Bus>(&mut self, chip8: &mut Chip8<'_, B>) -> io::Result<()> {
+        while self.connected {
+            match self.next_incoming()? {
+                Some(Incoming::Interrupt) => {
+                    if self.running {
+                        self.stop(SIGINT)?;
+                    }
+                }
+                Some(Incoming::Packet(packet)) => self.handle(chip8, &packet)?,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    // Takes the next interrupt or complete packet off the input, acknowledging packets
+    fn next_incoming(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            let Some(&first) = self.input.first() else { return Ok(None) };
+            if first == INTERRUPT {
+                self.input.remove(0);
+                return Ok(Some(Incoming::Interrupt));
+            }
+            if first != b'$' {
+                // Acks of our replies and line noise
+                self.input.remove(0);
+                continue;
+            }
+
+            let Some(end) = self.input.iter().position(|&b| b == b'#') else { return Ok(None) };
+            if self.input.len() < end + 3 {
+                return Ok(None);
+            }
+            let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+            let data = &packet[1..end];
+            let checksum = std::str::from_utf8(&packet[end + 1..]).ok().and_then(|s| u8::from_str_radix(s, 16).ok());
+            if checksum != Some(checksum_of(data)) {
+                if !self.no_ack {
+                    self.conn.write_all(b"-")?;
+                }
+                continue;
+            }
+            if !self.no_ack {
+                self.conn.write_all(b"+")?;
+            }
+            return Ok(Some(Incoming::Packet(String::from_utf8_lossy(data).into_owned())));
+        }
+    }
+
+    fn handle<B: Bus>(&mut self, chip8: &mut Chip8<'_, B>, packet: &str) -> io::Result<()> {
+        let reply = match packet {
+            "?" => format!("S{SIGTRAP:02x}"),
+            "g" => {
+                let mut hex = String::new();
+                for n in 0..REGISTER_COUNT {
+                    hex += &read_register(chip8, n);
+                }
+                hex
+            }
+            "c" => return self.resume(),
+            "s" => return self.single_step(chip8),
+            "vCont?" => "vCont;c;s".to_string(),
+            "D" => {
+                self.send(b"OK")?;
+                self.disconnect();
+                return Ok(());
+            }
+            "k" => {
+                self.disconnect();
+                return Ok(());
+            }
+            "QStartNoAckMode" => {
+                self.send(b"OK")?;
+                self.no_ack = true;
+                return Ok(());
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => format!("QC{THREAD_ID:x}"),
+            "qfThreadInfo" => format!("m{THREAD_ID:x}"),
+            "qsThreadInfo" => "l".to_string(),
+            _ if packet.starts_with("qSupported") => {
+                format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+;vContSupported+")
+            }
+            _ if packet.starts_with("qXfer:features:read:target.xml:") => {
+                read_target_xml(&packet["qXfer:features:read:target.xml:".len()..])
+            }
+            _ if packet.starts_with("vCont;") => match vcont_action(&packet["vCont;".len()..]) {
+                Some('c') => return self.resume(),
+                Some('s') => return self.single_step(chip8),
+                _ => String::new(),
+            },
+            _ if packet.starts_with('H') || packet.starts_with('T') => "OK".to_string(),
+            _ if packet.starts_with('G') => write_registers(chip8, &packet[1..]),
+            _ if packet.starts_with('p') => match parse_hex(&packet[1..]) {
+                Some(n) if n < REGISTER_COUNT => read_register(chip8, n),
+                _ => "E01".to_string(),
+            },
+            _ if packet.starts_with('P') => write_register(chip8, &packet[1..]),
+            _ if packet.starts_with('m') => read_memory(chip8, &packet[1..]),
+            _ if packet.starts_with('M') => write_memory(chip8, &packet[1..]),
+            // Software and hardware breakpoints are the same thing here
+            _ if packet.starts_with("Z0,") || packet.starts_with("Z1,") => match breakpoint_address(packet) {
+                Some(addr) => {
+                    self.breakpoints.insert(addr);
+                    "OK".to_string()
+                }
+                None => "E01".to_string(),
+            },
+            _ if packet.starts_with("z0,") || packet.starts_with("z1,") => match breakpoint_address(packet) {
+                Some(addr) => {
+                    self.breakpoints.remove(addr);
+                    "OK".to_string()
+                }
+                None => "E01".to_string(),
+            },
+            // Unsupported packets get an empty reply
+            _ => String::new(),
+        };
+        self.send(reply.as_bytes())
+    }
+
+    // The stop reply is sent when the machine stops
+    fn resume(&mut self) -> io::Result<()> {
+        self.running = true;
+        self.breakpoints.resume();
+        Ok(())
+    }
+
+    fn single_step<B: Bus>(&mut self, chip8: &mut Chip8<'_, B>) -> io::Result<()> {
+        match chip8.step_one() {
+            Ok(_) => self.stop(SIGTRAP),
+            Err(fault) => self.stop(fault_signal(&fault)),
+        }
+    }
+
+    fn stop(&mut self, signal: u8) -> io::Result<()> {
+        self.running = false;
+        self.send(format!("S{signal:02x}").as_bytes())
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(data);
+        packet.extend_from_slice(format!("#{:02x}", checksum_of(data)).as_bytes());
+        self.conn.write_all(&packet)?;
+        self.conn.flush()
+    }
+}
+
+fn fault_signal(fault: &Fault) -> u8 {
+    match fault {
+        Fault::MemoryOutOfBounds { .. } => SIGSEGV,
+        Fault::Bus { .. } => SIGBUS,
+        Fault::Unimplemented { .. } => SIGILL,
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(hex, "{b:02x}");
+    }
+    hex
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// Size of register `n` in bytes
+fn register_size(n: usize) -> usize {
+    if n == REG_I || n == REG_PC { 2 } else { 1 }
+}
+
+// Registers are little endian, which GDB assumes for a target without an architecture
+fn read_register<B: Bus>(chip8: &Chip8<'_, B>, n: usize) -> String {
+    let state = chip8.state();
+    match n {
+        0..=15 => format!("{:02x}", state.reg[n]),
+        REG_I => format!("{:02x}{:02x}", state.i & 0xff, (state.i >> 8) & 0xff),
+        REG_PC => format!("{:02x}{:02x}", state.pc & 0xff, (state.pc >> 8) & 0xff),
+        REG_SP => format!("{:02x}", state.sp),
+        REG_DT => format!("{:02x}", state.delay_timer),
+        _ => format!("{:02x}", state.sound_timer),
+    }
+}
+
+fn set_register<B: Bus>(chip8: &mut Chip8<'_, B>, n: usize, bytes: &[u8]) {
+    let value = bytes.iter().rev().fold(0usize, |v, b| v << 8 | *b as usize);
+    let mut state = chip8.state();
+    match n {
+        0..=15 => state.reg[n] = value as u8,
+        REG_I => state.i = value,
+        REG_PC => state.pc = value,
+        REG_SP => state.sp = value.min(state.stack.len()),
+        REG_DT => state.delay_timer = value as u8,
+        _ => state.sound_timer = value as u8,
+    }
+    chip8.set_state(&state);
+}
+
+// "n=value"
+fn write_register<B: Bus>(chip8: &mut Chip8<'_, B>, args: &str) -> String {
+    let parsed = args.split_once('=').and_then(|(n, value)| Some((parse_hex(n)?, from_hex(value)?)));
+    match parsed {
+        Some((n, bytes)) if n < REGISTER_COUNT && bytes.len() == register_size(n) => {
+            set_register(chip8, n, &bytes);
+            "OK".to_string()
+        }
+        _ => "E01".to_string(),
+    }
+}
+
+fn write_registers<B: Bus>(chip8: &mut Chip8<'_, B>, hex: &str) -> String {
+    let Some(bytes) = from_hex(hex) else { return "E01".to_string() };
+    let total: usize = (0..REGISTER_COUNT).map(register_size).sum();
+    if bytes.len() != total {
+        return "E01".to_string();
+    }
+    let mut offset = 0;
+    for n in 0..REGISTER_COUNT {
+        let size = register_size(n);
+        set_register(chip8, n, &bytes[offset..offset + size]);
+        offset += size;
+    }
+    "OK".to_string()
+}
+
+// "addr,length", reads stop at the end of memory
+fn read_memory<B: Bus>(chip8: &Chip8<'_, B>, args: &str) -> String {
+    let Some((addr, len)) = args.split_once(',').and_then(|(a, l)| Some((parse_hex(a)?, parse_hex(l)?))) else {
+        return "E01".to_string();
+    };
+    if addr >= Chip8::MEM_SIZE {
+        return "E14".to_string();
+    }
+    let end = addr.saturating_add(len).min(Chip8::MEM_SIZE);
+    let bytes: Vec<u8> = (addr..end).map(|a| chip8.bus().peek(a)).collect();
+    to_hex(&bytes)
+}
+
+// "addr,length:data", written with poke so the debugger can patch write protected memory
+fn write_memory<B: Bus>(chip8: &mut Chip8<'_, B>, args: &str) -> String {
+    let parsed = args.split_once(':').and_then(|(range, data)| {
+        let (addr, len) = range.split_once(',')?;
+        Some((parse_hex(addr)?, parse_hex(len)?, from_hex(data)?))
+    });
+    match parsed {
+        Some((addr, len, data)) if data.len() == len && addr.saturating_add(len) <= Chip8::MEM_SIZE => {
+            chip8.bus_mut().load(addr, &data);
+            "OK".to_string()
+        }
+        _ => "E01".to_string(),
+    }
+}
+
+// "Z0,addr,kind"
+// The action of "vCont;action[:thread];..." for the only thread, 'c' or 's'. The first action
+// for thread 1 or for all threads applies, the signals of C and S are ignored.
+fn vcont_action(actions: &str) -> Option<char> {
+    let action = actions.split(';').find(|action| match action.split_once(':') {
+        Some((_, thread)) => thread == "-1" || parse_hex(thread) == Some(THREAD_ID),
+        None => true,
+    })?;
+    Some(action.chars().next()?.to_ascii_lowercase()).filter(|action| matches!(action, 'c' | 's'))
+}
+
+fn breakpoint_address(packet: &str) -> Option<usize> {
+    let mut fields = packet.split(',');
+    fields.next()?;
+    parse_hex(fields.next()?).filter(|addr| *addr < Chip8::MEM_SIZE)
+}
+
+// "offset,length" of the document, 'l' marks the last chunk
+fn read_target_xml(args: &str) -> String {
+    let Some((offset, len)) = args.split_once(',').and_then(|(o, l)| Some((parse_hex(o)?, parse_hex(l)?))) else {
+        return "E01".to_string();
+    };
+    let xml = TARGET_XML.as_bytes();
+    let start = offset.min(xml.len());
+    let end = start.saturating_add(len).min(xml.len());
+    let marker = if end == xml.len() { 'l' } else { 'm' };
+    format!("{marker}{}", String::from_utf8_lossy(&xml[start..end]))
+}
+
+#[cfg(test)]
+mod tests;
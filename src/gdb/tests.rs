@@ -0,0 +1,225 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use super::{GdbStub, TARGET_XML};
+use crate::chip8::Chip8;
+
+const PROGRAM: &[u8] = &[
+    0x60, 0x05,     // 0x200: gp0 = 5
+    0x61, 0x03,     // 0x202: gp1 = 3
+    0x80, 0x14,     // 0x204: gp0 += gp1
+    0xa3, 0x00,     // 0x206: i = 0x300
+    0xf0, 0x55,     // 0x208: store gp0 to [i]
+    0x12, 0x0a,     // 0x20a: loop forever
+];
+
+// Talks to the stub like GDB does, checking the acks and checksums of the replies
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn connect(addr: SocketAddr) -> Self {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+        Client { stream }
+    }
+
+    fn send(&mut self, packet: &str) {
+        let checksum = packet.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.stream, "${packet}#{checksum:02x}").unwrap();
+        assert_eq!(self.read_byte(), b'+', "{packet} wasn't acknowledged");
+    }
+
+    fn reply(&mut self) -> String {
+        assert_eq!(self.read_byte(), b'$');
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                b => data.push(b),
+            }
+        }
+        let checksum = [self.read_byte(), self.read_byte()];
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(checksum, data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)));
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn request(&mut self, packet: &str) -> String {
+        self.send(packet);
+        self.reply()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+#[test]
+fn test_gdb_session() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut chip8 = Chip8::from_rom(PROGRAM.to_vec());
+        GdbStub::new(stream).serve(&mut chip8, 10).unwrap();
+        chip8.state()
+    });
+    let mut gdb = Client::connect(addr);
+
+    assert!(gdb.request("qSupported:xmlRegisters=i386").contains("qXfer:features:read+"));
+    let xml = gdb.request("qXfer:features:read:target.xml:0,fff");
+    assert_eq!(xml, format!("l{TARGET_XML}"));
+    assert_eq!(gdb.request("qXfer:features:read:target.xml:0,10"), format!("m{}", &TARGET_XML[..16]));
+    assert_eq!(gdb.request("?"), "S05");
+    assert_eq!(gdb.request("vMustReplyEmpty"), "");
+
+    // V0 to VF, I, PC, SP, DT, ST
+    assert_eq!(gdb.request("g"), "00".repeat(16) + "0000" + "0002" + "00" + "00" + "00");
+
+    // Continue to a breakpoint
+    assert_eq!(gdb.request("Z0,206,2"), "OK");
+    gdb.send("c");
+    assert_eq!(gdb.reply(), "S05");
+    assert_eq!(gdb.request("p11"), "0602");
+    assert_eq!(gdb.request("p0"), "08");
+
+    // Single step, then change a register
+    assert_eq!(gdb.request("s"), "S05");
+    assert_eq!(gdb.request("p10"), "0003");
+    assert_eq!(gdb.request("P0=2a"), "OK");
+    assert_eq!(gdb.request("p0"), "2a");
+    assert_eq!(gdb.request("P10=12"), "E01");
+
+    // 16-bit registers are little endian
+    assert_eq!(gdb.request("P10=3402"), "OK");
+    assert_eq!(gdb.request("p10"), "3402");
+    assert_eq!(gdb.request("g")[32..36], *"3402");
+    assert_eq!(gdb.request("P10=0003"), "OK");
+
+    // Continuing from a breakpoint runs its instruction before stopping again
+    assert_eq!(gdb.request("z0,206,2"), "OK");
+    assert_eq!(gdb.request("Z1,20a,2"), "OK");
+    gdb.send("c");
+    assert_eq!(gdb.reply(), "S05");
+    assert_eq!(gdb.request("p11"), "0a02");
+    gdb.send("c");
+    assert_eq!(gdb.reply(), "S05");
+    assert_eq!(gdb.request("p11"), "0a02");
+
+    // Memory
+    assert_eq!(gdb.request("m300,2"), "2a00");
+    assert_eq!(gdb.request("M300,2:beef"), "OK");
+    assert_eq!(gdb.request("m300,2"), "beef");
+    assert_eq!(gdb.request("mffe,4"), "0000");
+    assert_eq!(gdb.request("m1000,1"), "E14");
+
+    // Interrupt a running machine
+    assert_eq!(gdb.request("z1,20a,2"), "OK");
+    gdb.send("c");
+    gdb.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(gdb.reply(), "S02");
+
+    // Unimplemented instructions stop with SIGILL and leave pc at the instruction
+    assert_eq!(gdb.request("P11=1002"), "OK");
+    gdb.send("c");
+    assert_eq!(gdb.reply(), "S04");
+    assert_eq!(gdb.request("D"), "OK");
+
+    let state = server.join().unwrap();
+    assert_eq!(state.pc, 0x210);
+    assert_eq!(state.reg[0], 0x2a);
+}
+
+#[test]
+fn test_gdb_bad_checksum_and_no_ack_mode() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut chip8 = Chip8::from_rom(PROGRAM.to_vec());
+        GdbStub::new(stream).serve(&mut chip8, 10).unwrap();
+    });
+    let mut gdb = Client::connect(addr);
+
+    gdb.stream.write_all(b"$g#00").unwrap();
+    assert_eq!(gdb.read_byte(), b'-');
+    assert_eq!(gdb.request("QStartNoAckMode"), "OK");
+
+    // Without acks the stub replies directly
+    gdb.stream.write_all(b"$p11#d2").unwrap();
+    assert_eq!(gdb.read_byte(), b'$');
+    let mut rest = [0; 7];
+    gdb.stream.read_exact(&mut rest).unwrap();
+    assert_eq!(&rest, b"0002#c2");
+
+    gdb.stream.write_all(b"$k#6b").unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn test_gdb_vcont_with_thread_ids() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut chip8 = Chip8::from_rom(PROGRAM.to_vec());
+        GdbStub::new(stream).serve(&mut chip8, 10).unwrap();
+    });
+    let mut gdb = Client::connect(addr);
+
+    // What GDB sends for stepi and continue once it knows about thread 1
+    assert_eq!(gdb.request("vCont;s:1;c"), "S05");
+    assert_eq!(gdb.request("p11"), "0202");
+    assert_eq!(gdb.request("Z0,206,2"), "OK");
+    gdb.send("vCont;c:1");
+    assert_eq!(gdb.reply(), "S05");
+    assert_eq!(gdb.request("p11"), "0602");
+    // Actions for other threads don't apply
+    assert_eq!(gdb.request("vCont;c:2;s"), "S05");
+    assert_eq!(gdb.request("p11"), "0802");
+    assert_eq!(gdb.request("vCont;t"), "");
+
+    assert_eq!(gdb.request("D"), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn test_gdb_registers_are_16_bits() {
+    // gp0 = 0xff, then i += gp0 258 times
+    let mut program = vec![0x60, 0xff];
+    for _ in 0..258 {
+        program.extend([0xf0, 0x1e]);
+    }
+    let end = 0x200 + program.len();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut chip8 = Chip8::from_rom(program);
+        GdbStub::new(stream).serve(&mut chip8, 1000).unwrap();
+        chip8.state()
+    });
+    let mut gdb = Client::connect(addr);
+
+    // I is 0x100fe, only its low 16 bits are sent
+    assert_eq!(gdb.request(&format!("Z0,{end:x},2")), "OK");
+    gdb.send("c");
+    assert_eq!(gdb.reply(), "S05");
+    assert_eq!(gdb.request("p10"), "fe00");
+    let registers = gdb.request("g");
+    assert_eq!(registers.len(), 2 * (16 + 2 + 2 + 3));
+    assert_eq!(registers[32..36], *"fe00");
+    assert_eq!(gdb.request("D"), "OK");
+
+    assert_eq!(server.join().unwrap().i, 0x100fe);
+}
@@ -9,6 +9,7 @@ pub struct RenderContext {
     pub queue: wgpu::Queue,
     pub surface: wgpu::Surface,
     pub surface_format: wgpu::TextureFormat,
+    pub surface_config: SurfaceConfiguration,
 }
 
 impl RenderContext {
@@ -49,9 +50,21 @@ impl RenderContext {
             device,
             queue,
             surface,
-            surface_format
+            surface_format,
+            surface_config
         }
     }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        // Minimized windows report a zero size, which is not a valid surface configuration
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.surface_config.width = width;
+        self.surface_config.height = height;
+        self.surface.configure(&self.device, &self.surface_config);
+    }
 }
 
 
@@ -0,0 +1,59 @@
+//! CHIP-8 interpreter core.
+//!
+//! The library has no windowing or GPU dependencies, build it with
+//! `default-features = false, features = ["std"]` to leave out the
+//! `frontend` feature that the windowed binary needs.
+//!
+//! ```
+//! use chip8_rs::chip8::Chip8;
+//!
+//! let program = [0x60, 0x2a, 0x12, 0x02];     // v0 = 42, loop forever
+//! let mut c8 = Chip8::new(&program);
+//! c8.step(10).unwrap();
+//! c8.tick_60hz();
+//! assert_eq!(c8.state().reg[0], 42);
+//! assert_eq!(c8.pixels().len(), Chip8::DISPLAY_SIZE);
+//! ```
+//!
+//! The core is `no_std` with `alloc` when the default `std` feature is off.
+//! The host then supplies the random numbers with [`chip8::Chip8::set_rng`],
+//! a `log` logger for memory policy warnings and prints dumps through
+//! [`chip8::write_display`] and [`chip8::write_machine_state`].
+//!
+//! [`analyze`] checks a ROM without running it: the code reachable from the
+//! reset vector, instructions the core doesn't implement, the quirks the
+//! program depends on and the platform it was likely written for.
+//!
+//! [`crt`] holds the palettes and shader uniform layouts shared by the
+//! windowed and web frontends.
+//!
+//! With the `romdb` feature, which the frontend enables, `romdb` identifies
+//! ROMs by their SHA-1 and looks up the quirks, speed, colors and key map
+//! they need.
+//!
+//! With the `gdb` feature, [`gdb::GdbStub`] lets GDB or another client of the
+//! remote serial protocol inspect registers and memory, set breakpoints,
+//! single step and continue over TCP or a Unix socket.
+//!
+//! With the `dap` feature, [`dap::serve`] speaks the Debug Adapter Protocol
+//! over stdio so editors can launch a ROM, set breakpoints by address or by
+//! source line through a [`dap::SymbolMap`], step and inspect the machine.
+//!
+//! Drive [`chip8::Chip8::step`] with a few hundred instructions per second,
+//! call [`chip8::Chip8::tick_60hz`] at 60Hz and forward key presses with
+//! [`chip8::Chip8::set_key_state`].
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![allow(dead_code)]
+
+extern crate alloc;
+
+pub mod analyze;
+pub mod chip8;
+pub mod crt;
+#[cfg(feature = "dap")]
+pub mod dap;
+#[cfg(feature = "gdb")]
+pub mod gdb;
+#[cfg(feature = "romdb")]
+pub mod romdb;
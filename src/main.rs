@@ -1,18 +1,160 @@
 #![allow(dead_code)]
 
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
 use winit::{event::{WindowEvent}, event::{Event, VirtualKeyCode, ElementState}, event_loop::ControlFlow };
 
 mod gpu;
-mod chip8;
 mod display;
+mod stats;
+mod text;
+mod capture;
+mod memview;
+mod menu;
+mod osd;
+mod watch;
+
+use chip8_rs::analyze;
+use chip8_rs::chip8::{self, Chip8, Coverage, Fault, Profiler, Quirks};
+use chip8_rs::crt::{self, Palette};
+use chip8_rs::dap;
+use chip8_rs::gdb::{self, GdbStub};
+use chip8_rs::romdb::{self, RomDatabase};
+use display::{Chip8Display, ScaleMode};
+use capture::{Recorder, RecordingFormat};
+use memview::MemoryView;
+use menu::{Menu, MenuAction, MenuSettings};
+use watch::{ReloadMode, RomWatcher};
 
-use chip8::Chip8;
+// Instructions per frame for ROMs the database has no speed for
+const DEFAULT_SPEED: usize = 10;
 
+// `chip8-rs [--gdb ADDR | --dap] [--watch reset|patch] [--profile FILE] [--coverage FILE] [ROM or directory]`. A ROM runs right away and the launcher lists its directory.
+// Without a ROM the bundled trip8 demo runs and the launcher lists the given directory or roms/.
+// --gdb waits for a debugger on a TCP address or Unix socket path before the machine starts.
+// --dap runs headless as a Debug Adapter Protocol server on stdio, the editor launches the ROM.
+// --watch reloads the ROM when its file changes, resetting the machine or patching memory.
+// --profile writes a profile at exit: JSON for .json, folded stacks for .folded, a report for
+// anything else or stdout for -.
+// --coverage merges the coverage of the run into FILE at exit and writes a report next to it.
+// `chip8-rs coverage ROM FILE...` prints the report for the merged coverage files.
+// `chip8-rs analyze ROM` checks the ROM without running it and exits with 1 if it would fault.
 fn main() {
+    let mut arg = None;
+    let mut gdb_addr = None;
+    let mut watch_mode = None;
+    let mut profile_path = None;
+    let mut coverage_path = None;
+    let mut args = std::env::args_os().skip(1).peekable();
+    if args.peek().is_some_and(|a| a == "coverage") {
+        let paths: Vec<PathBuf> = args.skip(1).map(PathBuf::from).collect();
+        match merged_coverage_report(&paths) {
+            Ok(report) => print!("{report}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if args.peek().is_some_and(|a| a == "analyze") {
+        let paths: Vec<PathBuf> = args.skip(1).map(PathBuf::from).collect();
+        match analysis_report(&paths) {
+            Ok((report, errors)) => {
+                print!("{report}");
+                if errors {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+    while let Some(a) = args.next() {
+        if a == "--gdb" {
+            gdb_addr = args.next().map(|addr| addr.to_string_lossy().into_owned());
+        } else if a == "--watch" {
+            let mode = args.next().map(|mode| mode.to_string_lossy().into_owned()).unwrap_or_default();
+            watch_mode = ReloadMode::parse(&mode);
+            if watch_mode.is_none() {
+                eprintln!("--watch takes reset or patch, not '{mode}'");
+                std::process::exit(2);
+            }
+        } else if a == "--coverage" {
+            coverage_path = args.next().map(PathBuf::from);
+        } else if a == "--profile" {
+            profile_path = args.next().map(PathBuf::from);
+        } else if a == "--dap" {
+            // Nothing else may write to stdout while it carries the protocol
+            if let Err(e) = dap::serve(io::stdin(), io::stdout()) {
+                eprintln!("Debug adapter failed: {e}");
+                std::process::exit(1);
+            }
+            return;
+        } else {
+            arg = Some(PathBuf::from(a));
+        }
+    }
+    let (rom_path, rom_dir) = match arg {
+        Some(path) if path.is_dir() => (None, path),
+        Some(path) => {
+            let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf();
+            (Some(path), dir)
+        }
+        None => (None, PathBuf::from("roms")),
+    };
+
     let event_loop = winit::event_loop::EventLoop::new();
+    let mut display = Chip8Display::new(&event_loop);
+    let mut chip8 = Chip8::from_rom(include_bytes!("../roms/trip8.rom").to_vec());
+    let mut palette = Palette::Green;
+    let mut recorder: Option<Recorder> = None;
+    let mut fault: Option<Fault> = None;
+    let mut menu = Menu::new(rom_dir);
+    let mut memory_view = MemoryView::new();
 
-    let mut display = display::Chip8Display::new(&event_loop);
-    let mut chip8 = Chip8::new(include_bytes!("../roms/trip8.rom"));
+    if profile_path.is_some() {
+        chip8.set_profiler(Some(Profiler::new()));
+    }
+    if coverage_path.is_some() {
+        chip8.set_coverage(Some(Coverage::new()));
+    }
+
+    let mut rom = apply_rom(&mut display, &mut chip8, palette, "trip8");
+    let mut watcher = None;
+    if let Some(path) = rom_path {
+        match load_rom(&mut display, &mut chip8, palette, &path) {
+            Ok(loaded) => {
+                rom = loaded;
+                watcher = watch_mode.map(|_| RomWatcher::new(&path));
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                display.show_message(&e);
+            }
+        }
+    }
+
+    // The machine stays halted until the debugger continues it
+    let mut debugger = gdb_addr.and_then(|addr| {
+        println!("Waiting for a debugger on {addr}");
+        match gdb::accept(&addr) {
+            Ok(stub) => {
+                display.show_message("Debugger attached");
+                Some(stub)
+            }
+            Err(e) => {
+                eprintln!("Couldn't accept a debugger on {addr}: {e}");
+                None
+            }
+        }
+    });
 
     event_loop.run(move |event, _, control_flow| -> () {
         let window = display.window();
@@ -21,7 +163,73 @@ fn main() {
         if let Event::WindowEvent {event, ..} = event {
             match event {
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::Resized(size) => display.resize(size),
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => display.resize(*new_inner_size),
+                WindowEvent::KeyboardInput { input, .. } if menu.is_open() => {
+                    let Some(keycode) = input.virtual_keycode else { return };
+                    if input.state != ElementState::Pressed {
+                        return;
+                    }
+
+                    let settings = MenuSettings { instructions_per_frame: rom.instructions_per_frame, palette };
+                    match menu.handle_key(keycode, &settings) {
+                        MenuAction::None | MenuAction::Close => (),
+                        MenuAction::Reset => {
+                            chip8.reset();
+                            fault = None;
+                            display.show_message("Reset");
+                        }
+                        MenuAction::SaveState(slot) => save_state(&mut display, &chip8, &rom.name, slot),
+                        MenuAction::LoadState(slot) => if load_state(&mut display, &mut chip8, &rom.name, slot) { fault = None; },
+                        MenuAction::SetSpeed(speed) => {
+                            rom.instructions_per_frame = speed;
+                            display.show_message(&format!("Speed {speed} per frame"));
+                        }
+                        MenuAction::SetPalette(p) => {
+                            palette = p;
+                            let mut params = display.crt_params();
+                            params.set_palette(palette);
+                            display.set_crt_params(params);
+                            display.show_message(&format!("Palette {palette:?}"));
+                        }
+                        MenuAction::LoadRom(path) => match load_rom(&mut display, &mut chip8, palette, &path) {
+                            Ok(loaded) => {
+                                rom = loaded;
+                                fault = None;
+                                watcher = watch_mode.map(|_| RomWatcher::new(&path));
+                                // Coverage and the profile are of the ROM that runs at exit
+                                if chip8.coverage().is_some() {
+                                    chip8.set_coverage(Some(Coverage::new()));
+                                }
+                                if chip8.profiler().is_some() {
+                                    chip8.set_profiler(Some(Profiler::new()));
+                                }
+                            }
+                            Err(e) => {
+                                eprintln!("{e}");
+                                display.show_message(&e);
+                            }
+                        },
+                        MenuAction::Quit => *control_flow = ControlFlow::Exit,
+                    }
+                    let settings = MenuSettings { instructions_per_frame: rom.instructions_per_frame, palette };
+                    display.set_menu(menu.draw(&settings));
+                }
                 WindowEvent::KeyboardInput { input, .. } => {
+                    if input.state == ElementState::Pressed {
+                        if let Some(keycode) = input.virtual_keycode {
+                            if keycode == VirtualKeyCode::M {
+                                memory_view.toggle();
+                                return;
+                            }
+                            if memory_view.handle_key(keycode, &mut chip8) {
+                                return;
+                            }
+                            handle_crt_key(&mut display, &mut palette, keycode);
+                            handle_capture_key(&mut display, &chip8, &mut recorder, keycode);
+                        }
+                    }
+
                     let key = match input.virtual_keycode {
                         Some(VirtualKeyCode::Key0) => 0,
                         Some(VirtualKeyCode::Key1) => 1,
@@ -39,10 +247,21 @@ fn main() {
                         Some(VirtualKeyCode::D) => 13,
                         Some(VirtualKeyCode::E) => 14,
                         Some(VirtualKeyCode::F) => 15,
-                        Some(VirtualKeyCode::Tab) => { chip8.reset(); 255 },
+                        Some(VirtualKeyCode::Tab) => { chip8.reset(); fault = None; display.show_message("Reset"); 255 },
                         Some(VirtualKeyCode::Return) => { chip8::dump_display(&chip8); 255 }
-                        Some(VirtualKeyCode::Escape) => { *control_flow = ControlFlow::Exit; 255 }
-                        _ => 255
+                        Some(VirtualKeyCode::Escape | VirtualKeyCode::L) if input.state == ElementState::Pressed => {
+                            // Keys held while the menu opens would stay pressed, a pending Fx0A keeps waiting
+                            chip8.release_keys();
+                            if input.virtual_keycode == Some(VirtualKeyCode::L) {
+                                menu.open_launcher();
+                            } else {
+                                menu.open_pause();
+                            }
+                            display.set_menu(menu.draw(&MenuSettings { instructions_per_frame: rom.instructions_per_frame, palette }));
+                            255
+                        }
+                        Some(keycode) => mapped_key(&rom.keys, keycode).unwrap_or(255),
+                        None => 255
                     };
 
                     if key != 255 {
@@ -51,12 +270,387 @@ fn main() {
                 }
                 _ => ()
             }
+        } else if let Event::LoopDestroyed = event {
+            if let (Some(path), Some(profiler)) = (&profile_path, chip8.profiler()) {
+                write_profile(profiler, path);
+            }
+            if let (Some(path), Some(coverage)) = (&coverage_path, chip8.coverage()) {
+                write_coverage(coverage, chip8.program(), path);
+            }
         } else if let Event::RedrawRequested(_) = event {
             window.request_redraw();
-            chip8.tick_60hz();
-            chip8.step(10);
-            display.update(chip8.pixels());
+            if let (Some(w), Some(mode)) = (&mut watcher, watch_mode) {
+                if let Some(data) = w.poll() {
+                    if reload_rom(&mut display, &mut chip8, mode, w.path(), data) {
+                        fault = None;
+                    }
+                }
+            }
+            let cycles = chip8.cycles();
+            // The machine is paused while a menu is open or the memory view is paused, a faulted
+            // machine stays halted until it is reset
+            let paused = menu.is_open() || memory_view.is_paused();
+            if let (Some(stub), false) = (&mut debugger, paused) {
+                match run_debugger(stub, &mut chip8, rom.instructions_per_frame) {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        display.show_message("Debugger detached");
+                        debugger = None;
+                    }
+                    Err(e) => {
+                        eprintln!("Debugger connection failed: {e}");
+                        display.show_message("Debugger connection failed");
+                        debugger = None;
+                    }
+                }
+            } else if !paused {
+                chip8.tick_60hz();
+                if fault.is_none() {
+                    if let Err(f) = chip8.step(rom.instructions_per_frame) {
+                        eprintln!("{f}");
+                        chip8::dump_machine_state(&chip8);
+                        display.show_message(&f.to_string());
+                        fault = Some(f);
+                    }
+                }
+            }
+            display.set_panel(memory_view.update(&chip8));
+            display.update(chip8.framebuffer(), chip8.cycles() - cycles);
+
+            if let Some(rec) = &mut recorder {
+                if let Err(e) = rec.record(&chip8.pixels(), &display.crt_params()) {
+                    eprintln!("Recording to {} failed: {e}", rec.path().display());
+                    display.show_message("Recording failed");
+                    recorder = None;
+                }
+            }
         }
     });
 
 }
+
+// The debugger decides when the machine runs. Returns false once it is gone.
+fn run_debugger(stub: &mut GdbStub<Box<dyn gdb::Connection>>, chip8: &mut Chip8, count: usize) -> io::Result<bool> {
+    stub.poll(chip8)?;
+    if stub.is_running() {
+        chip8.tick_60hz();
+        stub.run(chip8, count)?;
+    }
+    Ok(stub.is_connected())
+}
+
+fn write_profile(profiler: &Profiler, path: &Path) {
+    let mut text = String::new();
+    let result = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => profiler.write_json(&mut text),
+        Some("folded") => profiler.write_folded(&mut text),
+        _ => profiler.write_report(&mut text),
+    };
+    result.expect("Writing to a String can't fail");
+
+    if path == Path::new("-") {
+        print!("{text}");
+        return;
+    }
+    match fs::write(path, text) {
+        Ok(()) => println!("Profile written to {}", path.display()),
+        Err(e) => eprintln!("Couldn't write the profile to {}: {e}", path.display()),
+    }
+}
+
+// Adds the coverage FILE holds from earlier runs of the same ROM, saves the total and writes its
+// report to FILE.txt. FILE is left alone if it holds coverage of another ROM.
+fn write_coverage(coverage: &Coverage, program: &[u8], path: &Path) {
+    let mut coverage = coverage.clone();
+    match fs::read(path) {
+        Ok(data) => match Coverage::from_bytes(&data, program) {
+            Ok(earlier) => coverage.merge(&earlier),
+            Err(e) => {
+                eprintln!("{}: {e}, leaving it alone", path.display());
+                return;
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => {
+            eprintln!("Couldn't read {}: {e}", path.display());
+            return;
+        }
+    }
+
+    let mut report_path = path.as_os_str().to_owned();
+    report_path.push(".txt");
+    let report_path = PathBuf::from(report_path);
+    let mut report = String::new();
+    coverage.write_report(program, &mut report).expect("Writing to a String can't fail");
+    match fs::write(path, coverage.to_bytes(program)).and_then(|_| fs::write(&report_path, report)) {
+        Ok(()) => println!("Coverage written to {} and {}", path.display(), report_path.display()),
+        Err(e) => eprintln!("Couldn't write the coverage to {}: {e}", path.display()),
+    }
+}
+
+fn merged_coverage_report(paths: &[PathBuf]) -> Result<String, String> {
+    let Some((rom, files)) = paths.split_first().filter(|(_, files)| !files.is_empty()) else {
+        return Err("Usage: chip8-rs coverage ROM FILE...".to_string());
+    };
+    let program = fs::read(rom).map_err(|e| format!("Couldn't read {}: {e}", rom.display()))?;
+    let mut coverage = Coverage::new();
+    for file in files {
+        let data = fs::read(file).map_err(|e| format!("Couldn't read {}: {e}", file.display()))?;
+        let other = Coverage::from_bytes(&data, &program).map_err(|e| format!("{}: {e}", file.display()))?;
+        coverage.merge(&other);
+    }
+    let mut report = String::new();
+    coverage.write_report(&program, &mut report).expect("Writing to a String can't fail");
+    Ok(report)
+}
+
+// The report and whether the analysis found errors
+fn analysis_report(paths: &[PathBuf]) -> Result<(String, bool), String> {
+    let [rom] = paths else {
+        return Err("Usage: chip8-rs analyze ROM".to_string());
+    };
+    let program = fs::read(rom).map_err(|e| format!("Couldn't read {}: {e}", rom.display()))?;
+    let analysis = analyze::analyze(&program);
+    let mut report = String::new();
+    analysis.write_report(&mut report).expect("Writing to a String can't fail");
+    Ok((report, analysis.has_errors()))
+}
+
+// Frontend settings of the running ROM, replaced when another ROM is loaded
+struct LoadedRom {
+    // Names the save state files
+    name: String,
+    instructions_per_frame: usize,
+    keys: BTreeMap<String, u8>,
+}
+
+fn load_rom(display: &mut Chip8Display, chip8: &mut Chip8, palette: Palette, path: &Path) -> Result<LoadedRom, String> {
+    let data = fs::read(path).map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+    Chip8::check_program_size(data.len()).map_err(|e| format!("{}: {e}", path.display()))?;
+    chip8.load_program(data);
+    let name = path.file_stem().map_or("rom".into(), |stem| stem.to_string_lossy().into_owned());
+    Ok(apply_rom(display, chip8, palette, &name))
+}
+
+// Applies a rewritten ROM, the settings of the ROM it replaces are kept. Returns true if the
+// machine runs the new program.
+fn reload_rom(display: &mut Chip8Display, chip8: &mut Chip8, mode: ReloadMode, path: &Path, data: io::Result<Vec<u8>>) -> bool {
+    let data = match data {
+        Ok(data) => Chip8::check_program_size(data.len()).map(|()| data).map_err(|e| format!("{}: {e}", path.display())),
+        Err(e) => Err(format!("Couldn't reload {}: {e}", path.display())),
+    };
+    let data = match data {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("{e}");
+            display.show_message(&e);
+            return false;
+        }
+    };
+
+    let message = match mode {
+        ReloadMode::Reset => {
+            chip8.load_program(data);
+            "ROM reloaded".to_string()
+        }
+        ReloadMode::Patch => format!("ROM patched, {} bytes changed", chip8.patch_program(data)),
+    };
+    println!("{message} from {}", path.display());
+    display.show_message(&message);
+    true
+}
+
+// Applies the quirks, speed and colors the ROM database has for the program and shows its title.
+// Unknown programs get the quirks their code hints at, see `Analysis::suggest_quirks`.
+fn apply_rom(display: &mut Chip8Display, chip8: &mut Chip8, palette: Palette, name: &str) -> LoadedRom {
+    let info = RomDatabase::bundled().identify(chip8.program());
+    let title = match &info {
+        Some(info) => info.display_name(),
+        None => name.to_string(),
+    };
+    println!("Running {title}");
+    display.window().set_title(&format!("chip8-rs - {title}"));
+    display.show_message(&title);
+
+    let mut config = chip8.config();
+    config.quirks = match &info {
+        Some(info) => info.quirks,
+        None => guess_quirks(chip8.program()),
+    };
+    chip8.set_config(config);
+
+    let mut params = display.crt_params();
+    params.set_palette(palette);
+    if let Some([bg, fg, ..]) = info.as_ref().and_then(|info| info.colors.as_ref()).map(|colors| colors.pixels.as_slice()) {
+        let rgba = |c: &[u8; 3]| [c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0, 1.0];
+        params.foreground = rgba(fg);
+        params.background = rgba(bg);
+    }
+    display.set_crt_params(params);
+
+    LoadedRom {
+        name: name.to_string(),
+        instructions_per_frame: info.as_ref().and_then(|info| info.tickrate).map_or(DEFAULT_SPEED, |t| t as usize),
+        keys: info.map(|info| info.keys).unwrap_or_default(),
+    }
+}
+
+fn guess_quirks(program: &[u8]) -> Quirks {
+    let suggestion = analyze::analyze(program).suggest_quirks();
+    if suggestion.reasons.is_empty() {
+        println!("Unknown ROM {}, using the default quirks", romdb::sha1_hex(program));
+    } else {
+        println!("Unknown ROM {}, guessing the quirks from its code", romdb::sha1_hex(program));
+    }
+    for reason in &suggestion.reasons {
+        println!("  {reason}");
+    }
+    suggestion.quirks
+}
+
+// Save states go to the working directory like captures, e.g. "blinky-slot1.c8st"
+fn state_path(name: &str, slot: u8) -> PathBuf {
+    PathBuf::from(format!("{name}-slot{}.c8st", slot + 1))
+}
+
+fn save_state(display: &mut Chip8Display, chip8: &Chip8, name: &str, slot: u8) {
+    let path = state_path(name, slot);
+    match fs::write(&path, chip8.save_snapshot()) {
+        Ok(()) => {
+            println!("Saved {}", path.display());
+            display.show_message(&format!("State saved to slot {}", slot + 1));
+        }
+        Err(e) => {
+            eprintln!("Couldn't save {}: {e}", path.display());
+            display.show_message(&format!("Couldn't save state: {e}"));
+        }
+    }
+}
+
+// Returns true if the state was restored
+fn load_state(display: &mut Chip8Display, chip8: &mut Chip8, name: &str, slot: u8) -> bool {
+    let path = state_path(name, slot);
+    let result = fs::read(&path).map_err(|e| e.to_string())
+        .and_then(|data| chip8.load_snapshot(&data).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => {
+            println!("Loaded {}", path.display());
+            display.show_message(&format!("State loaded from slot {}", slot + 1));
+            true
+        }
+        Err(e) => {
+            eprintln!("Couldn't load {}: {e}", path.display());
+            display.show_message(&format!("Couldn't load state: {e}"));
+            false
+        }
+    }
+}
+
+// Arrow keys, Space and X play the roles the ROM database gives to keypad keys
+fn mapped_key(keys: &BTreeMap<String, u8>, keycode: VirtualKeyCode) -> Option<u8> {
+    let role = match keycode {
+        VirtualKeyCode::Up => "up",
+        VirtualKeyCode::Down => "down",
+        VirtualKeyCode::Left => "left",
+        VirtualKeyCode::Right => "right",
+        VirtualKeyCode::Space => "a",
+        VirtualKeyCode::X => "b",
+        _ => return None
+    };
+    keys.get(role).copied().filter(|key| *key < 16)
+}
+
+fn handle_crt_key(display: &mut Chip8Display, palette: &mut Palette, keycode: VirtualKeyCode) {
+    let mut params = display.crt_params();
+    match keycode {
+        VirtualKeyCode::F1 => params.enabled = !params.enabled,
+        VirtualKeyCode::F2 => {
+            *palette = palette.next();
+            params.set_palette(*palette);
+        }
+        VirtualKeyCode::F3 => params.distortion = (params.distortion - 0.05).max(0.0),
+        VirtualKeyCode::F4 => params.distortion = (params.distortion + 0.05).min(1.0),
+        VirtualKeyCode::F5 => params.scanline_intensity = (params.scanline_intensity - 0.1).max(0.0),
+        VirtualKeyCode::F6 => params.scanline_intensity = (params.scanline_intensity + 0.1).min(1.0),
+        VirtualKeyCode::F7 => params.scanline_density = (params.scanline_density - 50.0).max(50.0),
+        VirtualKeyCode::F8 => params.scanline_density = (params.scanline_density + 50.0).min(2000.0),
+        VirtualKeyCode::F9 | VirtualKeyCode::F10 => {
+            let frames = display.persistence_frames();
+            let frames = if keycode == VirtualKeyCode::F9 { frames.saturating_sub(1) } else { frames + 1 };
+            display.set_persistence_frames(frames);
+            display.show_message(&format!("Persistence {frames} frames"));
+            return;
+        }
+        VirtualKeyCode::F12 => {
+            display.set_show_stats(!display.show_stats());
+            return;
+        }
+        _ => return
+    }
+    display.set_crt_params(params);
+
+    let message = match keycode {
+        VirtualKeyCode::F1 => format!("CRT effects {}", if params.enabled { "on" } else { "off" }),
+        VirtualKeyCode::F2 => format!("Palette {palette:?}"),
+        VirtualKeyCode::F3 | VirtualKeyCode::F4 => format!("Distortion {:.2}", params.distortion),
+        VirtualKeyCode::F5 | VirtualKeyCode::F6 => format!("Scanline intensity {:.1}", params.scanline_intensity),
+        _ => format!("Scanline density {:.0}", params.scanline_density),
+    };
+    display.show_message(&message);
+}
+
+fn handle_capture_key(display: &mut Chip8Display, chip8: &Chip8, recorder: &mut Option<Recorder>, keycode: VirtualKeyCode) {
+    let format = match keycode {
+        VirtualKeyCode::F11 => { display.toggle_fullscreen(); return; }
+        VirtualKeyCode::I => {
+            let mode = match display.scale_mode() {
+                ScaleMode::Bezel => ScaleMode::IntegerScale,
+                ScaleMode::IntegerScale => ScaleMode::Bezel
+            };
+            display.set_scale_mode(mode);
+            display.show_message(match mode {
+                ScaleMode::Bezel => "Bezel",
+                ScaleMode::IntegerScale => "Integer scale"
+            });
+            return;
+        }
+        VirtualKeyCode::P => {
+            let [raw_path, crt_path] = capture::capture_paths(["-raw.png", "-crt.png"]);
+            let result = capture::save_framebuffer(&chip8.pixels(), &raw_path)
+                .and_then(|_| display.capture_frame().save(&crt_path));
+            match result {
+                Ok(_) => {
+                    println!("Saved {} and {}", raw_path.display(), crt_path.display());
+                    display.show_message(&format!("Screenshot saved to {}", crt_path.display()));
+                }
+                Err(e) => {
+                    eprintln!("Screenshot failed: {e}");
+                    display.show_message(&format!("Screenshot failed: {e}"));
+                }
+            }
+            return;
+        }
+        VirtualKeyCode::G => RecordingFormat::Gif,
+        VirtualKeyCode::N => RecordingFormat::PngSequence,
+        _ => return
+    };
+
+    // G or N stops any running recording, the GIF trailer is written when the encoder is dropped
+    if let Some(rec) = recorder.take() {
+        println!("Recorded {} frames to {}", rec.frames(), rec.path().display());
+        display.show_message(&format!("Recorded {} frames", rec.frames()));
+        return;
+    }
+
+    match Recorder::start(format) {
+        Ok(rec) => {
+            println!("Recording to {}", rec.path().display());
+            display.show_message(&format!("Recording to {}", rec.path().display()));
+            *recorder = Some(rec);
+        }
+        Err(e) => {
+            eprintln!("Couldn't start recording: {e}");
+            display.show_message(&format!("Couldn't start recording: {e}"));
+        }
+    }
+}
@@ -0,0 +1,233 @@
+// Memory viewer drawn as an OSD panel: a hex dump of the 4K of memory with PC,
+// I, the font and the program highlighted, and the bytes at I drawn the way
+// Dxyn would draw them. The dump follows PC while the machine runs. Paused,
+// a cursor moves over it and typed hex digits overwrite the byte under it.
+
+use winit::event::VirtualKeyCode;
+
+use crate::chip8::{Bus, Chip8};
+use crate::osd::Panel;
+use crate::text::{CELL_HEIGHT, CELL_WIDTH};
+
+const BYTES_PER_ROW: usize = 16;
+const DUMP_ROWS: usize = 16;
+const TOTAL_ROWS: usize = Chip8::MEM_SIZE / BYTES_PER_ROW;
+// The dump starts below the header and column numbers
+const FIRST_DUMP_ROW: usize = 2;
+// "0x200  " before the bytes, each byte takes two digits and a space
+const DUMP_COLUMN: usize = 7;
+const SPRITE_COLUMN: usize = DUMP_COLUMN + BYTES_PER_ROW * 3 + 2;
+// Dxyn draws up to 15 rows
+const SPRITE_ROWS: usize = 15;
+const PANEL_COLUMNS: usize = SPRITE_COLUMN + 3 + 8;
+const PANEL_ROWS: usize = FIRST_DUMP_ROW + DUMP_ROWS + 3;
+
+const TEXT: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const FREE: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const FONT: [f32; 4] = [0.95, 0.7, 0.3, 1.0];
+const PROGRAM: [f32; 4] = [0.55, 0.85, 1.0, 1.0];
+const PC: [f32; 4] = [0.1, 0.5, 0.2, 1.0];
+const I: [f32; 4] = [0.5, 0.2, 0.6, 1.0];
+const CURSOR: [f32; 4] = [0.65, 0.55, 0.1, 1.0];
+const PIXEL_OFF: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+
+pub struct MemoryView {
+    open: bool,
+    paused: bool,
+    cursor: usize,
+    // First row of 16 bytes in the dump
+    top: usize,
+    // High nibble typed for the byte under the cursor
+    pending: Option<u8>,
+}
+
+impl MemoryView {
+    pub fn new() -> MemoryView {
+        MemoryView { open: false, paused: false, cursor: Chip8::RESET_VECTOR, top: 0, pending: None }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // The machine halts while the view is paused
+    pub fn is_paused(&self) -> bool {
+        self.open && self.paused
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.paused = false;
+        self.pending = None;
+    }
+
+    // Space pauses and resumes. Paused, the view takes the navigation keys and hex digits, returns
+    // true if it used the key.
+    pub fn handle_key(&mut self, keycode: VirtualKeyCode, chip8: &mut Chip8) -> bool {
+        if !self.open {
+            return false;
+        }
+        if keycode == VirtualKeyCode::Space {
+            self.paused = !self.paused;
+            self.pending = None;
+            return true;
+        }
+        if !self.paused {
+            return false;
+        }
+
+        let state = chip8.state();
+        let cursor = match keycode {
+            VirtualKeyCode::Left => self.cursor.wrapping_sub(1),
+            VirtualKeyCode::Right => self.cursor + 1,
+            VirtualKeyCode::Up => self.cursor.wrapping_sub(BYTES_PER_ROW),
+            VirtualKeyCode::Down => self.cursor + BYTES_PER_ROW,
+            VirtualKeyCode::PageUp => self.cursor.wrapping_sub(BYTES_PER_ROW * DUMP_ROWS),
+            VirtualKeyCode::PageDown => self.cursor + BYTES_PER_ROW * DUMP_ROWS,
+            VirtualKeyCode::Home => state.pc,
+            VirtualKeyCode::End => state.i,
+            VirtualKeyCode::Back => {
+                self.pending = None;
+                return true;
+            }
+            _ => match hex_digit(keycode) {
+                Some(digit) => {
+                    match self.pending.take() {
+                        None => self.pending = Some(digit),
+                        Some(high) => {
+                            chip8.bus_mut().poke(self.cursor, high << 4 | digit);
+                            self.move_cursor(self.cursor + 1);
+                        }
+                    }
+                    return true;
+                }
+                None => return false,
+            }
+        };
+        self.pending = None;
+        self.move_cursor(cursor);
+        true
+    }
+
+    // Lays out the panel for the current state of `chip8`, None while the view is closed
+    pub fn update(&mut self, chip8: &Chip8) -> Option<Panel> {
+        if !self.open {
+            return None;
+        }
+        let state = chip8.state();
+        if !self.paused {
+            self.move_cursor(state.pc);
+        }
+
+        let mut panel = Panel::new(PANEL_COLUMNS, PANEL_ROWS);
+        let status = if self.paused { "PAUSED" } else { "RUNNING" };
+        panel.text(0, 0, &format!("MEMORY  PC {:#05x}  I {:#05x}  {status}", state.pc, state.i), TEXT);
+        let numbers: Vec<String> = (0..BYTES_PER_ROW).map(|n| format!("{n:02X}")).collect();
+        panel.text(DUMP_COLUMN, 1, &numbers.join(" "), FREE);
+        panel.text(SPRITE_COLUMN, 1, "AT I", FREE);
+
+        let program = Chip8::RESET_VECTOR..Chip8::RESET_VECTOR + chip8.program().len();
+        for row in 0..DUMP_ROWS {
+            let line = FIRST_DUMP_ROW + row;
+            let base = (self.top + row) * BYTES_PER_ROW;
+            panel.text(0, line, &format!("{base:#05x}"), FREE);
+            for offset in 0..BYTES_PER_ROW {
+                let addr = base + offset;
+                let column = DUMP_COLUMN + offset * 3;
+                let highlight = if self.paused && addr == self.cursor {
+                    Some(CURSOR)
+                } else if addr == state.pc || addr == state.pc + 1 {
+                    Some(PC)
+                } else if addr == state.i {
+                    Some(I)
+                } else {
+                    None
+                };
+                if let Some(color) = highlight {
+                    panel.highlight(column, line, 2, color);
+                }
+
+                let color = if addr < Chip8::FONT_SIZE {
+                    FONT
+                } else if program.contains(&addr) {
+                    PROGRAM
+                } else {
+                    FREE
+                };
+                let text = match self.pending {
+                    Some(high) if self.paused && addr == self.cursor => format!("{high:X}_"),
+                    _ => format!("{:02X}", chip8.bus().peek(addr)),
+                };
+                panel.text(column, line, &text, color);
+            }
+        }
+
+        // One square pixel per cell, the bytes from I that are past the end of memory stay blank
+        for row in 0..SPRITE_ROWS {
+            let addr = state.i + row;
+            if addr >= Chip8::MEM_SIZE {
+                break;
+            }
+            let line = FIRST_DUMP_ROW + row;
+            let byte = chip8.bus().peek(addr);
+            panel.text(SPRITE_COLUMN, line, &format!("{byte:02X}"), FREE);
+            for bit in 0..8 {
+                let color = if byte & (0x80 >> bit) != 0 { TEXT } else { PIXEL_OFF };
+                let x = ((SPRITE_COLUMN + 3 + bit) * CELL_WIDTH) as f32;
+                let y = (line * CELL_HEIGHT) as f32;
+                panel.fill(x, y, CELL_WIDTH as f32 - 1.0, CELL_HEIGHT as f32 - 1.0, color);
+            }
+        }
+
+        let legend = PANEL_ROWS - 2;
+        let mut column = 0;
+        for (name, color, highlight) in [("PC", TEXT, Some(PC)), ("I", TEXT, Some(I)), ("FONT", FONT, None), ("PROGRAM", PROGRAM, None), ("FREE", FREE, None)] {
+            if let Some(highlight) = highlight {
+                panel.highlight(column, legend, name.len(), highlight);
+            }
+            panel.text(column, legend, name, color);
+            column += name.len() + 2;
+        }
+        let help = if self.paused {
+            "ARROWS PGUP PGDN MOVE  0-F EDIT  HOME PC  END I  SPACE RUN"
+        } else {
+            "SPACE PAUSE  M CLOSE"
+        };
+        panel.text(0, PANEL_ROWS - 1, help, FREE);
+        Some(panel)
+    }
+
+    // Keeps the cursor in memory and its row in the dump
+    fn move_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor % Chip8::MEM_SIZE;
+        let row = self.cursor / BYTES_PER_ROW;
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + DUMP_ROWS {
+            self.top = (row + 1 - DUMP_ROWS).min(TOTAL_ROWS - DUMP_ROWS);
+        }
+    }
+}
+
+fn hex_digit(keycode: VirtualKeyCode) -> Option<u8> {
+    let digit = match keycode {
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => 0,
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => 1,
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => 2,
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => 3,
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => 4,
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => 5,
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => 6,
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => 7,
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => 8,
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => 9,
+        VirtualKeyCode::A => 10,
+        VirtualKeyCode::B => 11,
+        VirtualKeyCode::C => 12,
+        VirtualKeyCode::D => 13,
+        VirtualKeyCode::E => 14,
+        VirtualKeyCode::F => 15,
+        _ => return None
+    };
+    Some(digit)
+}
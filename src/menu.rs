@@ -0,0 +1,302 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use winit::event::VirtualKeyCode;
+
+use crate::chip8::Chip8;
+use crate::crt::Palette;
+use crate::romdb::{self, RomDatabase, RomInfo};
+use crate::text::{TextCanvas, CELL_HEIGHT, CELL_WIDTH};
+
+// The menu canvas is drawn centered over the display. It is a single channel
+// texture, the shader picks colors by value.
+const MENU_TEXT: u8 = 255;
+const MENU_HIGHLIGHT: u8 = 160;
+const MENU_BACKDROP: u8 = 96;
+const MENU_COLUMNS: usize = 44;
+const MENU_LINES: usize = 18;
+pub const MENU_WIDTH: usize = MENU_COLUMNS * CELL_WIDTH + 4;
+pub const MENU_HEIGHT: usize = MENU_LINES * CELL_HEIGHT + 4;
+
+const SAVE_SLOTS: u8 = 4;
+const MIN_SPEED: usize = 1;
+const MAX_SPEED: usize = 200;
+
+// ROMs in the launcher, the rest of the lines show the selected ROM's metadata
+const LIST_LINES: usize = 12;
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "c8", "rom"];
+
+// What the user picked, applied by the event loop
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuAction {
+    None,
+    Close,
+    Reset,
+    SaveState(u8),
+    LoadState(u8),
+    SetSpeed(usize),
+    SetPalette(Palette),
+    LoadRom(PathBuf),
+    Quit,
+}
+
+// Values the pause menu shows and adjusts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MenuSettings {
+    pub instructions_per_frame: usize,
+    pub palette: Palette,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Screen {
+    Pause,
+    Launcher,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PauseItem {
+    Resume,
+    Reset,
+    SaveState,
+    LoadState,
+    Speed,
+    Palette,
+    LoadRom,
+    Quit,
+}
+
+const PAUSE_ITEMS: [PauseItem; 8] = [
+    PauseItem::Resume,
+    PauseItem::Reset,
+    PauseItem::SaveState,
+    PauseItem::LoadState,
+    PauseItem::Speed,
+    PauseItem::Palette,
+    PauseItem::LoadRom,
+    PauseItem::Quit,
+];
+
+struct RomEntry {
+    path: PathBuf,
+    name: String,
+    size: usize,
+    sha1: String,
+    info: Option<RomInfo>,
+}
+
+pub struct Menu {
+    screen: Option<Screen>,
+    selected: usize,
+    scroll: usize,
+    slot: u8,
+    rom_dir: PathBuf,
+    roms: Vec<RomEntry>,
+    canvas: TextCanvas,
+}
+
+impl Menu {
+    pub fn new(rom_dir: PathBuf) -> Self {
+        Menu {
+            screen: None,
+            selected: 0,
+            scroll: 0,
+            slot: 0,
+            rom_dir,
+            roms: Vec::new(),
+            canvas: TextCanvas::new(MENU_WIDTH, MENU_HEIGHT),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.screen.is_some()
+    }
+
+    pub fn open_pause(&mut self) {
+        self.screen = Some(Screen::Pause);
+        self.selected = 0;
+    }
+
+    // Rescans the ROM directory so freshly built ROMs show up
+    pub fn open_launcher(&mut self) {
+        self.roms = scan_roms(&self.rom_dir);
+        self.screen = Some(Screen::Launcher);
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    pub fn close(&mut self) {
+        self.screen = None;
+    }
+
+    pub fn handle_key(&mut self, keycode: VirtualKeyCode, settings: &MenuSettings) -> MenuAction {
+        let Some(screen) = self.screen else { return MenuAction::None };
+        let count = match screen {
+            Screen::Pause => PAUSE_ITEMS.len(),
+            Screen::Launcher => self.roms.len(),
+        };
+
+        match keycode {
+            VirtualKeyCode::Escape => {
+                self.close();
+                return MenuAction::Close;
+            }
+            VirtualKeyCode::Up if count > 0 => self.selected = (self.selected + count - 1) % count,
+            VirtualKeyCode::Down if count > 0 => self.selected = (self.selected + 1) % count,
+            VirtualKeyCode::PageUp => self.selected = self.selected.saturating_sub(LIST_LINES),
+            VirtualKeyCode::PageDown if count > 0 => self.selected = (self.selected + LIST_LINES).min(count - 1),
+            VirtualKeyCode::Left | VirtualKeyCode::Right if screen == Screen::Pause => {
+                return self.adjust(keycode == VirtualKeyCode::Right, settings);
+            }
+            VirtualKeyCode::Back if screen == Screen::Launcher => self.open_pause(),
+            VirtualKeyCode::Return | VirtualKeyCode::Space => return self.activate(screen),
+            _ => ()
+        }
+        MenuAction::None
+    }
+
+    fn adjust(&mut self, increase: bool, settings: &MenuSettings) -> MenuAction {
+        match PAUSE_ITEMS[self.selected] {
+            PauseItem::SaveState | PauseItem::LoadState => {
+                self.slot = if increase { (self.slot + 1) % SAVE_SLOTS } else { (self.slot + SAVE_SLOTS - 1) % SAVE_SLOTS };
+                MenuAction::None
+            }
+            PauseItem::Speed => {
+                let speed = settings.instructions_per_frame;
+                let speed = if increase { speed + 1 } else { speed.saturating_sub(1) };
+                MenuAction::SetSpeed(speed.clamp(MIN_SPEED, MAX_SPEED))
+            }
+            PauseItem::Palette => {
+                let i = Palette::ALL.iter().position(|p| *p == settings.palette).unwrap_or(0);
+                let n = Palette::ALL.len();
+                MenuAction::SetPalette(Palette::ALL[if increase { (i + 1) % n } else { (i + n - 1) % n }])
+            }
+            _ => MenuAction::None
+        }
+    }
+
+    fn activate(&mut self, screen: Screen) -> MenuAction {
+        let action = match screen {
+            Screen::Pause => match PAUSE_ITEMS[self.selected] {
+                PauseItem::Resume => MenuAction::Close,
+                PauseItem::Reset => MenuAction::Reset,
+                PauseItem::SaveState => MenuAction::SaveState(self.slot),
+                PauseItem::LoadState => MenuAction::LoadState(self.slot),
+                PauseItem::LoadRom => {
+                    self.open_launcher();
+                    return MenuAction::None;
+                }
+                PauseItem::Quit => MenuAction::Quit,
+                PauseItem::Speed | PauseItem::Palette => return MenuAction::None,
+            },
+            Screen::Launcher => match self.roms.get(self.selected) {
+                Some(rom) => MenuAction::LoadRom(rom.path.clone()),
+                None => return MenuAction::None,
+            },
+        };
+        self.close();
+        action
+    }
+
+    // Redraws the canvas, all zero while the menu is closed
+    pub fn draw(&mut self, settings: &MenuSettings) -> &TextCanvas {
+        self.canvas.clear();
+        match self.screen {
+            None => (),
+            Some(Screen::Pause) => {
+                self.begin("PAUSED");
+                for (i, item) in PAUSE_ITEMS.iter().enumerate() {
+                    let label = match item {
+                        PauseItem::Resume => "Resume".to_string(),
+                        PauseItem::Reset => "Reset".to_string(),
+                        PauseItem::SaveState => format!("Save state         < slot {} >", self.slot + 1),
+                        PauseItem::LoadState => format!("Load state         < slot {} >", self.slot + 1),
+                        PauseItem::Speed => format!("Speed              < {} per frame >", settings.instructions_per_frame),
+                        PauseItem::Palette => format!("Palette            < {:?} >", settings.palette),
+                        PauseItem::LoadRom => "Load ROM...".to_string(),
+                        PauseItem::Quit => "Quit".to_string(),
+                    };
+                    self.line(2 + i, &label, i == self.selected);
+                }
+                self.line(MENU_LINES - 1, "Enter select  Left/Right change  Esc close", false);
+            }
+            Some(Screen::Launcher) => {
+                self.begin(&format!("LOAD ROM  {}", self.rom_dir.display()));
+                if self.roms.is_empty() {
+                    self.line(2, "No ROMs found", false);
+                }
+
+                // Keep the selection in view
+                if self.selected < self.scroll {
+                    self.scroll = self.selected;
+                } else if self.selected >= self.scroll + LIST_LINES {
+                    self.scroll = self.selected + 1 - LIST_LINES;
+                }
+                for row in 0..LIST_LINES.min(self.roms.len() - self.scroll) {
+                    let i = self.scroll + row;
+                    let rom = &self.roms[i];
+                    let title = rom.info.as_ref().map_or("", |info| info.title.as_str());
+                    let label = format!("{:<18.18} {}", rom.name, title);
+                    self.line(1 + row, &label, i == self.selected);
+                }
+
+                for (row, text) in self.rom_details().iter().enumerate() {
+                    self.line(LIST_LINES + 2 + row, text, false);
+                }
+            }
+        }
+        &self.canvas
+    }
+
+    fn rom_details(&self) -> Vec<String> {
+        let Some(rom) = self.roms.get(self.selected) else { return Vec::new() };
+        let size = format!("{} bytes, sha1 {}", rom.size, &rom.sha1[..8]);
+        match &rom.info {
+            Some(info) => {
+                let mut origin = info.authors.join(", ");
+                if let Some(release) = &info.release {
+                    origin = if origin.is_empty() { release.clone() } else { format!("{origin}, {release}") };
+                }
+                let platform = info.platform.as_ref().map_or("Unknown platform".to_string(), |p| p.name.clone());
+                vec![info.title.clone(), origin, format!("{platform}, {size}")]
+            }
+            None if rom.size > Chip8::MAX_PROGRAM_SIZE => vec!["Too large to load".to_string(), size],
+            None => vec!["Not in the ROM database".to_string(), size],
+        }
+    }
+
+    fn begin(&mut self, title: &str) {
+        self.canvas.fill_rect(0, 0, self.canvas.width(), self.canvas.height(), MENU_BACKDROP);
+        self.line(0, title, false);
+    }
+
+    fn line(&mut self, line: usize, text: &str, selected: bool) {
+        let y = 2 + line * CELL_HEIGHT;
+        if selected {
+            self.canvas.fill_rect(0, y - 1, self.canvas.width(), CELL_HEIGHT, MENU_HIGHLIGHT);
+        }
+        let text: String = text.chars().take(MENU_COLUMNS).collect();
+        self.canvas.draw_text(2, y, &text, MENU_TEXT);
+    }
+}
+
+// Files with a ROM extension, sorted by name. Unreadable files are skipped.
+fn scan_roms(dir: &Path) -> Vec<RomEntry> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut roms: Vec<RomEntry> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().and_then(|e| e.to_str()).is_some_and(|e| ROM_EXTENSIONS.contains(&e.to_lowercase().as_str())))
+        .filter_map(|path| {
+            let data = fs::read(&path).ok()?;
+            let sha1 = romdb::sha1_hex(&data);
+            Some(RomEntry {
+                name: path.file_name()?.to_string_lossy().into_owned(),
+                size: data.len(),
+                info: RomDatabase::bundled().identify_sha1(&sha1),
+                sha1,
+                path,
+            })
+        })
+        .collect();
+    roms.sort_by(|a, b| a.name.cmp(&b.name));
+    roms
+}
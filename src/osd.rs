@@ -0,0 +1,363 @@
+// On-screen display drawn over the emulator in a render pass of its own:
+// transient messages stacked in the bottom left corner, optional HUD lines
+// in the top left and a panel of colored text in the top right. Every glyph
+// and backdrop is an instanced quad sampling a font atlas built from the 5x7
+// font, so text of any length costs one draw.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use wgpu::{
+    BindGroup, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource,
+    BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, Extent3d,
+    PipelineLayoutDescriptor, PrimitiveState, RenderPipelineDescriptor, ShaderModuleDescriptor,
+    ShaderStages, TextureDescriptor, TextureSampleType, TextureUsages, TextureViewDescriptor,
+    VertexBufferLayout, VertexStepMode, util::BufferInitDescriptor, util::DeviceExt
+};
+
+use crate::gpu;
+use crate::text::{self, TextCanvas, CELL_HEIGHT, CELL_WIDTH, FIRST_GLYPH, GLYPH_COUNT, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+// Window pixels per font pixel
+const SCALE: f32 = 2.0;
+const MARGIN: f32 = 16.0;
+// Backdrop around the text, in font pixels
+const PADDING: f32 = 2.0;
+
+const MESSAGE_DURATION: Duration = Duration::from_millis(2500);
+// Messages fade out over the end of their duration
+const MESSAGE_FADE: Duration = Duration::from_millis(500);
+const MAX_MESSAGES: usize = 4;
+
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const HUD_BACKDROP: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const MESSAGE_BACKDROP: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+const PANEL_BACKDROP: [f32; 4] = [0.0, 0.0, 0.0, 0.85];
+
+// The atlas is one row of cells, the glyphs in font order followed by a solid cell for backdrops
+const SOLID_CELL: usize = GLYPH_COUNT;
+const ATLAS_WIDTH: usize = (GLYPH_COUNT + 1) * CELL_WIDTH;
+
+// rect, atlas rect and color as 4 floats each
+const QUAD_FLOATS: usize = 12;
+const QUAD_BYTES: usize = QUAD_FLOATS * 4;
+const INITIAL_CAPACITY: usize = 256;
+
+const QUAD_ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Float32x4];
+
+struct Message {
+    text: String,
+    expires: Instant,
+}
+
+// A grid of text cells where every run of text and every filled rectangle has a color of its own,
+// e.g. the memory viewer. Colors are straight alpha, the OSD premultiplies them.
+pub struct Panel {
+    columns: usize,
+    rows: usize,
+    // Rectangles in font pixels from the top left of the grid, drawn below the text
+    fills: Vec<([f32; 4], [f32; 4])>,
+    texts: Vec<(usize, usize, String, [f32; 4])>,
+}
+
+impl Panel {
+    pub fn new(columns: usize, rows: usize) -> Panel {
+        Panel { columns, rows, fills: Vec::new(), texts: Vec::new() }
+    }
+
+    pub fn text(&mut self, column: usize, row: usize, text: &str, color: [f32; 4]) {
+        self.texts.push((column, row, text.to_string(), color));
+    }
+
+    // Fills `columns` cells from (column, row) behind their text
+    pub fn highlight(&mut self, column: usize, row: usize, columns: usize, color: [f32; 4]) {
+        let rect = [(column * CELL_WIDTH) as f32 - 1.0, (row * CELL_HEIGHT) as f32 - 1.0, (columns * CELL_WIDTH) as f32 + 1.0, CELL_HEIGHT as f32 + 1.0];
+        self.fills.push((rect, color));
+    }
+
+    // Fills a rectangle given in font pixels from the top left of the grid
+    pub fn fill(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.fills.push(([x, y, width, height], color));
+    }
+}
+
+pub struct Osd {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: BindGroup,
+    screen_buffer: wgpu::Buffer,
+    quad_buffer: wgpu::Buffer,
+    quad_capacity: usize,
+    quad_count: u32,
+    quads: Vec<f32>,
+    messages: VecDeque<Message>,
+    hud: Vec<String>,
+    panel: Option<Panel>,
+}
+
+impl Osd {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat) -> Osd {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("OSD shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("./shaders/osd.wgsl")))
+        });
+
+        let mut atlas = TextCanvas::new(ATLAS_WIDTH, CELL_HEIGHT);
+        let glyphs: String = (0..GLYPH_COUNT).map(|i| char::from(FIRST_GLYPH as u8 + i as u8)).collect();
+        atlas.draw_text(0, 0, &glyphs, 255);
+        atlas.fill_rect(SOLID_CELL * CELL_WIDTH, 0, CELL_WIDTH, CELL_HEIGHT, 255);
+        let atlas_texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: Some("OSD font atlas"),
+                size: Extent3d { width: atlas.width() as u32, height: atlas.height() as u32, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: TextureUsages::TEXTURE_BINDING
+            },
+            atlas.pixels()
+        );
+
+        let screen_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("OSD screen size"),
+            contents: &screen_bytes(1.0, 1.0),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    visibility: ShaderStages::VERTEX
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    visibility: ShaderStages::FRAGMENT
+                }
+            ]
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&atlas_texture.create_view(&TextureViewDescriptor::default()))
+                }
+            ]
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+            ..Default::default()
+        });
+
+        let target_state = ColorTargetState {
+            blend: gpu::PREMULTIPLIED_ALPHA,
+            format,
+            write_mask: ColorWrites::default()
+        };
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("OSD pipeline"),
+            depth_stencil: None,
+            multiview: None,
+            layout: Some(&layout),
+            multisample: wgpu::MultisampleState::default(),
+            primitive: PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleStrip, ..Default::default() },
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: QUAD_BYTES as u64,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &QUAD_ATTRIBUTES
+                }]
+            },
+            fragment: Some(wgpu::FragmentState { module: &shader, entry_point: "fs_main", targets: &[Some(target_state)] }),
+        });
+
+        Osd {
+            pipeline,
+            bind_group,
+            screen_buffer,
+            quad_buffer: create_quad_buffer(device, INITIAL_CAPACITY),
+            quad_capacity: INITIAL_CAPACITY,
+            quad_count: 0,
+            quads: Vec::new(),
+            messages: VecDeque::new(),
+            hud: Vec::new(),
+            panel: None,
+        }
+    }
+
+    // Shows `text` for a few seconds below the messages already shown. Repeating the newest
+    // message restarts its timer instead of stacking a copy.
+    pub fn show_message(&mut self, text: &str) {
+        let expires = Instant::now() + MESSAGE_DURATION;
+        match self.messages.back_mut() {
+            Some(last) if last.text == text => last.expires = expires,
+            _ => {
+                self.messages.push_back(Message { text: text.to_string(), expires });
+                if self.messages.len() > MAX_MESSAGES {
+                    self.messages.pop_front();
+                }
+            }
+        }
+    }
+
+    // Lines shown until they are replaced, an empty list hides the HUD
+    pub fn set_hud(&mut self, lines: Vec<String>) {
+        self.hud = lines;
+    }
+
+    // Shown until it is replaced, None hides the panel
+    pub fn set_panel(&mut self, panel: Option<Panel>) {
+        self.panel = panel;
+    }
+
+    // Lays out the text for a target of `width` x `height` pixels and uploads it, call before `draw`
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) {
+        let now = Instant::now();
+        self.messages.retain(|message| message.expires > now);
+
+        self.quads.clear();
+        if !self.hud.is_empty() {
+            push_block(&mut self.quads, MARGIN, MARGIN, &self.hud, HUD_BACKDROP, 1.0);
+        }
+        if let Some(panel) = &self.panel {
+            let panel_width = (panel.columns * CELL_WIDTH) as f32 + 2.0 * PADDING - 1.0;
+            let x = (width as f32 - MARGIN - panel_width * SCALE).max(MARGIN);
+            push_panel(&mut self.quads, x, MARGIN, panel);
+        }
+
+        let line_height = CELL_HEIGHT as f32 * SCALE;
+        let block_height = line_height + 2.0 * PADDING * SCALE;
+        let mut y = height as f32 - MARGIN - block_height;
+        for message in self.messages.iter().rev() {
+            let remaining = message.expires.saturating_duration_since(now);
+            let alpha = (remaining.as_secs_f32() / MESSAGE_FADE.as_secs_f32()).min(1.0);
+            push_block(&mut self.quads, MARGIN, y, std::slice::from_ref(&message.text), MESSAGE_BACKDROP, alpha);
+            y -= block_height + SCALE;
+        }
+
+        self.quad_count = (self.quads.len() / QUAD_FLOATS) as u32;
+        if self.quad_count == 0 {
+            return;
+        }
+
+        let count = self.quad_count as usize;
+        if count > self.quad_capacity {
+            self.quad_capacity = count.next_power_of_two();
+            self.quad_buffer = create_quad_buffer(device, self.quad_capacity);
+        }
+        let bytes: Vec<u8> = self.quads.iter().flat_map(|f| f.to_ne_bytes()).collect();
+        queue.write_buffer(&self.quad_buffer, 0, &bytes);
+        queue.write_buffer(&self.screen_buffer, 0, &screen_bytes(width as f32, height as f32));
+    }
+
+    // Draws what the last `prepare` laid out
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.quad_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.quad_buffer.slice(..(self.quad_count as usize * QUAD_BYTES) as u64));
+        render_pass.draw(0..4, 0..self.quad_count);
+    }
+}
+
+// Lines of text on a backdrop with its top left corner at (x, y) in window pixels
+fn push_block(quads: &mut Vec<f32>, x: f32, y: f32, lines: &[String], backdrop: [f32; 4], alpha: f32) {
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    let width = (columns * CELL_WIDTH) as f32 + 2.0 * PADDING - 1.0;
+    let height = (lines.len() * CELL_HEIGHT) as f32 + 2.0 * PADDING - 1.0;
+    let solid = [(SOLID_CELL * CELL_WIDTH) as f32, 0.0, 1.0, 1.0];
+    push_quad(quads, [x, y, width * SCALE, height * SCALE], solid, fade(backdrop, alpha));
+
+    let color = fade(TEXT_COLOR, alpha);
+    for (row, line) in lines.iter().enumerate() {
+        let ty = y + (PADDING + (row * CELL_HEIGHT) as f32) * SCALE;
+        for (column, c) in line.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let tx = x + (PADDING + (column * CELL_WIDTH) as f32) * SCALE;
+            let glyph = [(text::glyph_index(c) * CELL_WIDTH) as f32, 0.0, GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32];
+            push_quad(quads, [tx, ty, GLYPH_WIDTH as f32 * SCALE, GLYPH_HEIGHT as f32 * SCALE], glyph, color);
+        }
+    }
+}
+
+// The panel with its top left corner at (x, y) in window pixels
+fn push_panel(quads: &mut Vec<f32>, x: f32, y: f32, panel: &Panel) {
+    let width = (panel.columns * CELL_WIDTH) as f32 + 2.0 * PADDING - 1.0;
+    let height = (panel.rows * CELL_HEIGHT) as f32 + 2.0 * PADDING - 1.0;
+    let solid = [(SOLID_CELL * CELL_WIDTH) as f32, 0.0, 1.0, 1.0];
+    push_quad(quads, [x, y, width * SCALE, height * SCALE], solid, fade(PANEL_BACKDROP, 1.0));
+
+    let (left, top) = (x + PADDING * SCALE, y + PADDING * SCALE);
+    for ([fx, fy, fw, fh], color) in &panel.fills {
+        push_quad(quads, [left + fx * SCALE, top + fy * SCALE, fw * SCALE, fh * SCALE], solid, fade(*color, 1.0));
+    }
+    for (column, row, text, color) in &panel.texts {
+        let ty = top + (row * CELL_HEIGHT) as f32 * SCALE;
+        for (i, c) in text.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let tx = left + ((column + i) * CELL_WIDTH) as f32 * SCALE;
+            let glyph = [(text::glyph_index(c) * CELL_WIDTH) as f32, 0.0, GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32];
+            push_quad(quads, [tx, ty, GLYPH_WIDTH as f32 * SCALE, GLYPH_HEIGHT as f32 * SCALE], glyph, fade(*color, 1.0));
+        }
+    }
+}
+
+fn push_quad(quads: &mut Vec<f32>, rect: [f32; 4], atlas_rect: [f32; 4], color: [f32; 4]) {
+    quads.extend_from_slice(&rect);
+    quads.extend_from_slice(&atlas_rect);
+    quads.extend_from_slice(&color);
+}
+
+// Premultiplies the color with its alpha faded by `alpha`
+fn fade(color: [f32; 4], alpha: f32) -> [f32; 4] {
+    let a = color[3] * alpha;
+    [color[0] * a, color[1] * a, color[2] * a, a]
+}
+
+fn screen_bytes(width: f32, height: f32) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&width.to_ne_bytes());
+    bytes[4..].copy_from_slice(&height.to_ne_bytes());
+    bytes
+}
+
+fn create_quad_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("OSD quads"),
+        size: (capacity * QUAD_BYTES) as u64,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false
+    })
+}
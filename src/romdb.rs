@@ -0,0 +1,229 @@
+// Identifies programs by the SHA-1 of the ROM and looks up the settings they
+// need. The database uses the JSON files of the community CHIP-8 database
+// (https://github.com/chip-8/chip-8-database): programs.json, sha1-hashes.json
+// mapping hashes to program indices, and platforms.json with the quirks of
+// each platform. A small database of the bundled ROMs is compiled in, the
+// files of the full database load with `RomDatabase::from_json`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::chip8::{Config, Quirks};
+
+/// Lowercase hex SHA-1 of a ROM, the key of the database
+pub fn sha1_hex(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+/// A parsed ROM database
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+    platforms: Vec<Platform>,
+}
+
+/// A platform of platforms.json
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Platform {
+    pub id: String,
+    pub name: String,
+    /// Instructions per 60Hz frame programs for this platform expect
+    pub default_tickrate: Option<u32>,
+    #[serde(default)]
+    quirks: QuirkFlags,
+}
+
+/// Colors of a program as RGB, `pixels[0]` is the background and `pixels[1]` the foreground
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Colors {
+    pub pixels: Vec<[u8; 3]>,
+    pub buzzer: Option<[u8; 3]>,
+    pub silence: Option<[u8; 3]>,
+}
+
+/// Everything the database knows about one ROM, with the quirks and tick rate resolved from the
+/// ROM, its platform and the defaults
+#[derive(Clone, Debug)]
+pub struct RomInfo {
+    pub sha1: String,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    pub description: Option<String>,
+    /// The platform the ROM was written for, the first the database lists
+    pub platform: Option<Platform>,
+    pub quirks: Quirks,
+    /// Instructions per 60Hz frame
+    pub tickrate: Option<u32>,
+    pub colors: Option<Colors>,
+    /// Chip-8 keys by the role they play in the game: "up", "down", "left", "right", "a", "b"
+    /// and the same prefixed with "player2"
+    pub keys: BTreeMap<String, u8>,
+}
+
+impl Platform {
+    /// The interpreter defaults with the quirks of the platform
+    pub fn quirks(&self) -> Quirks {
+        let mut quirks = Quirks::default();
+        self.quirks.apply(&mut quirks);
+        quirks
+    }
+}
+
+impl RomInfo {
+    /// Title followed by the authors, e.g. "Blinky by Hans Christian Egeberg"
+    pub fn display_name(&self) -> String {
+        if self.authors.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} by {}", self.title, self.authors.join(", "))
+        }
+    }
+
+    /// `config` with the quirks of the ROM
+    pub fn apply(&self, config: Config) -> Config {
+        Config { quirks: self.quirks, ..config }
+    }
+}
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    description: Option<String>,
+    release: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    colors: Option<ColorStrings>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkFlags>,
+}
+
+#[derive(Deserialize)]
+struct ColorStrings {
+    #[serde(default)]
+    pixels: Vec<String>,
+    buzzer: Option<String>,
+    silence: Option<String>,
+}
+
+// Quirks as the database spells them. Platforms list all of them, quirkyPlatforms only the
+// ones a ROM needs changed.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuirkFlags {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+impl QuirkFlags {
+    fn apply(&self, quirks: &mut Quirks) {
+        let flags = [
+            (self.shift, &mut quirks.shift),
+            (self.memory_increment_by_x, &mut quirks.memory_increment_by_x),
+            (self.memory_leave_i_unchanged, &mut quirks.memory_leave_i_unchanged),
+            (self.wrap, &mut quirks.wrap),
+            (self.jump, &mut quirks.jump),
+            (self.vblank, &mut quirks.vblank),
+            (self.logic, &mut quirks.logic),
+        ];
+        for (flag, quirk) in flags {
+            if let Some(flag) = flag {
+                *quirk = flag;
+            }
+        }
+    }
+}
+
+// "#rrggbb", anything else is ignored
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+impl RomDatabase {
+    /// The database of the ROMs in roms/, parsed on first use
+    pub fn bundled() -> &'static RomDatabase {
+        static BUNDLED: OnceLock<RomDatabase> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            RomDatabase::from_json(
+                include_str!("../roms/database/programs.json"),
+                include_str!("../roms/database/sha1-hashes.json"),
+                include_str!("../roms/database/platforms.json"),
+            ).expect("Bundled ROM database is invalid")
+        })
+    }
+
+    /// Parses the contents of programs.json, sha1-hashes.json and platforms.json
+    pub fn from_json(programs: &str, hashes: &str, platforms: &str) -> Result<Self, serde_json::Error> {
+        Ok(RomDatabase {
+            programs: serde_json::from_str(programs)?,
+            hashes: serde_json::from_str(hashes)?,
+            platforms: serde_json::from_str(platforms)?,
+        })
+    }
+
+    pub fn platform(&self, id: &str) -> Option<&Platform> {
+        self.platforms.iter().find(|p| p.id == id)
+    }
+
+    /// Looks up a ROM by its contents, see [`RomDatabase::identify_sha1`]
+    pub fn identify(&self, rom: &[u8]) -> Option<RomInfo> {
+        self.identify_sha1(&sha1_hex(rom))
+    }
+
+    /// Looks up a ROM by its lowercase hex SHA-1. The quirks start from the interpreter defaults,
+    /// the quirks of the ROM's platform and then the ROM's own overrides for that platform apply.
+    pub fn identify_sha1(&self, sha1: &str) -> Option<RomInfo> {
+        let program = self.programs.get(*self.hashes.get(sha1)?)?;
+        let rom = program.roms.get(sha1)?;
+        let platform = rom.platforms.first().and_then(|id| self.platform(id));
+
+        let mut quirks = Quirks::default();
+        if let Some(platform) = platform {
+            quirks = platform.quirks();
+            if let Some(overrides) = rom.quirky_platforms.get(&platform.id) {
+                overrides.apply(&mut quirks);
+            }
+        }
+
+        Some(RomInfo {
+            sha1: sha1.to_string(),
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            release: program.release.clone(),
+            description: program.description.clone(),
+            platform: platform.cloned(),
+            quirks,
+            tickrate: rom.tickrate.or_else(|| platform.and_then(|p| p.default_tickrate)),
+            colors: rom.colors.as_ref().map(|colors| Colors {
+                pixels: colors.pixels.iter().filter_map(|c| parse_color(c)).collect(),
+                buzzer: colors.buzzer.as_deref().and_then(parse_color),
+                silence: colors.silence.as_deref().and_then(parse_color),
+            }),
+            keys: rom.keys.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,77 @@
+use super::{sha1_hex, RomDatabase};
+use crate::chip8::Quirks;
+
+const PROGRAMS: &str = r##"[
+    {"title": "Other", "roms": {}},
+    {
+        "title": "Game",
+        "authors": ["A", "B"],
+        "roms": {
+            "0000000000000000000000000000000000000001": {
+                "platforms": ["chip48", "originalChip8"],
+                "tickrate": 20,
+                "colors": {"pixels": ["#000000", "#ff8000", "bad"], "buzzer": "#102030"},
+                "keys": {"up": 5, "a": 6},
+                "quirkyPlatforms": {"chip48": {"shift": false}}
+            },
+            "0000000000000000000000000000000000000002": {"platforms": ["unknown"]}
+        }
+    }
+]"##;
+const HASHES: &str = r#"{
+    "0000000000000000000000000000000000000001": 1,
+    "0000000000000000000000000000000000000002": 1
+}"#;
+
+#[test]
+fn test_sha1() {
+    assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+}
+
+#[test]
+fn test_identify() {
+    let platforms = include_str!("../../roms/database/platforms.json");
+    let db = RomDatabase::from_json(PROGRAMS, HASHES, platforms).unwrap();
+    let info = db.identify_sha1("0000000000000000000000000000000000000001").unwrap();
+    assert_eq!(info.display_name(), "Game by A, B");
+    assert_eq!(info.platform.unwrap().id, "chip48");
+    assert_eq!(info.tickrate, Some(20));
+    assert_eq!(info.keys["up"], 5);
+    let colors = info.colors.unwrap();
+    assert_eq!(colors.pixels, [[0, 0, 0], [0xff, 0x80, 0]]);
+    assert_eq!(colors.buzzer, Some([0x10, 0x20, 0x30]));
+
+    // chip48 quirks with the ROM's override
+    assert_eq!(info.quirks, Quirks {
+        shift: false,
+        memory_increment_by_x: true,
+        memory_leave_i_unchanged: false,
+        wrap: false,
+        jump: true,
+        vblank: false,
+        logic: false,
+    });
+
+    // Unknown platforms keep the interpreter defaults
+    let info = db.identify_sha1("0000000000000000000000000000000000000002").unwrap();
+    assert!(info.platform.is_none());
+    assert_eq!(info.quirks, Quirks::default());
+    assert_eq!(info.tickrate, None);
+
+    assert!(db.identify(b"not in the database").is_none());
+}
+
+#[test]
+fn test_bundled() {
+    let db = RomDatabase::bundled();
+    let info = db.identify(include_bytes!("../../roms/blinky.rom")).unwrap();
+    assert_eq!(info.display_name(), "Blinky by Hans Christian Egeberg");
+    assert_eq!(info.platform.as_ref().unwrap().id, "chip48");
+    assert_eq!(info.tickrate, Some(30));
+    assert_eq!(info.keys["left"], 7);
+    assert!(info.quirks.shift && info.quirks.memory_increment_by_x);
+
+    let info = db.identify(include_bytes!("../../roms/trip8.rom")).unwrap();
+    assert_eq!(info.display_name(), "Trip8 Demo by Revival Studios");
+    assert!(!info.quirks.vblank);
+}
@@ -1,7 +1,55 @@
+struct Params {
+    foreground: vec4<f32>,
+    background: vec4<f32>,
+    distortion: f32,
+    scanlineDensity: f32,
+    scanlineIntensity: f32,
+    enabled: u32,
+    rollingBar: f32,
+    noise: f32,
+    vignette: f32,
+}
+
+struct Frame {
+    time: f32,
+    deltaTime: f32,
+    resolution: vec2<f32>,
+    pixelPerfect: u32,
+}
+
 @group(0) @binding(0) var texSampler: sampler;
 @group(0) @binding(1) var pixels: texture_2d<f32>;
 @group(0) @binding(2) var overlaySampler: sampler;
 @group(0) @binding(3) var overlayTexture: texture_2d<f32>;
+@group(0) @binding(4) var<uniform> params: Params;
+@group(0) @binding(5) var<uniform> frame: Frame;
+@group(0) @binding(6) var menuTexture: texture_2d<f32>;
+
+let MENU_SCALE: f32 = 2.0;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// The menu texture is centered and holds text at full intensity, the selected line's bar
+// above half and the backdrop below
+fn menu(windowUv: vec2<f32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(menuTexture));
+    let origin = floor((frame.resolution - size * MENU_SCALE) * 0.5);
+    let px = (windowUv * frame.resolution - origin) / MENU_SCALE;
+    if (any(px < vec2(0.0)) || any(px >= size)) {
+        return vec4(0.0);
+    }
+
+    let v = textureLoad(menuTexture, vec2<i32>(px), 0).r;
+    if (v > 0.9) {
+        return vec4(0.95, 0.95, 0.95, 1.0);
+    }
+    if (v > 0.5) {
+        return vec4(0.2, 0.3, 0.45, 0.9);
+    }
+    return vec4(0.0, 0.0, 0.0, select(0.0, 0.8, v > 0.0));
+}
 
 @fragment
 fn main(
@@ -9,28 +57,47 @@ fn main(
     @location(1) windowUv: vec2<f32>
 ) -> @location(0) vec4<f32> {
 
-    let uv = displayUv - 0.5;
-    let uvL = length(uv);
-    let distL = uvL * (1.0 + 0.15 * uvL * uvL);
-    let distUv = (uv / uvL * distL) + vec2(0.5);
+    var distUv = displayUv;
+    if (params.enabled != 0u && frame.pixelPerfect == 0u) {
+        let uv = displayUv - 0.5;
+        let uvL = max(length(uv), 0.0001);
+        let distL = uvL * (1.0 + params.distortion * uvL * uvL);
+        distUv = (uv / uvL * distL) + vec2(0.5);
+    }
 
     var p: f32 = textureSample(pixels, texSampler, distUv).r;
     if (any(distUv < vec2(0.0)) || any(distUv > vec2(1.0))) {
         p = 0.0;
     }
 
-    var c: vec4<f32> = select(
-        vec4(0.0, 0.004, 0.002, 1.0),
-        vec4(0.1, 0.5, 0.1, 1.0),
-        p > 0.0
-    );
+    var c: vec4<f32> = mix(params.background, params.foreground, p);
+
+    if (params.enabled != 0u) {
+        let wave = sin(distUv.y * params.scanlineDensity) * 0.5;
+        let raster = clamp(1.0 - params.scanlineIntensity * (0.3 - wave), 0.0, 1.0);
+        c = vec4(c.rgb * raster * raster, 1.0);
+
+        // A faint bright band rolling down the screen
+        let bar = fract(distUv.y * 0.5 - frame.time * 0.15);
+        let band = smoothstep(0.0, 0.08, bar) * (1.0 - smoothstep(0.08, 0.2, bar));
+        c = vec4(c.rgb + params.rollingBar * band * params.foreground.rgb, 1.0);
 
-    let raster = clamp(sin(distUv.y * 600.0) * 0.5 + 0.7, 0.0, 1.0);
-    c = vec4(c.rgb * raster * raster, 1.0);
+        let n = hash(distUv * 512.0 + vec2(frame.time * 61.0, frame.time * 17.0));
+        c = vec4(max(c.rgb + (n - 0.5) * params.noise, vec3(0.0)), 1.0);
+
+        let d = length(displayUv - 0.5);
+        let flicker = 1.0 + 0.04 * sin(frame.time * 113.0) * sin(frame.time * 7.0);
+        c = vec4(c.rgb * clamp(1.0 - params.vignette * d * d * 2.0 * flicker, 0.0, 1.0), 1.0);
+    }
 
     let overlay = textureSample(overlayTexture, overlaySampler, windowUv);
-    let overlay = vec4(overlay.rgb * overlay.a, overlay.a);
-    c = c * (1.0 - overlay.a) + overlay;
+    if (frame.pixelPerfect == 0u) {
+        let overlay = vec4(overlay.rgb * overlay.a, overlay.a);
+        c = c * (1.0 - overlay.a) + overlay;
+    }
+
+    let menuColor = menu(windowUv);
+    c = vec4(mix(c.rgb, menuColor.rgb, menuColor.a), c.a);
 
     return c;
-}
\ No newline at end of file
+}
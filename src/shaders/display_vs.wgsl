@@ -1,3 +1,12 @@
+struct Frame {
+    time: f32,
+    deltaTime: f32,
+    resolution: vec2<f32>,
+    pixelPerfect: u32,
+}
+
+@group(0) @binding(5) var<uniform> frame: Frame;
+
 struct Output {
     @builtin(position) position: vec4<f32>,
     @location(0) displayUv: vec2<f32>,
@@ -21,8 +30,13 @@ fn main(@builtin(vertex_index) VertexIndex : u32) -> Output {
     );
 
     var output: Output;
-    let scale = vec2(1.2, 1.1);
-    output.displayUv = uv[VertexIndex] * scale - vec2(-0.0, 0.05);
+    if (frame.pixelPerfect != 0u) {
+        output.displayUv = uv[VertexIndex];
+    } else {
+        // Map the display inside the bezel opening
+        let scale = vec2(1.2, 1.1);
+        output.displayUv = uv[VertexIndex] * scale - vec2(-0.0, 0.05);
+    }
     output.windowUv = uv[VertexIndex];
     output.position = vec4<f32>(pos[VertexIndex], 0.0, 1.0);
     return output;
@@ -0,0 +1,38 @@
+struct Screen {
+    size: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(0) @binding(1) var atlas: texture_2d<f32>;
+
+// Rectangles are in window pixels, the atlas rectangle in texels
+struct Quad {
+    @location(0) rect: vec4<f32>,
+    @location(1) atlasRect: vec4<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct Output {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texel: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32, quad: Quad) -> Output {
+    let corner = vec2(f32(vertex & 1u), f32(vertex >> 1u));
+    let px = quad.rect.xy + corner * quad.rect.zw;
+
+    var output: Output;
+    output.position = vec4(px / screen.size * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+    output.texel = quad.atlasRect.xy + corner * quad.atlasRect.zw;
+    output.color = quad.color;
+    return output;
+}
+
+// The color is premultiplied, the atlas holds the coverage
+@fragment
+fn fs_main(input: Output) -> @location(0) vec4<f32> {
+    let coverage = textureLoad(atlas, vec2<i32>(input.texel), 0).r;
+    return input.color * coverage;
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+// Frame timing statistics, averaged over roughly half a second so the overlay
+// stays readable.
+pub struct FrameStats {
+    last_frame: Instant,
+    window_start: Instant,
+    window_frames: u32,
+    window_instructions: u64,
+    pub fps: f32,
+    pub frame_time_ms: f32,
+    pub instructions_per_frame: f32,
+    pub instructions_per_second: f32,
+}
+
+impl FrameStats {
+    const WINDOW: Duration = Duration::from_millis(500);
+
+    pub fn new() -> Self {
+        let now = Instant::now();
+        FrameStats {
+            last_frame: now,
+            window_start: now,
+            window_frames: 0,
+            window_instructions: 0,
+            fps: 0.0,
+            frame_time_ms: 0.0,
+            instructions_per_frame: 0.0,
+            instructions_per_second: 0.0,
+        }
+    }
+
+    // Records a presented frame and the number of instructions executed for it.
+    // Returns the time since the previous frame and whether the averages were refreshed.
+    pub fn record_frame(&mut self, instructions: u64) -> (Duration, bool) {
+        self.record_frame_at(Instant::now(), instructions)
+    }
+
+    fn record_frame_at(&mut self, now: Instant, instructions: u64) -> (Duration, bool) {
+        let delta = now - self.last_frame;
+        self.last_frame = now;
+        self.window_frames += 1;
+        self.window_instructions += instructions;
+
+        let elapsed = now - self.window_start;
+        if elapsed < FrameStats::WINDOW {
+            return (delta, false);
+        }
+
+        let seconds = elapsed.as_secs_f32();
+        let frames = self.window_frames as f32;
+        self.fps = frames / seconds;
+        self.frame_time_ms = seconds * 1000.0 / frames;
+        self.instructions_per_frame = self.window_instructions as f32 / frames;
+        self.instructions_per_second = self.window_instructions as f32 / seconds;
+
+        self.window_start = now;
+        self.window_frames = 0;
+        self.window_instructions = 0;
+        (delta, true)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use super::FrameStats;
+
+#[test]
+fn test_averages() {
+    let mut stats = FrameStats::new();
+    let start = stats.window_start;
+    let frame = Duration::from_millis(20);
+
+    // 24 frames of 100 instructions fall short of the window
+    for n in 1..25 {
+        assert_eq!(stats.record_frame_at(start + frame * n, 100), (frame, false));
+    }
+    assert_eq!(stats.fps, 0.0);
+
+    // The 25th ends it: 25 frames and 2500 instructions in half a second
+    assert_eq!(stats.record_frame_at(start + frame * 25, 100), (frame, true));
+    assert!((stats.fps - 50.0).abs() < 0.01);
+    assert!((stats.frame_time_ms - 20.0).abs() < 0.01);
+    assert!((stats.instructions_per_frame - 100.0).abs() < 0.01);
+    assert!((stats.instructions_per_second - 5000.0).abs() < 0.1);
+
+    // The next window starts from scratch, the averages stay until it ends
+    let start = start + frame * 25;
+    let frame = Duration::from_millis(100);
+    for n in 1..5 {
+        assert_eq!(stats.record_frame_at(start + frame * n, 10), (frame, false));
+    }
+    assert!((stats.fps - 50.0).abs() < 0.01);
+    assert_eq!(stats.record_frame_at(start + frame * 5, 10), (frame, true));
+    assert!((stats.fps - 10.0).abs() < 0.01);
+    assert!((stats.frame_time_ms - 100.0).abs() < 0.01);
+    assert!((stats.instructions_per_frame - 10.0).abs() < 0.01);
+    assert!((stats.instructions_per_second - 100.0).abs() < 0.1);
+}
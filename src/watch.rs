@@ -0,0 +1,80 @@
+// Notices when the running ROM is rewritten, e.g. by an assembler, for --watch.
+// The file is polled a few times per second. A change counts once the
+// modification time and size stay the same for two polls, so a ROM that is
+// still being written isn't loaded half way.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// What happens to the machine when the ROM changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReloadMode {
+    // Reset with the new program
+    Reset,
+    // Write the changed bytes into memory and keep running, see `Chip8::patch_program`
+    Patch,
+}
+
+impl ReloadMode {
+    pub fn parse(s: &str) -> Option<ReloadMode> {
+        match s {
+            "reset" => Some(ReloadMode::Reset),
+            "patch" => Some(ReloadMode::Patch),
+            _ => None
+        }
+    }
+}
+
+type Stamp = Option<(SystemTime, u64)>;
+
+pub struct RomWatcher {
+    path: PathBuf,
+    stamp: Stamp,
+    // A change seen on the last poll that has to hold until the next
+    pending: Option<Stamp>,
+    next_poll: Instant,
+}
+
+impl RomWatcher {
+    pub fn new(path: &Path) -> RomWatcher {
+        RomWatcher { path: path.to_path_buf(), stamp: stamp(path), pending: None, next_poll: Instant::now() + POLL_INTERVAL }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Call every frame, returns the contents of the file once it changed
+    pub fn poll(&mut self) -> Option<io::Result<Vec<u8>>> {
+        let now = Instant::now();
+        if now < self.next_poll {
+            return None;
+        }
+        self.next_poll = now + POLL_INTERVAL;
+
+        let current = stamp(&self.path);
+        if current == self.stamp {
+            self.pending = None;
+            return None;
+        }
+        if self.pending != Some(current) {
+            self.pending = Some(current);
+            return None;
+        }
+
+        // A deleted file is waited for, editors may replace it by renaming
+        self.pending = None;
+        current?;
+        self.stamp = current;
+        Some(fs::read(&self.path))
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -0,0 +1,132 @@
+// JavaScript API of the web build. The page in www/ drives a `WebChip8` from
+// requestAnimationFrame and renders it with the same WGSL shaders as the
+// windowed frontend.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use wasm_bindgen::prelude::*;
+
+use chip8_rs::chip8::Chip8;
+use chip8_rs::crt::{self, CrtParams, Palette};
+
+#[wasm_bindgen]
+pub struct WebChip8 {
+    chip8: Chip8<'static>,
+}
+
+#[wasm_bindgen]
+impl WebChip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<WebChip8, JsError> {
+        check_rom(rom)?;
+        Ok(WebChip8 { chip8: Chip8::from_rom(rom.to_vec()) })
+    }
+
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        check_rom(rom)?;
+        self.chip8.load_program(rom.to_vec());
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.chip8.reset();
+    }
+
+    // One 60Hz frame: counts the timers down and runs `instructions` instructions.
+    // Faults and interpreter panics, e.g. a return with an empty stack, are thrown.
+    #[wasm_bindgen(js_name = stepFrame)]
+    pub fn step_frame(&mut self, instructions: usize) -> Result<(), JsError> {
+        self.chip8.tick_60hz();
+        match panic::catch_unwind(AssertUnwindSafe(|| self.chip8.step(instructions))) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(fault)) => Err(JsError::new(&fault.to_string())),
+            Err(payload) => {
+                let message = payload.downcast_ref::<String>().cloned()
+                    .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "Interpreter panicked".into());
+                Err(JsError::new(&message))
+            }
+        }
+    }
+
+    // Keys outside the 16 key keypad are ignored rather than thrown on, so the
+    // page can forward its key mapping without checking
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if key < 16 {
+            self.chip8.set_key_state(key, pressed);
+        }
+    }
+
+    // One byte per pixel, 255 for lit pixels, ready to upload as an r8unorm texture
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.chip8.pixels()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.chip8.framebuffer().width()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.chip8.framebuffer().height()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cycles(&self) -> f64 {
+        self.chip8.cycles() as f64
+    }
+}
+
+fn check_rom(rom: &[u8]) -> Result<(), JsError> {
+    Chip8::check_program_size(rom.len()).map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = vertexShader)]
+pub fn vertex_shader() -> String {
+    browser_wgsl(include_str!("../../src/shaders/display_vs.wgsl"))
+}
+
+#[wasm_bindgen(js_name = fragmentShader)]
+pub fn fragment_shader() -> String {
+    browser_wgsl(include_str!("../../src/shaders/display_fs.wgsl"))
+}
+
+// The shaders are written for the naga version wgpu 0.13 uses, which only
+// knows module scope constants as `let`. Current WGSL spells them `const`.
+fn browser_wgsl(source: &str) -> String {
+    source.lines()
+        .map(|line| match line.strip_prefix("let ") {
+            Some(rest) => format!("const {rest}\n"),
+            None => format!("{line}\n"),
+        })
+        .collect()
+}
+
+#[wasm_bindgen(js_name = paletteCount)]
+pub fn palette_count() -> usize {
+    Palette::ALL.len()
+}
+
+// Foreground then background color as 8 floats, for renderers without shaders
+#[wasm_bindgen(js_name = paletteColors)]
+pub fn palette_colors(palette: usize) -> Vec<f32> {
+    let (fg, bg) = Palette::ALL[palette % Palette::ALL.len()].colors();
+    fg.into_iter().chain(bg).collect()
+}
+
+// Contents of the `Params` uniform for the given palette with the default effect settings
+#[wasm_bindgen(js_name = crtUniform)]
+pub fn crt_uniform(palette: usize, enabled: bool) -> Vec<u8> {
+    let mut params = CrtParams { enabled, ..CrtParams::default() };
+    params.set_palette(Palette::ALL[palette % Palette::ALL.len()]);
+    params.to_uniform_bytes().to_vec()
+}
+
+// Contents of the `Frame` uniform. The page always renders with the bezel.
+#[wasm_bindgen(js_name = frameUniform)]
+pub fn frame_uniform(time: f32, delta_time: f32, width: f32, height: f32) -> Vec<u8> {
+    crt::frame_uniform_bytes(time, delta_time, width, height, false).to_vec()
+}
@@ -0,0 +1,74 @@
+// Run with `wasm-pack test --node` from the web directory
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::*;
+
+use chip8_web::{crt_uniform, fragment_shader, frame_uniform, palette_colors, palette_count, vertex_shader, WebChip8};
+
+const DRAW_ZERO: &[u8] = &[
+    0x60, 0x00,     // gp0 = 0
+    0xf0, 0x29,     // i = glyph for gp0
+    0xd0, 0x05,     // draw it at (gp0, gp0)
+    0x12, 0x06,     // loop
+];
+
+#[wasm_bindgen_test]
+fn test_step_frame_draws() {
+    let mut c8 = WebChip8::new(DRAW_ZERO).unwrap();
+    assert_eq!((c8.width(), c8.height()), (64, 32));
+    assert!(c8.framebuffer().iter().all(|px| *px == 0));
+
+    c8.step_frame(10).unwrap();
+    assert_eq!(c8.cycles(), 10.0);
+    let pixels = c8.framebuffer();
+    assert_eq!(pixels.len(), 64 * 32);
+    assert_eq!(pixels[..5], [255, 255, 255, 255, 0]);
+    assert_eq!(pixels[64..69], [255, 0, 0, 255, 0]);
+}
+
+#[wasm_bindgen_test]
+fn test_load_rom() {
+    let mut c8 = WebChip8::new(&[0x12, 0x00]).unwrap();
+    c8.step_frame(10).unwrap();
+    assert!(c8.framebuffer().iter().all(|px| *px == 0));
+
+    c8.load_rom(DRAW_ZERO).unwrap();
+    c8.step_frame(10).unwrap();
+    assert_eq!(c8.framebuffer()[0], 255);
+
+    assert!(c8.load_rom(&[0; 4096]).is_err());
+    assert!(WebChip8::new(&[0; 4096]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_set_key() {
+    let test_program: &[u8] = &[
+        0xe0, 0x9e,     // skip if key gp0 is pressed
+        0x12, 0x00,     // loop
+        0x61, 0x01,     // gp1 = 1
+        0xf1, 0x29,     // i = glyph for gp1
+        0xd0, 0x05,     // draw it
+        0x12, 0x0a,     // loop
+    ];
+
+    let mut c8 = WebChip8::new(test_program).unwrap();
+    c8.set_key(99, true);
+    c8.step_frame(10).unwrap();
+    assert!(c8.framebuffer().iter().all(|px| *px == 0));
+
+    c8.set_key(0, true);
+    c8.step_frame(10).unwrap();
+    assert_eq!(c8.framebuffer()[2], 255);
+}
+
+#[wasm_bindgen_test]
+fn test_render_data() {
+    assert!(vertex_shader().contains("@vertex"));
+    assert!(!fragment_shader().lines().any(|line| line.starts_with("let ")));
+    assert!(fragment_shader().contains("const HUD_SCALE"));
+
+    assert_eq!(palette_colors(0).len(), 8);
+    assert_eq!(palette_colors(palette_count()), palette_colors(0));
+    assert_eq!(crt_uniform(0, true).len(), 64);
+    assert_eq!(frame_uniform(1.0, 0.016, 640.0, 480.0).len(), 32);
+}
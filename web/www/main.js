@@ -0,0 +1,235 @@
+// Canvas frontend for the wasm build. Renders with the WGSL shaders of the
+// windowed frontend through WebGPU and falls back to a plain 2D canvas in the
+// palette colors where WebGPU is missing.
+import init, {
+    WebChip8, vertexShader, fragmentShader, crtUniform, frameUniform, paletteColors, paletteCount
+} from './pkg/chip8_web.js';
+
+// Paths are relative to this page, serve the repository root to reach them.
+// The canvas has the size of the bezel image like the window of the windowed frontend.
+const ROM_DIR = '../../roms/';
+const OVERLAY_URL = '../../assets/frame.png';
+const ROMS = ['trip8', 'particle', 'stars', 'blinky', 'breakout', 'airplane'];
+const INSTRUCTIONS_PER_FRAME = 10;
+
+async function fetchBytes(url) {
+    const response = await fetch(url);
+    if (!response.ok) {
+        throw new Error(`${url}: ${response.status}`);
+    }
+    return new Uint8Array(await response.arrayBuffer());
+}
+
+class WebGpuRenderer {
+    static async create(canvas, width, height) {
+        const adapter = navigator.gpu && await navigator.gpu.requestAdapter();
+        if (!adapter) {
+            return null;
+        }
+        const device = await adapter.requestDevice();
+        return new WebGpuRenderer(canvas, device, width, height, await WebGpuRenderer.loadOverlay(device));
+    }
+
+    // The bezel, or a transparent texel when it can't be loaded
+    static async loadOverlay(device) {
+        let bitmap = null;
+        try {
+            bitmap = await createImageBitmap(await (await fetch(OVERLAY_URL)).blob(), { premultiplyAlpha: 'none' });
+        } catch (e) {
+            console.warn(`No overlay: ${e}`);
+        }
+        const size = bitmap ? [bitmap.width, bitmap.height] : [1, 1];
+        const texture = device.createTexture({
+            size,
+            format: 'rgba8unorm',
+            usage: GPUTextureUsage.TEXTURE_BINDING | GPUTextureUsage.COPY_DST | GPUTextureUsage.RENDER_ATTACHMENT,
+        });
+        if (bitmap) {
+            device.queue.copyExternalImageToTexture({ source: bitmap }, { texture }, size);
+        }
+        return texture;
+    }
+
+    constructor(canvas, device, width, height, overlay) {
+        this.device = device;
+        this.width = width;
+        this.height = height;
+        this.context = canvas.getContext('webgpu');
+        const format = navigator.gpu.getPreferredCanvasFormat();
+        this.context.configure({ device, format, alphaMode: 'premultiplied' });
+
+        this.pixels = device.createTexture({
+            size: [width, height],
+            format: 'r8unorm',
+            usage: GPUTextureUsage.TEXTURE_BINDING | GPUTextureUsage.COPY_DST,
+        });
+        // The menu is only drawn by the windowed frontend, an empty texel disables it
+        const menu = device.createTexture({
+            size: [1, 1],
+            format: 'r8unorm',
+            usage: GPUTextureUsage.TEXTURE_BINDING,
+        });
+        this.params = device.createBuffer({ size: 64, usage: GPUBufferUsage.UNIFORM | GPUBufferUsage.COPY_DST });
+        this.frame = device.createBuffer({ size: 32, usage: GPUBufferUsage.UNIFORM | GPUBufferUsage.COPY_DST });
+
+        this.pipeline = device.createRenderPipeline({
+            layout: 'auto',
+            vertex: { module: device.createShaderModule({ code: vertexShader() }), entryPoint: 'main' },
+            fragment: {
+                module: device.createShaderModule({ code: fragmentShader() }),
+                entryPoint: 'main',
+                targets: [{
+                    format,
+                    blend: {
+                        color: { srcFactor: 'one', dstFactor: 'one-minus-src-alpha' },
+                        alpha: { srcFactor: 'one', dstFactor: 'one-minus-src-alpha' },
+                    },
+                }],
+            },
+            primitive: { topology: 'triangle-strip' },
+        });
+
+        const sampler = device.createSampler({ magFilter: 'nearest' });
+        this.bindGroup = device.createBindGroup({
+            layout: this.pipeline.getBindGroupLayout(0),
+            entries: [
+                { binding: 0, resource: sampler },
+                { binding: 1, resource: this.pixels.createView() },
+                { binding: 2, resource: sampler },
+                { binding: 3, resource: overlay.createView() },
+                { binding: 4, resource: { buffer: this.params } },
+                { binding: 5, resource: { buffer: this.frame } },
+                { binding: 6, resource: menu.createView() },
+            ],
+        });
+    }
+
+    setPalette(palette, crtEnabled) {
+        this.device.queue.writeBuffer(this.params, 0, crtUniform(palette, crtEnabled));
+    }
+
+    draw(pixels, time, deltaTime) {
+        const queue = this.device.queue;
+        const canvas = this.context.canvas;
+        queue.writeTexture({ texture: this.pixels }, pixels, { bytesPerRow: this.width }, [this.width, this.height]);
+        queue.writeBuffer(this.frame, 0, frameUniform(time, deltaTime, canvas.width, canvas.height));
+
+        const encoder = this.device.createCommandEncoder();
+        const pass = encoder.beginRenderPass({
+            colorAttachments: [{
+                view: this.context.getCurrentTexture().createView(),
+                clearValue: { r: 0, g: 0, b: 0, a: 0 },
+                loadOp: 'clear',
+                storeOp: 'store',
+            }],
+        });
+        pass.setPipeline(this.pipeline);
+        pass.setBindGroup(0, this.bindGroup);
+        pass.draw(4);
+        pass.end();
+        queue.submit([encoder.finish()]);
+    }
+}
+
+// Flat pixels in the palette colors, without any of the CRT effects
+class CanvasRenderer {
+    constructor(canvas, width, height) {
+        this.context = canvas.getContext('2d');
+        this.context.imageSmoothingEnabled = false;
+        this.image = new ImageData(width, height);
+        this.scratch = new OffscreenCanvas(width, height);
+    }
+
+    setPalette(palette) {
+        const to8 = (c) => Math.round(Math.min(c, 1) * 255);
+        const colors = paletteColors(palette);
+        this.fg = Array.from(colors.slice(0, 3), to8);
+        this.bg = Array.from(colors.slice(4, 7), to8);
+    }
+
+    draw(pixels) {
+        const data = this.image.data;
+        pixels.forEach((px, i) => {
+            const c = px ? this.fg : this.bg;
+            data.set([c[0], c[1], c[2], 255], i * 4);
+        });
+        this.scratch.getContext('2d').putImageData(this.image, 0, 0);
+        const canvas = this.context.canvas;
+        this.context.drawImage(this.scratch, 0, 0, canvas.width, canvas.height);
+    }
+}
+
+async function main() {
+    await init();
+    const canvas = document.getElementById('screen');
+    const errorText = document.getElementById('error');
+    const romSelect = document.getElementById('rom');
+
+    const chip8 = new WebChip8(await fetchBytes(ROM_DIR + ROMS[0] + '.rom'));
+    const renderer = await WebGpuRenderer.create(canvas, chip8.width, chip8.height)
+        ?? new CanvasRenderer(canvas, chip8.width, chip8.height);
+
+    let palette = 0;
+    let crtEnabled = true;
+    let halted = false;
+    renderer.setPalette(palette, crtEnabled);
+
+    const load = (rom) => {
+        chip8.loadRom(rom);
+        halted = false;
+        errorText.textContent = '';
+    };
+
+    for (const name of ROMS) {
+        romSelect.add(new Option(name, name));
+    }
+    romSelect.onchange = async () => load(await fetchBytes(ROM_DIR + romSelect.value + '.rom'));
+    document.getElementById('file').onchange = async (e) => {
+        load(new Uint8Array(await e.target.files[0].arrayBuffer()));
+    };
+
+    const onKey = (e, pressed) => {
+        if (e.target !== document.body) {
+            return;
+        }
+        const key = parseInt(e.key, 16);
+        if (e.key.length === 1 && !isNaN(key)) {
+            chip8.setKey(key, pressed);
+        } else if (pressed && e.key === 'Tab') {
+            chip8.reset();
+            halted = false;
+            errorText.textContent = '';
+        } else if (pressed && e.key === 'F1') {
+            crtEnabled = !crtEnabled;
+            renderer.setPalette(palette, crtEnabled);
+        } else if (pressed && e.key === 'F2') {
+            palette = (palette + 1) % paletteCount();
+            renderer.setPalette(palette, crtEnabled);
+        } else {
+            return;
+        }
+        e.preventDefault();
+    };
+    window.addEventListener('keydown', (e) => onKey(e, true));
+    window.addEventListener('keyup', (e) => onKey(e, false));
+
+    const start = performance.now();
+    let last = start;
+    const frame = (now) => {
+        // A faulted machine stays halted until it is reset, like the windowed frontend
+        if (!halted) {
+            try {
+                chip8.stepFrame(INSTRUCTIONS_PER_FRAME);
+            } catch (e) {
+                errorText.textContent = e.message;
+                halted = true;
+            }
+        }
+        renderer.draw(chip8.framebuffer(), (now - start) / 1000, (now - last) / 1000);
+        last = now;
+        requestAnimationFrame(frame);
+    };
+    requestAnimationFrame(frame);
+}
+
+main();